@@ -110,6 +110,18 @@ impl Shell {
         }
     }
 
+    /// Prints a diagnostic as the compiler rendered it. In quiet mode only
+    /// errors are printed.
+    pub fn diagnostic(&mut self, rendered: &str, error: bool) -> CargoResult<()> {
+        match self.verbosity {
+            Verbosity::Quiet if !error => Ok(()),
+            _ => {
+                write!(self.err.as_write(), "{}", rendered)?;
+                Ok(())
+            }
+        }
+    }
+
     pub fn set_verbosity(&mut self, verbosity: Verbosity) {
         self.verbosity = verbosity;
     }
@@ -142,6 +154,15 @@ impl Shell {
             ShellOut::Write(_) => ColorChoice::Never,
         }
     }
+
+    /// Whether output written to stderr should be colored.
+    pub fn supports_color(&self) -> bool {
+        match self.color_choice() {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::CargoAuto => atty::is(atty::Stream::Stderr),
+        }
+    }
 }
 
 impl ShellOut {
@@ -24,6 +24,7 @@ extern crate jobserver;
 extern crate libc;
 extern crate libgit2_sys;
 extern crate num_cpus;
+extern crate ring;
 extern crate semver;
 extern crate serde;
 extern crate serde_ignored;
@@ -32,6 +33,7 @@ extern crate tar;
 extern crate tempdir;
 extern crate termcolor;
 extern crate toml;
+extern crate untrusted;
 extern crate url;
 
 use std::fmt;
@@ -1,21 +1,36 @@
+use std::collections::HashSet;
 use std::default::Default;
 use std::fs;
 use std::path::Path;
 
-use core::{Profiles, Workspace};
-use util::Config;
+use core::{Package, PackageSet, Profiles, Resolve, Workspace};
+use util::{paths, Config};
 use util::errors::{CargoResult, CargoResultExt};
-use ops::{self, Context, BuildConfig, Kind, Unit};
+use ops::{self, Context, BuildConfig, Kind, TargetConfig, Unit};
 
 pub struct CleanOptions<'a> {
     pub spec: &'a [String],
     pub target: Option<&'a str>,
     pub config: &'a Config,
     pub release: bool,
+    /// Only remove the artifacts which the workspace no longer builds.
+    pub stale: bool,
+    /// Report what `stale` would remove without removing anything.
+    pub dry_run: bool,
 }
 
 /// Cleans the project from build artifacts.
 pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
+    if opts.dry_run && !opts.stale {
+        bail!("`--dry-run` can only be used together with `--stale`")
+    }
+    if opts.stale {
+        if !opts.spec.is_empty() {
+            bail!("`--stale` can't be used together with `--package`")
+        }
+        return clean_stale(ws, opts);
+    }
+
     let target_dir = ws.target_dir();
 
     // If we have a spec, then we need to delete some packages, otherwise, just
@@ -30,17 +45,8 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
 
     let (packages, resolve) = ops::resolve_ws(ws)?;
 
-    let profiles = ws.profiles();
-    let host_triple = opts.config.rustc()?.host.clone();
-    let mut cx = Context::new(ws, &resolve, &packages, opts.config,
-                                   BuildConfig {
-                                       host_triple: host_triple,
-                                       requested_target: opts.target.map(|s| s.to_owned()),
-                                       release: opts.release,
-                                       jobs: 1,
-                                       ..BuildConfig::default()
-                                   },
-                                   profiles)?;
+    let mut cx = context(ws, &resolve, &packages, opts)?;
+    let kinds = kinds(&cx);
     let mut units = Vec::new();
 
     for spec in opts.spec {
@@ -49,25 +55,7 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
         let pkg = packages.get(&pkgid)?;
 
         // Generate all relevant `Unit` targets for this package
-        for target in pkg.targets() {
-            for kind in [Kind::Host, Kind::Target].iter() {
-                let Profiles {
-                    ref release, ref dev, ref test, ref bench, ref doc,
-                    ref custom_build, ref test_deps, ref bench_deps, ref check,
-                    ref doctest,
-                } = *profiles;
-                let profiles = [release, dev, test, bench, doc, custom_build,
-                                test_deps, bench_deps, check, doctest];
-                for profile in profiles.iter() {
-                    units.push(Unit {
-                        pkg: &pkg,
-                        target: target,
-                        profile: profile,
-                        kind: *kind,
-                    });
-                }
-            }
-        }
+        units.extend(all_units(pkg, ws.profiles(), &kinds));
     }
 
     cx.probe_target_info(&units)?;
@@ -94,6 +82,189 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
     Ok(())
 }
 
+/// Removes the artifacts in the current layout whose metadata hash doesn't
+/// belong to any package of the workspace's resolve.
+///
+/// Every unit's artifacts share the hash of its fingerprint directory, which
+/// records the id of the package they were built from. A hash is kept as long
+/// as that package, at that version, is still part of the resolve, whatever
+/// features or profile it was built with.
+fn clean_stale(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
+    let (packages, resolve) = ops::resolve_ws(ws)?;
+    let live_ids = resolve.iter().map(|id| id.to_string()).collect::<HashSet<_>>();
+    let live_names = resolve.iter().map(|id| id.name().to_string()).collect::<HashSet<_>>();
+    let cx = context(ws, &resolve, &packages, opts)?;
+
+    let mut live = HashSet::new();
+    let mut dirs = Vec::new();
+    for kind in kinds(&cx) {
+        let layout = cx.layout(kind);
+        let fingerprints = layout.fingerprint();
+        if let Ok(entries) = fs::read_dir(fingerprints) {
+            for entry in entries {
+                let entry = entry.chain_err(|| {
+                    format!("failed to read directory `{}`", fingerprints.display())
+                })?;
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let hash = match artifact_hash(&name) {
+                    Some(hash) => hash.to_string(),
+                    None => continue,
+                };
+                let is_live = match paths::read(&ops::package_id_loc(&entry.path())) {
+                    Ok(id) => live_ids.contains(id.trim()),
+                    // Built before package ids were recorded, so only the
+                    // package's name is known.
+                    Err(_) => live_names.contains(&name[..name.len() - hash.len() - 1]),
+                };
+                if is_live {
+                    live.insert(hash);
+                }
+            }
+        }
+        dirs.push(layout.deps().to_path_buf());
+        dirs.push(layout.examples().to_path_buf());
+        dirs.push(fingerprints.to_path_buf());
+        dirs.push(layout.build().to_path_buf());
+    }
+
+    let mut stale = Vec::new();
+    for dir in dirs.iter() {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let entry = entry.chain_err(|| {
+                format!("failed to read directory `{}`", dir.display())
+            })?;
+            let is_stale = match entry.file_name().to_str().and_then(artifact_hash) {
+                Some(hash) => !live.contains(hash),
+                None => false,
+            };
+            if is_stale {
+                stale.push(entry.path());
+            }
+        }
+    }
+    stale.sort();
+
+    let mut size = 0;
+    for path in stale.iter() {
+        size += disk_usage(path);
+        opts.config.shell().verbose(|s| s.status("Removing", path.display()))?;
+        if !opts.dry_run {
+            rm_rf(path)?;
+        }
+    }
+
+    let summary = format!("{} stale files, {}", stale.len(), format_size(size));
+    if opts.dry_run {
+        opts.config.shell().status("Summary", format!("{} would be removed", summary))
+    } else {
+        opts.config.shell().status("Removed", summary)
+    }
+}
+
+/// Returns the metadata hash in the name of an artifact, fingerprint directory
+/// or build script directory, such as `3f1c2b1e9d0a7c55` in
+/// `libfoo-3f1c2b1e9d0a7c55.rlib`.
+fn artifact_hash(name: &str) -> Option<&str> {
+    let stem = name.split('.').next().unwrap();
+    let hash = match stem.rfind('-') {
+        Some(i) => &stem[i + 1..],
+        None => return None,
+    };
+    if hash.len() == 16 && hash.chars().all(|c| c.is_digit(16) && !c.is_uppercase()) {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    let m = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return 0,
+    };
+    if !m.is_dir() {
+        return m.len()
+    }
+    match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| disk_usage(&e.path())).sum(),
+        Err(_) => 0,
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes)
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
+
+/// Creates a context for the layout that `opts` refers to.
+fn context<'a, 'cfg>(ws: &'a Workspace<'cfg>,
+                     resolve: &'a Resolve,
+                     packages: &'a PackageSet<'cfg>,
+                     opts: &CleanOptions) -> CargoResult<Context<'a, 'cfg>> {
+    let host_triple = opts.config.rustc()?.host.clone();
+    let targets = opts.target.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let requested_targets = ops::requested_targets(opts.config, &targets)?;
+    Context::new(ws, resolve, packages, opts.config,
+                 BuildConfig {
+                     host_triple: host_triple,
+                     targets: requested_targets.iter().map(|_| {
+                         TargetConfig::default()
+                     }).collect(),
+                     requested_targets: requested_targets,
+                     release: opts.release,
+                     jobs: 1,
+                     ..BuildConfig::default()
+                 },
+                 ws.profiles())
+}
+
+/// Returns the kinds of units whose artifacts are in the layouts of `cx`.
+fn kinds(cx: &Context) -> Vec<Kind> {
+    let targets = cx.build_config.requested_targets.len();
+    Some(Kind::Host).into_iter().chain((0..targets).map(Kind::Target)).collect()
+}
+
+/// Returns a unit for every target of `pkg` in every profile and kind.
+fn all_units<'a>(pkg: &'a Package, profiles: &'a Profiles, kinds: &[Kind]) -> Vec<Unit<'a>> {
+    let Profiles {
+        ref release, ref dev, ref test, ref bench, ref doc,
+        ref custom_build, ref test_deps, ref bench_deps, ref check,
+        ref doctest,
+    } = *profiles;
+    let profiles = [release, dev, test, bench, doc, custom_build,
+                    test_deps, bench_deps, check, doctest];
+    let mut units = Vec::new();
+    for target in pkg.targets() {
+        for kind in kinds.iter() {
+            for profile in profiles.iter() {
+                units.push(Unit {
+                    pkg: pkg,
+                    target: target,
+                    profile: profile,
+                    kind: *kind,
+                });
+            }
+        }
+    }
+    units
+}
+
 fn rm_rf(path: &Path) -> CargoResult<()> {
     let m = fs::metadata(path);
     if m.as_ref().map(|s| s.is_dir()).unwrap_or(false) {
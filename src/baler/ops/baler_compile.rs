@@ -24,13 +24,14 @@
 
 use std::collections::{HashMap, HashSet};
 use std::default::Default;
-use std::path::PathBuf;
+use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use core::{Source, Package, Target};
 use core::{Profile, TargetKind, Profiles, Workspace, PackageId, PackageIdSpec};
 use core::resolver::Resolve;
-use ops::{self, BuildOutput, Executor, DefaultExecutor};
+use ops::{self, BuildOutput, Executor, DefaultExecutor, LinkType, ProcessExecutor};
 use util::config::Config;
 use util::{CargoResult, profile};
 
@@ -39,8 +40,9 @@ pub struct CompileOptions<'a> {
     pub config: &'a Config,
     /// Number of concurrent jobs to use.
     pub jobs: Option<u32>,
-    /// The target platform to compile for (example: `i686-unknown-linux-gnu`).
-    pub target: Option<&'a str>,
+    /// The target platforms to compile for (example: `i686-unknown-linux-gnu`).
+    /// If empty, `build.target` or the host is used.
+    pub targets: &'a [String],
     /// Extra features to build for the root package
     pub features: &'a [String],
     /// Flag whether all available features should be built for the root package
@@ -63,6 +65,15 @@ pub struct CompileOptions<'a> {
     /// The specified target will be compiled with all the available arguments,
     /// note that this only accounts for the *final* invocation of rustc
     pub target_rustc_args: Option<&'a [String]>,
+    /// Whether to print the build plan as JSON instead of building anything.
+    pub build_plan: bool,
+    /// Whether to write a report of how long each unit took to build.
+    pub timings: bool,
+    /// Whether to keep building the units which don't depend on one which
+    /// failed to build.
+    pub keep_going: bool,
+    /// Directory the top-level artifacts are copied into once they're built.
+    pub out_dir: Option<&'a Path>,
 }
 
 impl<'a> CompileOptions<'a> {
@@ -71,7 +82,7 @@ impl<'a> CompileOptions<'a> {
         CompileOptions {
             config: config,
             jobs: None,
-            target: None,
+            targets: &[],
             features: &[],
             all_features: false,
             no_default_features: false,
@@ -82,6 +93,10 @@ impl<'a> CompileOptions<'a> {
             message_format: MessageFormat::Human,
             target_rustdoc_args: None,
             target_rustc_args: None,
+            build_plan: false,
+            timings: false,
+            keep_going: false,
+            out_dir: None,
         }
     }
 }
@@ -97,9 +112,15 @@ pub enum CompileMode {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum MessageFormat {
     Human,
-    Json
+    Json,
+    /// Like `Human`, but with each diagnostic shortened to a single line.
+    Short,
+    /// Like `Json`, but with diagnostics printed as rustc renders them rather
+    /// than as `compiler-message`s.
+    JsonRenderDiagnostics,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -169,7 +190,11 @@ pub enum CompileFilter<'a> {
 
 pub fn compile<'a>(ws: &Workspace<'a>, options: &CompileOptions<'a>)
                    -> CargoResult<ops::Compilation<'a>> {
-    compile_with_exec(ws, options, Arc::new(DefaultExecutor))
+    let exec: Arc<Executor> = match ProcessExecutor::from_config(options.config)? {
+        Some(exec) => Arc::new(exec),
+        None => Arc::new(DefaultExecutor),
+    };
+    compile_with_exec(ws, options, exec)
 }
 
 pub fn compile_with_exec<'a>(ws: &Workspace<'a>,
@@ -189,15 +214,13 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
                       options: &CompileOptions<'a>,
                       exec: Arc<Executor>)
                       -> CargoResult<ops::Compilation<'a>> {
-    let CompileOptions { config, jobs, target, spec, features,
-                         all_features, no_default_features,
-                         release, mode, message_format,
-                         ref filter,
+    let CompileOptions { config, jobs, targets: requested_targets, spec,
+                         features, all_features, no_default_features,
+                         release, mode, message_format, build_plan, timings,
+                         keep_going, out_dir, ref filter,
                          ref target_rustdoc_args,
                          ref target_rustc_args } = *options;
 
-    let target = target.map(|s| s.to_string());
-
     if jobs == Some(0) {
         bail!("jobs must be at least 1")
     }
@@ -289,10 +312,17 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
 
     let mut ret = {
         let _p = profile::start("compiling");
-        let mut build_config = scrape_build_config(config, jobs, target)?;
+        let mut build_config = scrape_build_config(config, jobs, requested_targets)?;
         build_config.release = release;
         build_config.test = mode == CompileMode::Test || mode == CompileMode::Bench;
-        build_config.json_messages = message_format == MessageFormat::Json;
+        build_config.json_messages = message_format == MessageFormat::Json ||
+                                     message_format == MessageFormat::JsonRenderDiagnostics;
+        build_config.short_diagnostics = message_format == MessageFormat::Short;
+        build_config.render_diagnostics = message_format == MessageFormat::JsonRenderDiagnostics;
+        build_config.build_plan = build_plan;
+        build_config.timings = timings;
+        build_config.keep_going = keep_going;
+        build_config.out_dir = out_dir.map(|d| d.to_path_buf());
         if let CompileMode::Doc { deps } = mode {
             build_config.doc_all = deps;
         }
@@ -631,13 +661,16 @@ fn generate_targets<'a>(pkg: &'a Package,
 /// configured options are:
 ///
 /// * build.jobs
-/// * build.target
+/// * build.target (a triple, or a list of them)
+/// * build.freshness
+/// * build.shared-cache
+/// * build.shared-cache-max-size
 /// * target.$target.ar
 /// * target.$target.linker
 /// * target.$target.libfoo.metadata
 fn scrape_build_config(config: &Config,
                        jobs: Option<u32>,
-                       target: Option<String>)
+                       targets: &[String])
                        -> CargoResult<ops::BuildConfig> {
     if jobs.is_some() && config.jobserver_from_env().is_some() {
         config.shell().warn("a `-j` argument was passed to Cargo but Cargo is \
@@ -659,22 +692,86 @@ fn scrape_build_config(config: &Config,
         None => None,
     };
     let jobs = jobs.or(cfg_jobs).unwrap_or(::num_cpus::get() as u32);
-    let cfg_target = config.get_string("build.target")?.map(|s| s.val);
-    let target = target.or(cfg_target);
+    let targets = requested_targets(config, targets)?;
+    let content_freshness = match config.get_string("build.freshness")? {
+        Some(ref v) if v.val == "mtime" => false,
+        Some(ref v) if v.val == "content" => true,
+        Some(v) => {
+            bail!("build.freshness must be `mtime` or `content`, but found `{}` in {}",
+                  v.val, v.definition)
+        }
+        None => false,
+    };
+    let shared_cache = match config.get_string("build.shared-cache")? {
+        Some(ref v) if v.val.starts_with("~/") => {
+            match env::home_dir() {
+                Some(home) => Some(home.join(&v.val[2..])),
+                None => bail!("build.shared-cache in {} refers to the home \
+                               directory, but it couldn't be found",
+                              v.definition),
+            }
+        }
+        Some(_) => config.get_path("build.shared-cache")?.map(|v| v.val),
+        None => None,
+    };
+    let shared_cache_max_size = match config.get_i64("build.shared-cache-max-size")? {
+        Some(v) => {
+            if v.val < 0 {
+                bail!("build.shared-cache-max-size must not be negative, \
+                       but found {} in {}", v.val, v.definition)
+            }
+            v.val as u64 * 1024 * 1024
+        }
+        None => 5 * 1024 * 1024 * 1024,
+    };
     let mut base = ops::BuildConfig {
         host_triple: config.rustc()?.host.clone(),
-        requested_target: target.clone(),
+        requested_targets: targets,
         jobs: jobs,
+        pipelining: config.get_bool("build.pipelining")?.map(|v| v.val).unwrap_or(true),
+        replay_warnings: config.get_bool("build.replay-warnings")?
+                               .map(|v| v.val).unwrap_or(false),
+        content_freshness: content_freshness,
+        shared_cache: shared_cache,
+        shared_cache_max_size: shared_cache_max_size,
         ..Default::default()
     };
     base.host = scrape_target_config(config, &base.host_triple)?;
-    base.target = match target.as_ref() {
-        Some(triple) => scrape_target_config(config, &triple)?,
-        None => base.host.clone(),
-    };
+    for triple in base.requested_targets.iter() {
+        base.targets.push(scrape_target_config(config, ops::target_short_name(triple))?);
+    }
     Ok(base)
 }
 
+/// Returns the target triples to build for: `targets` if any were passed on
+/// the command line, otherwise those in `build.target`.
+pub fn requested_targets(config: &Config, targets: &[String])
+                         -> CargoResult<Vec<String>> {
+    // Custom target specifications are passed to rustc by path, and rustc
+    // is run in each package's directory rather than the current one. Those
+    // on the command line are relative to the current directory, and those in
+    // `build.target` to the config file defining them.
+    let (mut targets, root) = if targets.is_empty() {
+        match config.get_list_or_split_string("build.target")? {
+            Some(v) => {
+                let root = v.definition.root(config).to_path_buf();
+                (v.val, root)
+            }
+            None => (Vec::new(), config.cwd().to_path_buf()),
+        }
+    } else {
+        (targets.to_vec(), config.cwd().to_path_buf())
+    };
+    for target in targets.iter_mut() {
+        if ops::is_target_spec(target) {
+            *target = root.join(&target).display().to_string();
+        }
+    }
+    let mut seen = HashSet::new();
+    targets.retain(|t| seen.insert(t.clone()));
+    Ok(targets)
+}
+
 fn scrape_target_config(config: &Config, triple: &str)
                         -> CargoResult<ops::TargetConfig> {
 
@@ -700,6 +797,8 @@ fn scrape_target_config(config: &Config, triple: &str)
             library_paths: Vec::new(),
             library_links: Vec::new(),
             cfgs: Vec::new(),
+            check_cfgs: Vec::new(),
+            linker_args: Vec::new(),
             env: Vec::new(),
             metadata: Vec::new(),
             rerun_if_changed: Vec::new(),
@@ -740,6 +839,29 @@ fn scrape_target_config(config: &Config, triple: &str)
                     let list = value.list(&k)?;
                     output.cfgs.extend(list.iter().map(|v| v.0.clone()));
                 }
+                "rustc-check-cfg" => {
+                    let list = value.list(&k)?;
+                    output.check_cfgs.extend(list.iter().map(|v| v.0.clone()));
+                }
+                "rustc-link-arg" |
+                "rustc-link-arg-bins" |
+                "rustc-cdylib-link-arg" => {
+                    let link_type = match &k[..] {
+                        "rustc-link-arg" => LinkType::All,
+                        "rustc-link-arg-bins" => LinkType::Bin,
+                        _ => LinkType::Cdylib,
+                    };
+                    let list = value.list(&k)?;
+                    output.linker_args.extend(list.iter().map(|v| {
+                        (link_type, v.0.clone())
+                    }));
+                }
+                "metadata" => {
+                    for (name, val) in value.table(&k)?.0 {
+                        let val = val.string(name)?.0;
+                        output.metadata.push((name.clone(), val.to_string()));
+                    }
+                }
                 "rustc-env" => {
                     for (name, val) in value.table(&k)?.0 {
                         let val = val.string(name)?.0;
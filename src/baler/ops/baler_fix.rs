@@ -0,0 +1,353 @@
+//! Implementation of `baler fix`, which applies the fixes rustc suggests for
+//! its diagnostics to the sources of the workspace's members.
+//!
+//! The workspace is checked with an `Executor` which reads the JSON
+//! diagnostics of every member's units. The suggestions rustc marks as
+//! `MachineApplicable` are applied, as long as they don't overlap one another,
+//! and the workspace is checked again until nothing is left to fix.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde_json::{self, Value};
+
+use core::{PackageId, Workspace};
+use sources::PathSource;
+use util::{internal, paths, ProcessBuilder};
+use util::errors::CargoResult;
+use ops::{self, CompileOptions, Executor, Unit};
+use super::baler_package::check_not_dirty;
+
+/// How many times the workspace is fixed before giving up on reaching a point
+/// where there's nothing left to fix.
+const MAX_PASSES: usize = 4;
+
+pub struct FixOptions<'a> {
+    pub compile_opts: CompileOptions<'a>,
+    /// Whether files with uncommitted changes may be fixed.
+    pub allow_dirty: bool,
+}
+
+/// A replacement of the bytes `start..end` of a file.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+struct Replacement {
+    file: PathBuf,
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// The replacements making up one suggestion, which are applied together or
+/// not at all.
+type Suggestion = Vec<Replacement>;
+
+/// Checks the workspace and applies the fixes rustc suggests, until there's
+/// nothing left to fix.
+pub fn fix(ws: &Workspace, opts: &FixOptions) -> CargoResult<()> {
+    let config = ws.config();
+    if !opts.allow_dirty {
+        for member in ws.members() {
+            let mut src = PathSource::new(member.root(),
+                                          member.package_id().source_id(),
+                                          config);
+            src.update()?;
+            check_not_dirty(member, &src)?;
+        }
+    }
+
+    let members = ws.members().map(|p| p.package_id().clone()).collect::<HashSet<_>>();
+    let roots = ws.members().map(|p| p.root().to_path_buf()).collect::<Vec<_>>();
+    let target_dir = ws.target_dir().into_path_unlocked();
+
+    let mut fixed = BTreeMap::new();
+    // The contents of the files fixed by the last pass before it, and how
+    // many fixes it made to each, for when the fixes break the build.
+    let mut previous = BTreeMap::new();
+
+    let new_exec = || Arc::new(FixExecutor {
+        members: members.clone(),
+        cwd: config.cwd().to_path_buf(),
+        suggestions: Mutex::new(HashSet::new()),
+    });
+
+    // The last pass only checks that the fixes before it still compile.
+    for pass in 0..MAX_PASSES + 1 {
+        let exec = new_exec();
+        if let Err(e) = ops::compile_with_exec(ws, &opts.compile_opts, exec.clone()) {
+            if previous.is_empty() {
+                return Err(e)
+            }
+            // Revert the files fixed by the last pass one at a time, checking
+            // again after each, so the fixes to the others are kept once the
+            // code compiles again.
+            let mut reverting = mem::replace(&mut previous, BTreeMap::new()).into_iter();
+            while let Some((file, (contents, count))) = reverting.next() {
+                paths::write(&file, contents.as_bytes())?;
+                let remaining = fixed.remove(&file).unwrap_or(0) - count;
+                if remaining > 0 {
+                    fixed.insert(file.clone(), remaining);
+                }
+                config.shell().warn(format!("the code no longer compiled after fixing `{}`, \
+                                             so the last fixes to it were reverted",
+                                            relative(config.cwd(), &file)))?;
+                if reverting.len() == 0 {
+                    break
+                }
+                if ops::compile_with_exec(ws, &opts.compile_opts, new_exec()).is_ok() {
+                    break
+                }
+            }
+            break
+        }
+        previous.clear();
+        if pass == MAX_PASSES {
+            break
+        }
+
+        let suggestions = mem::replace(&mut *exec.suggestions.lock().unwrap(), HashSet::new());
+        let suggestions = suggestions.into_iter().filter(|s| {
+            s.iter().all(|r| {
+                !r.file.starts_with(&target_dir) &&
+                    roots.iter().any(|root| r.file.starts_with(root))
+            })
+        }).collect();
+        let edits = choose_replacements(suggestions);
+        if edits.is_empty() {
+            break
+        }
+
+        for (file, (replacements, count)) in edits {
+            let contents = paths::read(&file)?;
+            let new_contents = apply_replacements(&file, &contents, &replacements)?;
+            paths::write(&file, new_contents.as_bytes())?;
+            *fixed.entry(file.clone()).or_insert(0) += count;
+            previous.insert(file, (contents, count));
+        }
+    }
+
+    for (file, count) in fixed {
+        config.shell().status("Fixed", format!("{} ({} {})",
+                                               relative(config.cwd(), &file), count,
+                                               if count == 1 { "fix" } else { "fixes" }))?;
+    }
+    Ok(())
+}
+
+/// Picks the suggestions which don't overlap one picked before them, and
+/// groups their replacements by file, along with the number of suggestions
+/// touching each file.
+fn choose_replacements(suggestions: BTreeSet<Suggestion>)
+                       -> BTreeMap<PathBuf, (Vec<Replacement>, usize)> {
+    let mut edits: BTreeMap<PathBuf, (Vec<Replacement>, usize)> = BTreeMap::new();
+    for suggestion in suggestions {
+        let overlaps = suggestion.iter().any(|r| {
+            edits.get(&r.file).map_or(false, |&(ref picked, _)| {
+                picked.iter().any(|p| overlap(p, r))
+            })
+        });
+        if overlaps {
+            continue
+        }
+        let mut files = BTreeSet::new();
+        for replacement in suggestion {
+            files.insert(replacement.file.clone());
+            edits.entry(replacement.file.clone())
+                 .or_insert((Vec::new(), 0)).0.push(replacement);
+        }
+        for file in files {
+            edits.get_mut(&file).unwrap().1 += 1;
+        }
+    }
+    edits
+}
+
+fn overlap(a: &Replacement, b: &Replacement) -> bool {
+    (a.start < b.end && b.start < a.end) || a.start == b.start
+}
+
+/// Returns `contents` with the non-overlapping `replacements` made to it.
+fn apply_replacements(file: &Path,
+                      contents: &str,
+                      replacements: &[Replacement]) -> CargoResult<String> {
+    let mut replacements = replacements.iter().collect::<Vec<_>>();
+    replacements.sort_by_key(|r| r.start);
+
+    let mut ret = String::with_capacity(contents.len());
+    let mut pos = 0;
+    for r in replacements {
+        if r.start < pos || r.end < r.start || r.end > contents.len() ||
+           !contents.is_char_boundary(r.start) || !contents.is_char_boundary(r.end) {
+            return Err(internal(format!("rustc suggested replacing bytes {}..{} of `{}`, \
+                                         which don't fit its contents",
+                                        r.start, r.end, file.display())))
+        }
+        ret.push_str(&contents[pos..r.start]);
+        ret.push_str(&r.text);
+        pos = r.end;
+    }
+    ret.push_str(&contents[pos..]);
+    Ok(ret)
+}
+
+fn relative(cwd: &Path, file: &Path) -> String {
+    file.strip_prefix(cwd).unwrap_or(file).display().to_string()
+}
+
+/// An executor reading the diagnostics rustc emits for the workspace's
+/// members, and rechecking those every time.
+struct FixExecutor {
+    members: HashSet<PackageId>,
+    cwd: PathBuf,
+    suggestions: Mutex<HashSet<Suggestion>>,
+}
+
+impl FixExecutor {
+    fn record(&self, cmd: &ProcessBuilder, line: &str) {
+        if !line.starts_with('{') {
+            return
+        }
+        let diagnostic: Value = match serde_json::from_str(line) {
+            Ok(diagnostic) => diagnostic,
+            Err(..) => return,
+        };
+        let cwd = cmd.get_cwd().unwrap_or(self.cwd.as_path());
+        let mut suggestions = Vec::new();
+        collect_suggestions(&diagnostic, cwd, &mut suggestions);
+        self.suggestions.lock().unwrap().extend(suggestions);
+    }
+}
+
+impl Executor for FixExecutor {
+    fn exec(&self, mut cmd: ProcessBuilder, id: &PackageId) -> CargoResult<()> {
+        if !self.members.contains(id) {
+            cmd.exec()?;
+            return Ok(())
+        }
+        cmd.arg("--error-format").arg("json");
+        cmd.exec_with_streaming(
+            &mut |line| {
+                writeln!(io::stdout(), "{}", line)?;
+                Ok(())
+            },
+            &mut |line| {
+                self.record(&cmd, line);
+                // Print the diagnostic as rustc would have.
+                if line.starts_with('{') {
+                    let rendered = serde_json::from_str::<Value>(line).ok().and_then(|d| {
+                        d.get("rendered").and_then(|r| r.as_str()).map(|r| r.to_string())
+                    });
+                    if let Some(rendered) = rendered {
+                        write!(io::stderr(), "{}", rendered)?;
+                    }
+                } else {
+                    writeln!(io::stderr(), "{}", line)?;
+                }
+                Ok(())
+            },
+            false)?;
+        Ok(())
+    }
+
+    fn exec_json(&self,
+                 cmd: ProcessBuilder,
+                 id: &PackageId,
+                 handle_stdout: &mut FnMut(&str) -> CargoResult<()>,
+                 handle_stderr: &mut FnMut(&str) -> CargoResult<()>)
+                 -> CargoResult<()> {
+        if !self.members.contains(id) {
+            cmd.exec_with_streaming(handle_stdout, handle_stderr, false)?;
+            return Ok(())
+        }
+        cmd.exec_with_streaming(handle_stdout, &mut |line| {
+            self.record(&cmd, line);
+            handle_stderr(line)
+        }, false)?;
+        Ok(())
+    }
+
+    fn force_rebuild(&self, unit: &Unit) -> bool {
+        self.members.contains(unit.pkg.package_id())
+    }
+}
+
+/// Gathers the machine applicable suggestions of `diagnostic` and of its
+/// children, with the files they replace made relative to `cwd`.
+fn collect_suggestions(diagnostic: &Value, cwd: &Path, out: &mut Vec<Suggestion>) {
+    let mut suggestion = Vec::new();
+    if let Some(spans) = diagnostic.get("spans").and_then(|s| s.as_array()) {
+        for span in spans {
+            let applicability = span.get("suggestion_applicability").and_then(|a| a.as_str());
+            if applicability != Some("MachineApplicable") {
+                continue
+            }
+            let text = span.get("suggested_replacement").and_then(|r| r.as_str());
+            let file = span.get("file_name").and_then(|f| f.as_str());
+            let start = span.get("byte_start").and_then(|b| b.as_u64());
+            let end = span.get("byte_end").and_then(|b| b.as_u64());
+            if let (Some(text), Some(file), Some(start), Some(end)) = (text, file, start, end) {
+                suggestion.push(Replacement {
+                    file: cwd.join(file),
+                    start: start as usize,
+                    end: end as usize,
+                    text: text.to_string(),
+                });
+            }
+        }
+    }
+    if !suggestion.is_empty() {
+        suggestion.sort();
+        out.push(suggestion);
+    }
+    if let Some(children) = diagnostic.get("children").and_then(|c| c.as_array()) {
+        for child in children {
+            collect_suggestions(child, cwd, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{apply_replacements, choose_replacements, Replacement};
+
+    fn replacement(start: usize, end: usize, text: &str) -> Replacement {
+        Replacement {
+            file: PathBuf::from("src/lib.rs"),
+            start: start,
+            end: end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn replacements_are_applied() {
+        let contents = "let x = 1;\nlet mut y = 2;\n";
+        let replacements = [replacement(15, 19, ""), replacement(4, 5, "_x")];
+        let fixed = apply_replacements(Path::new("src/lib.rs"), contents,
+                                       &replacements).unwrap();
+        assert_eq!(fixed, "let _x = 1;\nlet y = 2;\n");
+    }
+
+    #[test]
+    fn overlapping_suggestions_are_skipped() {
+        let suggestions = vec![
+            vec![replacement(0, 4, "a")],
+            vec![replacement(2, 6, "b")],
+            vec![replacement(6, 8, "c")],
+        ].into_iter().collect();
+        let edits = choose_replacements(suggestions);
+        let &(ref replacements, count) = &edits[Path::new("src/lib.rs")];
+        assert_eq!(count, 2);
+        assert_eq!(replacements, &vec![replacement(0, 4, "a"), replacement(6, 8, "c")]);
+    }
+
+    #[test]
+    fn replacements_outside_the_file_are_an_error() {
+        assert!(apply_replacements(Path::new("src/lib.rs"), "fn main() {}",
+                                   &[replacement(10, 40, "")]).is_err());
+    }
+}
@@ -61,6 +61,11 @@ pub fn install(root: Option<&str>,
                opts: &ops::CompileOptions,
                force: bool) -> CargoResult<()> {
     let config = opts.config;
+    let targets = ops::requested_targets(config, opts.targets)?;
+    if targets.len() > 1 {
+        bail!("`baler install` can only build for one target at a time, but \
+               `build.target` lists {}", targets.len())
+    }
     let root = resolve_root(root, config)?;
     let map = SourceConfigMap::new(config)?;
     let (pkg, source) = if source_id.is_git() {
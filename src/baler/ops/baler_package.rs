@@ -1,4 +1,5 @@
-use std::fs::{self, File};
+use std::env;
+use std::fs::{self, File, Metadata};
 use std::io::SeekFrom;
 use std::io::prelude::*;
 use std::path::{self, Path};
@@ -12,6 +13,7 @@ use tar::{Archive, Builder, Header, EntryType};
 use core::{Package, Workspace, Source, SourceId};
 use sources::PathSource;
 use util::{self, internal, Config, FileLock};
+use util::archive;
 use util::errors::{CargoResult, CargoResultExt};
 use ops::{self, DefaultExecutor};
 
@@ -137,7 +139,7 @@ fn verify_dependencies(pkg: &Package) -> CargoResult<()> {
     Ok(())
 }
 
-fn check_not_dirty(p: &Package, src: &PathSource) -> CargoResult<()> {
+pub fn check_not_dirty(p: &Package, src: &PathSource) -> CargoResult<()> {
     if let Ok(repo) = git2::Repository::discover(p.root()) {
         if let Some(workdir) = repo.workdir() {
             debug!("found a git repo at {:?}, checking if index present",
@@ -186,9 +188,11 @@ fn tar(ws: &Workspace,
        src: &PathSource,
        dst: &File,
        filename: &str) -> CargoResult<()> {
-    // Prepare the encoder and its header
+    // Prepare the encoder and its header. The header isn't given a timestamp
+    // so that the same sources always give the same archive.
     let filename = Path::new(filename);
     let encoder = GzBuilder::new().filename(util::path2bytes(filename)?)
+                                  .mtime(0)
                                   .write(dst, Compression::Best);
 
     // Put all package files into a compressed archive
@@ -196,7 +200,13 @@ fn tar(ws: &Workspace,
     let pkg = ws.current()?;
     let config = ws.config();
     let root = pkg.root();
-    for file in src.list_files(pkg)?.iter() {
+    let prefix = format!("{}-{}", pkg.name(), pkg.version());
+    let limit = config.max_unpacked_size()?;
+    let mtime = archive_mtime()?;
+    let mut total = 0;
+    let mut files = src.list_files(pkg)?;
+    files.sort();
+    for file in files.iter() {
         let relative = util::without_prefix(&file, &root).unwrap();
         check_filename(relative)?;
         let relative = relative.to_str().ok_or_else(|| {
@@ -206,8 +216,20 @@ fn tar(ws: &Workspace,
         config.shell().verbose(|shell| {
             shell.status("Archiving", &relative)
         })?;
-        let path = format!("{}-{}{}{}", pkg.name(), pkg.version(),
-                           path::MAIN_SEPARATOR, relative);
+        let path = format!("{}{}{}", prefix, path::MAIN_SEPARATOR, relative);
+
+        // Hold ourselves to the same rules that are enforced when the archive
+        // is unpacked again.
+        archive::check_path(Path::new(&path), &prefix)?;
+        let metadata = fs::metadata(file).chain_err(|| {
+            format!("could not learn metadata for: `{}`", relative)
+        })?;
+        if !metadata.is_file() {
+            bail!("cannot package `{}`: only regular files can be archived",
+                  relative)
+        }
+        total += metadata.len();
+        archive::check_size(total, limit)?;
 
         // The tar::Builder type by default will build GNU archives, but
         // unfortunately we force it here to use UStar archives instead. The
@@ -234,10 +256,14 @@ fn tar(ws: &Workspace,
         let mut file = File::open(file).chain_err(|| {
             format!("failed to open for archiving: `{}`", file.display())
         })?;
-        let metadata = file.metadata().chain_err(|| {
-            format!("could not learn metadata for: `{}`", relative)
-        })?;
-        header.set_metadata(&metadata);
+        // Only what the file holds and whether it can be executed make it
+        // into the archive, not who owns it or when it was last touched.
+        header.set_entry_type(EntryType::file());
+        header.set_mode(archive_mode(&metadata));
+        header.set_size(metadata.len());
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
 
         if relative == "Baler.toml" {
             let orig = Path::new(&path).with_file_name("Baler.toml.orig");
@@ -249,10 +275,15 @@ fn tar(ws: &Workspace,
 
             let mut header = Header::new_ustar();
             let toml = pkg.to_registry_toml();
+            total += toml.len() as u64;
+            archive::check_size(total, limit)?;
             header.set_path(&path)?;
             header.set_entry_type(EntryType::file());
             header.set_mode(0o644);
             header.set_size(toml.len() as u64);
+            header.set_mtime(mtime);
+            header.set_uid(0);
+            header.set_gid(0);
             header.set_cksum();
             ar.append(&header, toml.as_bytes()).chain_err(|| {
                 internal(format!("could not archive source file `{}`", relative))
@@ -269,6 +300,41 @@ fn tar(ws: &Workspace,
     Ok(())
 }
 
+/// The modification time given to the files in the archive, which is
+/// `SOURCE_DATE_EPOCH` when that's set.
+fn archive_mtime() -> CargoResult<u64> {
+    // Stamping files with the epoch itself upsets some tools, so a fixed date
+    // in 2006 is used by default instead.
+    const DEFAULT_MTIME: u64 = 1153704088;
+
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => match epoch.trim().parse() {
+            Ok(mtime) => Ok(mtime),
+            Err(..) => bail!("`SOURCE_DATE_EPOCH` should be a number of seconds \
+                              since the Unix epoch, but is `{}`", epoch),
+        },
+        Err(..) => Ok(DEFAULT_MTIME),
+    }
+}
+
+/// The permissions a file is archived with, which only depend on whether it
+/// can be executed.
+#[cfg(unix)]
+fn archive_mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+#[cfg(not(unix))]
+fn archive_mode(_metadata: &Metadata) -> u32 {
+    0o644
+}
+
 fn run_verify(ws: &Workspace, tar: &File, opts: &PackageOpts) -> CargoResult<()> {
     let config = ws.config();
     let pkg = ws.current()?;
@@ -281,8 +347,10 @@ fn run_verify(ws: &Workspace, tar: &File, opts: &PackageOpts) -> CargoResult<()>
     if fs::metadata(&dst).is_ok() {
         fs::remove_dir_all(&dst)?;
     }
-    let mut archive = Archive::new(f);
-    archive.unpack(dst.parent().unwrap())?;
+    let prefix = format!("{}-{}", pkg.name(), pkg.version());
+    let limit = config.max_unpacked_size()?;
+    let mut tar = Archive::new(f);
+    archive::unpack(&mut tar, dst.parent().unwrap(), &prefix, limit)?;
 
     // Manufacture an ephemeral workspace to ensure that even if the top-level
     // package has a workspace we can still build our new crate.
@@ -294,7 +362,7 @@ fn run_verify(ws: &Workspace, tar: &File, opts: &PackageOpts) -> CargoResult<()>
     ops::compile_ws(&ws, None, &ops::CompileOptions {
         config: config,
         jobs: opts.jobs,
-        target: None,
+        targets: &[],
         features: &[],
         no_default_features: false,
         all_features: false,
@@ -305,6 +373,10 @@ fn run_verify(ws: &Workspace, tar: &File, opts: &PackageOpts) -> CargoResult<()>
         mode: ops::CompileMode::Build,
         target_rustdoc_args: None,
         target_rustc_args: None,
+        build_plan: false,
+        timings: false,
+        keep_going: false,
+        out_dir: None,
     }, Arc::new(DefaultExecutor))?;
 
     Ok(())
@@ -50,6 +50,12 @@ pub fn run(ws: &Workspace,
         }
     }
 
+    let targets = ops::requested_targets(config, options.targets)?;
+    if targets.len() > 1 {
+        bail!("`baler run` can only build for one target at a time, but \
+               `build.target` lists {}", targets.len())
+    }
+
     let compile = ops::compile(ws, options)?;
     assert_eq!(compile.binaries.len(), 1);
     let exe = &compile.binaries[0];
@@ -0,0 +1,247 @@
+//! A cache of compiled artifacts which is shared between workspaces, enabled
+//! with `build.shared-cache`.
+//!
+//! Units from non-path sources compile to the same artifacts wherever they're
+//! built, so once one workspace has built them others can copy them out of the
+//! cache instead of running rustc. Entries are keyed by the unit's fingerprint
+//! hash and its metadata hash, under a directory for the target triple. The
+//! fingerprint doesn't cover the linker and archiver configured for the
+//! triple, so they're mixed into its hash here:
+//!
+//! ```text
+//! <cache>/<triple>/<fingerprint>-<metadata>/<outputs...>
+//! ```
+//!
+//! Entries are written to a temporary directory and then renamed into place,
+//! so they're never seen half-written. Each build holds a shared lock on the
+//! cache, and old entries are only evicted while holding it exclusively.
+
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use filetime::{self, FileTime};
+use fs2::FileExt;
+
+use util;
+use util::errors::{CargoResult, CargoResultExt};
+
+use super::{Context, Unit};
+use super::fingerprint;
+use super::job::Work;
+use super::job_queue::JobState;
+
+pub struct ArtifactCache {
+    root: PathBuf,
+    max_size: u64,
+    lock: File,
+}
+
+impl ArtifactCache {
+    /// Opens the cache at `root`, taking a shared lock on it for the rest of
+    /// the build.
+    pub fn open(root: &Path, max_size: u64) -> CargoResult<ArtifactCache> {
+        fs::create_dir_all(root).chain_err(|| {
+            format!("failed to create shared cache directory `{}`", root.display())
+        })?;
+        let path = root.join(".lock");
+        let lock = OpenOptions::new().read(true).write(true).create(true)
+            .open(&path)
+            .chain_err(|| format!("failed to open: {}", path.display()))?;
+        lock.lock_shared().chain_err(|| {
+            format!("failed to lock file: {}", path.display())
+        })?;
+        Ok(ArtifactCache {
+            root: root.to_path_buf(),
+            max_size: max_size,
+            lock: lock,
+        })
+    }
+
+    /// Copies the cached `files` of an entry to where the build expects them.
+    fn restore(&self, entry: &Path, files: &[PathBuf], state: &JobState,
+               pipelined: bool) -> CargoResult<()> {
+        for file in files {
+            let cached = entry.join(file.file_name().unwrap());
+            if fs::metadata(file).is_ok() {
+                fs::remove_file(file)?;
+            }
+            fs::copy(&cached, file).chain_err(|| {
+                format!("failed to restore `{}` from the shared cache",
+                        cached.display())
+            })?;
+        }
+
+        if pipelined {
+            state.rmeta_produced();
+        }
+
+        // Entries are evicted least recently used first.
+        let now = now();
+        filetime::set_file_times(entry, now, now)?;
+        Ok(())
+    }
+
+    /// Adds the freshly built `files` to the cache as `entry`.
+    fn store(&self, entry: &Path, files: &[PathBuf]) -> CargoResult<()> {
+        if entry.exists() {
+            return Ok(())
+        }
+        let tmp = self.root.join("tmp").join(format!("{}-{}",
+            entry.file_name().unwrap().to_string_lossy(), process::id()));
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        fs::create_dir_all(&tmp)?;
+        for file in files {
+            fs::copy(file, tmp.join(file.file_name().unwrap())).chain_err(|| {
+                format!("failed to copy `{}` to the shared cache", file.display())
+            })?;
+        }
+        fs::create_dir_all(entry.parent().unwrap())?;
+
+        // Another build may have stored the same entry in the meantime, in
+        // which case its copy is as good as ours.
+        if fs::rename(&tmp, entry).is_err() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        Ok(())
+    }
+
+    /// Evicts the least recently used entries until the cache fits in its size
+    /// limit.
+    ///
+    /// This needs the cache to itself, so it's skipped if other builds are
+    /// using it; they'll evict once they're done.
+    pub fn finish(&self) -> CargoResult<()> {
+        self.lock.unlock()?;
+        if self.lock.try_lock_exclusive().is_err() {
+            return Ok(())
+        }
+
+        // Nothing else can be writing entries now, so leftovers are from
+        // builds which were interrupted.
+        let tmp = self.root.join("tmp");
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for triple in fs::read_dir(&self.root)? {
+            let triple = triple?;
+            if !triple.file_type()?.is_dir() {
+                continue
+            }
+            for entry in fs::read_dir(triple.path())? {
+                let entry = entry?.path();
+                let mut size = 0;
+                for file in fs::read_dir(&entry)? {
+                    size += file?.metadata()?.len();
+                }
+                let used = FileTime::from_last_modification_time(&fs::metadata(&entry)?);
+                total += size;
+                entries.push((used, size, entry));
+            }
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, size, entry) in entries {
+            if total <= self.max_size {
+                break
+            }
+            debug!("evicting {} from the shared cache", entry.display());
+            fs::remove_dir_all(&entry)?;
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps the `work` building a unit to go through the shared cache, if there
+/// is one and the unit can be shared.
+///
+/// If the cache has the unit's artifacts they're restored instead of running
+/// `work`, and the unit is recorded in `cx.restored`. Otherwise its artifacts
+/// are added to the cache once `work` has built them.
+pub fn prepare<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
+                         unit: &Unit<'a>,
+                         work: Work) -> CargoResult<Work> {
+    let cache = match cx.artifact_cache {
+        Some(ref cache) => cache.clone(),
+        None => return Ok(work),
+    };
+    if !cacheable(cx, unit)? {
+        return Ok(work)
+    }
+    let metadata = match cx.target_metadata(unit) {
+        Some(metadata) => metadata,
+        None => return Ok(work),
+    };
+    let fingerprint = util::hash_u64(&(fingerprint::fingerprint_hash(cx, unit)?,
+                                        cx.linker(unit.kind),
+                                        cx.ar(unit.kind)));
+    let triple = cx.triple(unit.kind).to_string();
+    let entry = cache.root.join(triple)
+        .join(format!("{}-{}", util::to_hex(fingerprint), metadata));
+
+    // The dep-info is kept too, as `output_depinfo` reads it for the units
+    // being built.
+    let mut files = vec![fingerprint::dep_info_loc(cx, unit)];
+    for &(ref src, _, _) in cx.target_filenames(unit)?.iter() {
+        files.push(src.clone());
+        // Pipelined dependents are compiled against the metadata.
+        if cx.rmeta_required(unit) && src.extension().map_or(false, |e| e == "rlib") {
+            files.push(src.with_extension("rmeta"));
+        }
+    }
+
+    let cached = files.iter().all(|file| {
+        entry.join(file.file_name().unwrap()).exists()
+    });
+    if cached {
+        debug!("restoring {} from the shared cache", entry.display());
+        cx.restored.insert(*unit);
+        let pipelined = cx.rmeta_required(unit);
+        return Ok(Work::new(move |state| {
+            cache.restore(&entry, &files, state, pipelined)
+        }))
+    }
+
+    Ok(work.then(Work::new(move |_| cache.store(&entry, &files))))
+}
+
+/// Returns whether the artifacts of `unit` depend only on its fingerprint, so
+/// they can be shared between workspaces.
+///
+/// That's the case for units of packages from registries and git, unless they
+/// or anything they depend on has a build script, whose output could differ
+/// from one build to the next.
+fn cacheable<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
+                       -> CargoResult<bool> {
+    if let Some(&cacheable) = cx.shared_cacheable.get(unit) {
+        return Ok(cacheable)
+    }
+    let mut ret = !unit.pkg.package_id().source_id().is_path() &&
+                  !unit.profile.doc &&
+                  !unit.profile.run_custom_build &&
+                  !unit.pkg.targets().iter().any(|t| t.is_custom_build());
+    if ret {
+        for dep in cx.dep_targets(unit)?.iter() {
+            if !cacheable(cx, dep)? {
+                ret = false;
+                break
+            }
+        }
+    }
+    cx.shared_cacheable.insert(*unit, ret);
+    Ok(ret)
+}
+
+fn now() -> FileTime {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => FileTime::from_seconds_since_1970(d.as_secs(), d.subsec_nanos()),
+        Err(..) => FileTime::zero(),
+    }
+}
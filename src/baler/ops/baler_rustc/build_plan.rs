@@ -0,0 +1,108 @@
+//! The build plan printed by `baler build --build-plan`.
+//!
+//! Rather than running anything, each unit of work records the process it
+//! would have run here. The plan lists these invocations in an order in which
+//! they could be run, each one naming the invocations it depends on by their
+//! index in the list.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use semver::Version;
+use serde_json;
+
+use core::TargetKind;
+use util::{CargoResult, ProcessBuilder};
+
+use super::{Context, Kind, Unit};
+
+#[derive(Serialize)]
+struct Invocation {
+    package_name: String,
+    package_version: Version,
+    target_kind: TargetKind,
+    kind: &'static str,
+    deps: Vec<usize>,
+    outputs: Vec<PathBuf>,
+    links: BTreeMap<PathBuf, PathBuf>,
+    program: String,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    cwd: Option<PathBuf>,
+    /// Whether the invocation also depends on the output of build scripts,
+    /// which would add arguments and environment variables that can't be
+    /// known without running them.
+    build_script_dependent: bool,
+}
+
+#[derive(Serialize, Default)]
+pub struct BuildPlan {
+    invocations: Vec<Invocation>,
+}
+
+impl BuildPlan {
+    pub fn new() -> BuildPlan {
+        BuildPlan::default()
+    }
+
+    /// Records `cmd` as the process run for `unit`, returning the index of the
+    /// new invocation.
+    ///
+    /// The invocations in `deps` must already have been added, and `filenames`
+    /// are the outputs of `unit` as given by `Context::target_filenames`.
+    pub fn add<'a, 'cfg>(&mut self,
+                         cx: &mut Context<'a, 'cfg>,
+                         unit: &Unit<'a>,
+                         deps: Vec<usize>,
+                         cmd: &ProcessBuilder,
+                         filenames: &[(PathBuf, Option<PathBuf>, bool)])
+                         -> CargoResult<usize> {
+        let mut build_script_dependent = match cx.build_scripts.get(unit) {
+            Some(scripts) => {
+                !scripts.to_link.is_empty() || !scripts.plugins.is_empty()
+            }
+            None => false,
+        };
+        // Build scripts are passed the metadata of the build scripts of
+        // their `links` dependencies.
+        if unit.profile.run_custom_build {
+            build_script_dependent |= cx.dep_targets(unit)?.iter().any(|dep| {
+                dep.profile.run_custom_build
+            });
+        }
+
+        let env = cmd.get_envs().iter().filter_map(|(key, value)| {
+            value.as_ref().map(|value| {
+                (key.clone(), value.to_string_lossy().into_owned())
+            })
+        }).collect();
+
+        self.invocations.push(Invocation {
+            package_name: unit.pkg.name().to_string(),
+            package_version: unit.pkg.version().clone(),
+            target_kind: unit.target.kind().clone(),
+            kind: match unit.kind {
+                Kind::Host => "host",
+                Kind::Target(..) => "target",
+            },
+            deps: deps,
+            outputs: filenames.iter().map(|&(ref dst, _, _)| dst.clone()).collect(),
+            links: filenames.iter().filter_map(|&(ref dst, ref link_dst, _)| {
+                link_dst.as_ref().map(|link_dst| (link_dst.clone(), dst.clone()))
+            }).collect(),
+            program: cmd.get_program().to_string_lossy().into_owned(),
+            args: cmd.get_args().iter().map(|arg| {
+                arg.to_string_lossy().into_owned()
+            }).collect(),
+            env: env,
+            cwd: cmd.get_cwd().map(|cwd| cwd.to_path_buf()),
+            build_script_dependent: build_script_dependent,
+        });
+        Ok(self.invocations.len() - 1)
+    }
+
+    /// Prints the plan to stdout.
+    pub fn output_plan(&self) {
+        println!("{}", serde_json::to_string(self).unwrap());
+    }
+}
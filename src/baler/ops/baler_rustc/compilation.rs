@@ -1,10 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::PathBuf;
+use std::str::FromStr;
 use semver::Version;
 
 use core::{PackageId, Package, Target, TargetKind};
 use util::{self, CargoResult, Config, LazyCell, ProcessBuilder, process, join_paths};
+use util::{Cfg, CfgExpr};
 
 /// A structure returning the result of a compilation.
 pub struct Compilation<'cfg> {
@@ -52,6 +54,10 @@ pub struct Compilation<'cfg> {
 
     pub target: String,
 
+    /// The `cfg` values of `target`, used to pick a runner from the
+    /// `target.'cfg(...)'` tables.
+    pub target_cfg: Vec<Cfg>,
+
     config: &'cfg Config,
 
     target_runner: LazyCell<Option<(PathBuf, Vec<String>)>>,
@@ -74,6 +80,7 @@ impl<'cfg> Compilation<'cfg> {
             cfgs: HashMap::new(),
             config: config,
             target: String::new(),
+            target_cfg: Vec::new(),
             target_runner: LazyCell::new(),
         }
     }
@@ -94,10 +101,42 @@ impl<'cfg> Compilation<'cfg> {
         self.fill_env(process(cmd), pkg, true)
     }
 
+    /// Returns the runner configured for the target, either for its triple
+    /// or, failing that, in the one `target.'cfg(...)'` table which matches it
+    /// and has a runner.
     fn target_runner(&self) -> CargoResult<&Option<(PathBuf, Vec<String>)>> {
         self.target_runner.get_or_try_init(|| {
             let key = format!("target.{}.runner", self.target);
-            Ok(self.config.get_path_and_args(&key)?.map(|v| v.val))
+            if let Some(runner) = self.config.get_path_and_args(&key)? {
+                return Ok(Some(runner.val))
+            }
+
+            let table = match self.config.get_table("target")? {
+                Some(table) => table.val,
+                None => return Ok(None),
+            };
+            let mut cfgs = table.keys().filter(|key| {
+                key.starts_with("cfg(") && key.ends_with(")") &&
+                    CfgExpr::from_str(&key[4..key.len() - 1]).map(|c| {
+                        c.matches(&self.target_cfg)
+                    }).unwrap_or(false)
+            }).collect::<Vec<_>>();
+            cfgs.sort();
+            let mut runners = Vec::new();
+            for cfg in cfgs {
+                let key = format!("target.{}.runner", cfg);
+                if let Some(runner) = self.config.get_path_and_args(&key)? {
+                    runners.push((key, runner));
+                }
+            }
+            if runners.len() > 1 {
+                let keys = runners.iter().map(|&(ref key, ref runner)| {
+                    format!("\n  `{}` in {}", key, runner.definition)
+                }).collect::<String>();
+                bail!("several runners are configured for the target `{}`, \
+                       but only one can be used:{}", self.target, keys)
+            }
+            Ok(runners.pop().map(|(_, runner)| runner.val))
         })
     }
 
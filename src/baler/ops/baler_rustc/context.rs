@@ -13,12 +13,13 @@ use jobserver::Client;
 use core::{Package, PackageId, PackageSet, Resolve, Target, Profile};
 use core::{TargetKind, Profiles, Dependency, Workspace};
 use core::dependency::Kind as DepKind;
-use util::{self, internal, Config, profile, Cfg, CfgExpr};
+use util::{self, internal, paths, Config, profile, Cfg, CfgExpr};
 use util::errors::{CargoResult, CargoResultExt};
 
 use super::TargetConfig;
+use super::artifact_cache::ArtifactCache;
 use super::custom_build::{BuildState, BuildScripts, BuildDeps};
-use super::fingerprint::Fingerprint;
+use super::fingerprint::{ContentHashes, DirtyReason, Fingerprint};
 use super::layout::Layout;
 use super::links::Links;
 use super::{Kind, Compilation, BuildConfig};
@@ -40,16 +41,33 @@ pub struct Context<'a, 'cfg: 'a> {
     pub build_state: Arc<BuildState>,
     pub build_explicit_deps: HashMap<Unit<'a>, BuildDeps>,
     pub fingerprints: HashMap<Unit<'a>, Arc<Fingerprint>>,
+    /// Why each unit which isn't fresh has to be rebuilt.
+    pub dirty_reasons: HashMap<Unit<'a>, DirtyReason>,
     pub compiled: HashSet<Unit<'a>>,
     pub build_config: BuildConfig,
     pub build_scripts: HashMap<Unit<'a>, Arc<BuildScripts>>,
     pub links: Links<'a>,
     pub used_in_plugin: HashSet<Unit<'a>>,
     pub jobserver: Client,
+    /// Libraries which have a dependent that can start compiling as soon as
+    /// their metadata is ready.
+    pub rmeta_required: HashSet<Unit<'a>>,
+    /// Hashes of input files, used when freshness is based on their contents.
+    pub content_hashes: Arc<ContentHashes>,
+    /// The cache shared between workspaces, if `build.shared-cache` is set.
+    pub artifact_cache: Option<Arc<ArtifactCache>>,
+    /// Units whose artifacts are copied out of the shared cache.
+    pub restored: HashSet<Unit<'a>>,
+    /// Whether each unit looked at so far can go in the shared cache.
+    pub shared_cacheable: HashMap<Unit<'a>, bool>,
 
     host: Layout,
-    target: Option<Layout>,
-    target_info: TargetInfo,
+    /// The layout of each requested target.
+    targets: Vec<Layout>,
+    target_info: Vec<TargetInfo>,
+    /// A hash of the contents of each requested target which is a custom
+    /// target specification file.
+    target_specs: Vec<Option<u64>>,
     host_info: TargetInfo,
     profiles: &'a Profiles,
     incremental_enabled: bool,
@@ -60,6 +78,9 @@ pub struct Context<'a, 'cfg: 'a> {
 struct TargetInfo {
     crate_types: HashMap<String, Option<(String, String)>>,
     cfg: Option<Vec<Cfg>>,
+    /// Whether rustc accepts `--json`, which pipelining and rendering
+    /// diagnostics out of JSON rely on.
+    json: bool,
 }
 
 #[derive(Clone)]
@@ -74,11 +95,23 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
                profiles: &'a Profiles) -> CargoResult<Context<'a, 'cfg>> {
 
         let dest = if build_config.release { "release" } else { "debug" };
-        let host_layout = Layout::new(ws, None, &dest)?;
-        let target_layout = match build_config.requested_target.as_ref() {
-            Some(target) => Some(Layout::new(ws, Some(&target), dest)?),
-            None => None,
-        };
+        // A build plan doesn't write anything, so it doesn't need the
+        // directories to itself either.
+        let layout = if build_config.build_plan { Layout::unlocked } else { Layout::new };
+        let host_layout = layout(ws, None, &dest)?;
+        let mut target_layouts = Vec::new();
+        let mut target_specs = Vec::new();
+        for target in build_config.requested_targets.iter() {
+            target_layouts.push(layout(ws, Some(target), dest)?);
+            target_specs.push(if is_target_spec(target) {
+                let contents = paths::read(Path::new(target)).chain_err(|| {
+                    format!("failed to read the target specification `{}`", target)
+                })?;
+                Some(util::hash_u64(&contents))
+            } else {
+                None
+            });
+        }
 
         // Enable incremental builds if the user opts in. For now,
         // this is an environment variable until things stabilize a
@@ -116,22 +149,29 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(Context {
             ws: ws,
             host: host_layout,
-            target: target_layout,
+            target_info: vec![TargetInfo::default(); target_layouts.len()],
+            targets: target_layouts,
+            target_specs: target_specs,
             resolve: resolve,
             packages: packages,
             config: config,
-            target_info: TargetInfo::default(),
             host_info: TargetInfo::default(),
             compilation: Compilation::new(config),
             build_state: Arc::new(BuildState::new(&build_config)),
             build_config: build_config,
             fingerprints: HashMap::new(),
+            dirty_reasons: HashMap::new(),
             profiles: profiles,
             compiled: HashSet::new(),
             build_scripts: HashMap::new(),
             build_explicit_deps: HashMap::new(),
             links: Links::new(),
             used_in_plugin: HashSet::new(),
+            rmeta_required: HashSet::new(),
+            content_hashes: Arc::new(ContentHashes::new()),
+            artifact_cache: None,
+            restored: HashSet::new(),
+            shared_cacheable: HashMap::new(),
             incremental_enabled: incremental_enabled,
             jobserver: jobserver,
             target_filenames: HashMap::new(),
@@ -143,23 +183,25 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
     pub fn prepare(&mut self) -> CargoResult<()> {
         let _p = profile::start("preparing layout");
 
-        self.host.prepare().chain_err(|| {
-            internal(format!("couldn't prepare build directories"))
-        })?;
-        match self.target {
-            Some(ref mut target) => {
+        if !self.build_config.build_plan {
+            self.host.prepare().chain_err(|| {
+                internal(format!("couldn't prepare build directories"))
+            })?;
+            for target in self.targets.iter_mut() {
                 target.prepare().chain_err(|| {
                     internal(format!("couldn't prepare build directories"))
                 })?;
             }
-            None => {}
         }
 
         self.compilation.plugins_dylib_path = self.host.deps().to_path_buf();
 
-        let layout = self.target.as_ref().unwrap_or(&self.host);
-        self.compilation.root_output = layout.dest().to_path_buf();
-        self.compilation.deps_output = layout.deps().to_path_buf();
+        let (root_output, deps_output) = {
+            let layout = self.layout(self.default_kind());
+            (layout.dest().to_path_buf(), layout.deps().to_path_buf())
+        };
+        self.compilation.root_output = root_output;
+        self.compilation.deps_output = deps_output;
         Ok(())
     }
 
@@ -176,12 +218,10 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             self.visit_crate_type(unit, &mut crate_types)?;
         }
         debug!("probe_target_info: crate_types={:?}", crate_types);
-        self.probe_target_info_kind(&crate_types, Kind::Target)?;
-        if self.requested_target().is_none() {
-            self.host_info = self.target_info.clone();
-        } else {
-            self.probe_target_info_kind(&crate_types, Kind::Host)?;
+        for i in 0..self.targets.len() {
+            self.probe_target_info_kind(&crate_types, Kind::Target(i))?;
         }
+        self.probe_target_info_kind(&crate_types, Kind::Host)?;
         Ok(())
     }
 
@@ -223,16 +263,26 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         for crate_type in crate_types {
             process.arg("--crate-type").arg(crate_type);
         }
-        if kind == Kind::Target {
-            process.arg("--target").arg(&self.target_triple());
+        if kind != Kind::Host {
+            process.arg("--target").arg(self.rustc_target(kind));
         }
 
         let mut with_cfg = process.clone();
         with_cfg.arg("--print=sysroot");
         with_cfg.arg("--print=cfg");
 
+        // Older compilers don't know about `--json`, so try the query with it
+        // first and fall back to what they do understand.
+        let mut with_json = with_cfg.clone();
+        with_json.arg("--error-format").arg("json");
+        with_json.arg("--json=artifacts");
+
+        let mut json = true;
         let mut has_cfg_and_sysroot = true;
-        let output = with_cfg.exec_with_output().or_else(|_| {
+        let output = with_json.exec_with_output().or_else(|_| {
+            json = false;
+            with_cfg.exec_with_output()
+        }).or_else(|_| {
             has_cfg_and_sysroot = false;
             process.exec_with_output()
         }).chain_err(|| {
@@ -277,17 +327,17 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
                                target-specific information from rustc"),
             };
             let mut rustlib = PathBuf::from(line);
-            if kind == Kind::Host {
+            if kind == Kind::Host && !self.targets.is_empty() {
                 if cfg!(windows) {
                     rustlib.push("bin");
                 } else {
                     rustlib.push("lib");
                 }
                 self.compilation.host_dylib_path = Some(rustlib);
-            } else {
+            } else if kind == self.default_kind() {
                 rustlib.push("lib");
                 rustlib.push("rustlib");
-                rustlib.push(self.target_triple());
+                rustlib.push(self.triple(kind));
                 rustlib.push("lib");
                 self.compilation.target_dylib_path = Some(rustlib);
             }
@@ -300,11 +350,12 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         };
 
         let info = match kind {
-            Kind::Target => &mut self.target_info,
+            Kind::Target(i) => &mut self.target_info[i],
             Kind::Host => &mut self.host_info,
         };
         info.crate_types = map;
         info.cfg = cfg;
+        info.json = json;
         Ok(())
     }
 
@@ -343,11 +394,69 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(())
     }
 
-    /// Returns the appropriate directory layout for either a plugin or not.
-    fn layout(&self, kind: Kind) -> &Layout {
+    /// Builds up `rmeta_required` from the list of top-level units.
+    ///
+    /// This will recursively walk `units` and all of their dependencies to
+    /// find the libraries which a dependent only needs the metadata of.
+    pub fn build_rmeta_required(&mut self, units: &[Unit<'a>]) -> CargoResult<()> {
+        let mut visited = HashSet::new();
+        let mut stack = units.to_vec();
+        while let Some(unit) = stack.pop() {
+            if !visited.insert(unit) {
+                continue
+            }
+            for dep in self.dep_targets(&unit)? {
+                if self.only_requires_rmeta(&unit, &dep) {
+                    self.rmeta_required.insert(dep);
+                }
+                stack.push(dep);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether `parent` can be compiled against the metadata of `dep`
+    /// alone, rather than waiting for `dep` to be fully built.
+    ///
+    /// Only rlibs are pipelined like this, and only into other rlibs or
+    /// documentation, as anything which gets linked needs the object code.
+    pub fn only_requires_rmeta(&self, parent: &Unit<'a>, dep: &Unit<'a>) -> bool {
+        fn only_rlib(unit: &Unit) -> bool {
+            unit.target.is_lib() &&
+                !unit.profile.test &&
+                !unit.profile.check &&
+                !unit.profile.run_custom_build &&
+                unit.target.rustc_crate_types().iter().all(|t| {
+                    *t == "lib" || *t == "rlib"
+                })
+        }
+        self.build_config.pipelining &&
+            self.supports_json(dep.kind) &&
+            !dep.profile.doc &&
+            only_rlib(dep) &&
+            (only_rlib(parent) || (parent.profile.doc && !parent.profile.test))
+    }
+
+    /// Returns whether the compiler building units of the given kind accepts
+    /// `--json`.
+    pub fn supports_json(&self, kind: Kind) -> bool {
+        match kind {
+            Kind::Host => self.host_info.json,
+            Kind::Target(i) => self.target_info[i].json,
+        }
+    }
+
+    /// Returns whether `unit` should make its metadata available before the
+    /// rest of its outputs.
+    pub fn rmeta_required(&self, unit: &Unit<'a>) -> bool {
+        self.rmeta_required.contains(unit)
+    }
+
+    /// Returns the directory layout units of the given kind are placed in.
+    pub fn layout(&self, kind: Kind) -> &Layout {
         match kind {
             Kind::Host => &self.host,
-            Kind::Target => self.target.as_ref().unwrap_or(&self.host)
+            Kind::Target(i) => &self.targets[i],
         }
     }
 
@@ -357,6 +466,18 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         self.layout(unit.kind).deps()
     }
 
+    /// Returns the file in which the content hashes of input files are cached
+    /// between builds.
+    pub fn content_hashes_path(&self) -> PathBuf {
+        self.host.fingerprint().join("content-hashes.json")
+    }
+
+    /// Returns the file in which the time each unit took to build is kept
+    /// between builds.
+    pub fn unit_costs_path(&self) -> PathBuf {
+        self.host.fingerprint().join("unit-costs.json")
+    }
+
     /// Returns the directory for the specified unit where fingerprint
     /// information is stored.
     pub fn fingerprint_dir(&mut self, unit: &Unit) -> PathBuf {
@@ -416,14 +537,30 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         &self.build_config.host_triple
     }
 
-    /// Return the target triple which this context is targeting.
-    pub fn target_triple(&self) -> &str {
-        self.requested_target().unwrap_or(self.host_triple())
+    /// Return the triple which units of the given kind are compiled for. For
+    /// a custom target specification this is the name of the file, without
+    /// its `.json` extension.
+    pub fn triple(&self, kind: Kind) -> &str {
+        target_short_name(self.rustc_target(kind))
     }
 
-    /// Requested (not actual) target for the build
-    pub fn requested_target(&self) -> Option<&str> {
-        self.build_config.requested_target.as_ref().map(|s| &s[..])
+    /// Return what's passed to rustc's `--target` to compile units of the
+    /// given kind, which may be the path to a custom target specification.
+    pub fn rustc_target(&self, kind: Kind) -> &str {
+        match kind {
+            Kind::Host => self.host_triple(),
+            Kind::Target(i) => &self.build_config.requested_targets[i],
+        }
+    }
+
+    /// Returns the kind of the units which are compiled for the first
+    /// requested target, or the host if no target was requested.
+    pub fn default_kind(&self) -> Kind {
+        if self.targets.is_empty() {
+            Kind::Host
+        } else {
+            Kind::Target(0)
+        }
     }
 
     /// Get the metadata for a target in a specific profile
@@ -479,9 +616,13 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         unit.profile.hash(&mut hasher);
 
         // Artifacts compiled for the host should have a different metadata
-        // piece than those compiled for the target, so make sure we throw in
-        // the unit's `kind` as well
-        unit.kind.hash(&mut hasher);
+        // piece than those compiled for a target, so make sure we throw in
+        // whether the unit is for the host and its target as well. Custom
+        // target specifications are hashed by their contents, so editing one
+        // rebuilds everything compiled for it.
+        (unit.kind == Kind::Host).hash(&mut hasher);
+        self.triple(unit.kind).hash(&mut hasher);
+        self.target_spec_hash(unit.kind).hash(&mut hasher);
 
         // Finally throw in the target name/kind. This ensures that concurrent
         // compiles of targets in the same crate don't collide.
@@ -571,10 +712,9 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         let out_dir = self.out_dir(unit);
         let stem = self.file_stem(unit);
         let link_stem = self.link_stem(unit);
-        let info = if unit.target.for_host() {
-            &self.host_info
-        } else {
-            &self.target_info
+        let info = match unit.kind {
+            Kind::Host => &self.host_info,
+            Kind::Target(i) => &self.target_info[i],
         };
 
         let mut ret = Vec::new();
@@ -636,11 +776,11 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             if unsupported.len() > 0 {
                 bail!("cannot produce {} for `{}` as the target `{}` \
                        does not support these crate types",
-                      unsupported.join(", "), unit.pkg, self.target_triple())
+                      unsupported.join(", "), unit.pkg, self.triple(unit.kind))
             }
             bail!("cannot compile `{}` as the target `{}` does not \
                    support any of the output crate types",
-                  unit.pkg, self.target_triple());
+                  unit.pkg, self.triple(unit.kind));
         }
         info!("Target filenames: {:?}", ret);
 
@@ -649,6 +789,31 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(ret)
     }
 
+    /// Returns where the executable of a binary `unit` ends up once it's
+    /// built, under its unhashed name if it gets one.
+    ///
+    /// This is worked out from the name of executables on the unit's target
+    /// rather than picked out of `target_filenames`, which also lists the
+    /// other files some targets produce along with them, and lists only the
+    /// metadata when checking.
+    pub fn bin_exe(&mut self, unit: &Unit<'a>) -> CargoResult<PathBuf> {
+        let (dir, stem) = match self.link_stem(unit) {
+            Some((dir, stem)) => (dir, stem),
+            None => (self.out_dir(unit), self.file_stem(unit)),
+        };
+        let info = match unit.kind {
+            Kind::Host => &self.host_info,
+            Kind::Target(i) => &self.target_info[i],
+        };
+        match info.crate_types.get("bin") {
+            Some(&Some((ref prefix, ref suffix))) => {
+                Ok(dir.join(format!("{}{}{}", prefix, stem, suffix)))
+            }
+            _ => bail!("the target `{}` can't produce executables",
+                       self.triple(unit.kind)),
+        }
+    }
+
     /// For a package, return all targets which are registered as dependencies
     /// for that package.
     pub fn dep_targets(&self, unit: &Unit<'a>) -> CargoResult<Vec<Unit<'a>>> {
@@ -879,11 +1044,17 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             Some(p) => p,
             None => return true,
         };
-        let (name, info) = match kind {
-            Kind::Host => (self.host_triple(), &self.host_info),
-            Kind::Target => (self.target_triple(), &self.target_info),
-        };
-        platform.matches(name, info.cfg.as_ref().map(|cfg| &cfg[..]))
+        let info = self.info(&kind);
+        platform.matches(self.triple(kind), info.cfg.as_ref().map(|cfg| &cfg[..]))
+    }
+
+    /// Returns the hash of the contents of the custom target specification
+    /// units of the given kind are compiled for, if it is one.
+    pub fn target_spec_hash(&self, kind: Kind) -> Option<u64> {
+        match kind {
+            Kind::Host => None,
+            Kind::Target(i) => self.target_specs[i],
+        }
     }
 
     /// Gets a package for the given package id.
@@ -903,18 +1074,14 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
 
     /// Get the list of cfg printed out from the compiler for the specified kind
     pub fn cfg(&self, kind: Kind) -> &[Cfg] {
-        let info = match kind {
-            Kind::Host => &self.host_info,
-            Kind::Target => &self.target_info,
-        };
-        info.cfg.as_ref().map(|s| &s[..]).unwrap_or(&[])
+        self.info(&kind).cfg.as_ref().map(|s| &s[..]).unwrap_or(&[])
     }
 
     /// Get the target configuration for a particular host or target
     fn target_config(&self, kind: Kind) -> &TargetConfig {
         match kind {
             Kind::Host => &self.build_config.host,
-            Kind::Target => &self.build_config.target,
+            Kind::Target(i) => &self.build_config.targets[i],
         }
     }
 
@@ -986,7 +1153,7 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
     fn info(&self, kind: &Kind) -> &TargetInfo {
         match *kind {
             Kind::Host => &self.host_info,
-            Kind::Target => &self.target_info,
+            Kind::Target(i) => &self.target_info[i],
         }
     }
 }
@@ -1017,8 +1184,8 @@ fn env_args(config: &Config,
     // This means that, e.g. even if the specified --target is the
     // same as the host, build scripts in plugins won't get
     // RUSTFLAGS.
-    let compiling_with_target = build_config.requested_target.is_some();
-    let is_target_kind = kind == Kind::Target;
+    let compiling_with_target = !build_config.requested_targets.is_empty();
+    let is_target_kind = kind != Kind::Host;
 
     if compiling_with_target && !is_target_kind {
         // This is probably a build script or plugin and we're
@@ -1040,7 +1207,10 @@ fn env_args(config: &Config,
 
     let name = name.chars().flat_map(|c| c.to_lowercase()).collect::<String>();
     // Then the target.*.rustflags value...
-    let target = build_config.requested_target.as_ref().unwrap_or(&build_config.host_triple);
+    let target = match kind {
+        Kind::Host => &build_config.host_triple,
+        Kind::Target(i) => target_short_name(&build_config.requested_targets[i]),
+    };
     let key = format!("target.{}.{}", target, name);
     if let Some(args) = config.get_list_or_split_string(&key)? {
         let args = args.val.into_iter();
@@ -1083,6 +1253,22 @@ fn env_args(config: &Config,
     Ok(Vec::new())
 }
 
+/// Returns whether `target` names a custom target specification file rather
+/// than a target known to rustc.
+pub fn is_target_spec(target: &str) -> bool {
+    target.ends_with(".json")
+}
+
+/// Returns the name by which `target` is known in configuration and in the
+/// directory layout: the file stem of a target specification, or the triple.
+pub fn target_short_name(target: &str) -> &str {
+    if is_target_spec(target) {
+        Path::new(target).file_stem().and_then(|s| s.to_str()).unwrap_or(target)
+    } else {
+        target
+    }
+}
+
 impl fmt::Display for Metadata {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:016x}", self.0)
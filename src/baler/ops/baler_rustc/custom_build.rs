@@ -4,7 +4,7 @@ use std::path::{PathBuf, Path};
 use std::str;
 use std::sync::{Mutex, Arc};
 
-use core::PackageId;
+use core::{PackageId, Target};
 use util::{Freshness, Cfg};
 use util::errors::{CargoResult, CargoResultExt, CargoError};
 use util::{internal, profile, paths};
@@ -22,6 +22,11 @@ pub struct BuildOutput {
     pub library_links: Vec<String>,
     /// Various `--cfg` flags to pass to the compiler
     pub cfgs: Vec<String>,
+    /// Names and values to pass to the compiler with `--check-cfg`
+    pub check_cfgs: Vec<String>,
+    /// Additional arguments to pass to the linker, and which of the package's
+    /// targets they're for
+    pub linker_args: Vec<(LinkType, String)>,
     /// Additional environment variables to run the compiler with.
     pub env: Vec<(String, String)>,
     /// Metadata to pass to the immediate dependencies
@@ -34,6 +39,35 @@ pub struct BuildOutput {
     pub warnings: Vec<String>,
 }
 
+/// The targets of a package which a `rustc-link-arg` style directive applies
+/// to.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkType {
+    /// Every target which is linked: binaries, examples, tests, benchmarks
+    /// and cdylibs.
+    All,
+    /// Only binaries.
+    Bin,
+    /// Only cdylibs.
+    Cdylib,
+}
+
+impl LinkType {
+    /// Returns whether linker arguments of this type are passed when building
+    /// `target`, which is a test harness if `test` is set.
+    pub fn applies_to(&self, target: &Target, test: bool) -> bool {
+        match *self {
+            LinkType::All => {
+                test || target.is_bin() || target.is_example() ||
+                    target.is_test() || target.is_bench() || target.is_cdylib()
+            }
+            LinkType::Bin => target.is_bin(),
+            LinkType::Cdylib => target.is_cdylib(),
+        }
+    }
+}
+
 pub type BuildMap = HashMap<(PackageId, Kind), BuildOutput>;
 
 pub struct BuildState {
@@ -84,6 +118,12 @@ pub fn prepare<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         build_work(cx, unit)?
     };
 
+    // A build plan only records what would run, so there's no fingerprint to
+    // manage.
+    if cx.build_config.build_plan {
+        return Ok((work_dirty, work_fresh, Freshness::Dirty))
+    }
+
     // Now that we've prep'd our work, build the work needed to manage the
     // fingerprint and then start returning that upwards.
     let (freshness, dirty, fresh) =
@@ -114,10 +154,7 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     cmd.env("OUT_DIR", &build_output)
        .env("CARGO_MANIFEST_DIR", unit.pkg.root())
        .env("NUM_JOBS", &cx.jobs().to_string())
-       .env("TARGET", &match unit.kind {
-           Kind::Host => cx.host_triple(),
-           Kind::Target => cx.target_triple(),
-       })
+       .env("TARGET", cx.triple(unit.kind))
        .env("DEBUG", &profile.debuginfo.is_some().to_string())
        .env("OPT_LEVEL", &profile.opt_level)
        .env("PROFILE", if cx.build_config.release { "release" } else { "debug" })
@@ -185,6 +222,7 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let build_scripts = super::load_build_deps(cx, unit);
     let kind = unit.kind;
     let json_messages = cx.build_config.json_messages;
+    let build_plan = cx.build_config.build_plan;
 
     // Check to see if the build script has already run, and if it has keep
     // track of whether it has told us about some explicit dependencies
@@ -192,8 +230,10 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let deps = BuildDeps::new(&output_file, prev_output.as_ref());
     cx.build_explicit_deps.insert(*unit, deps);
 
-    fs::create_dir_all(&script_output)?;
-    fs::create_dir_all(&build_output)?;
+    if !build_plan {
+        fs::create_dir_all(&script_output)?;
+        fs::create_dir_all(&build_output)?;
+    }
 
     let root_output = cx.target_root().to_path_buf();
 
@@ -203,6 +243,13 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     // Note that this has to do some extra work just before running the command
     // to determine extra environment variables and such.
     let dirty = Work::new(move |state| {
+        // A build plan only records the command, which can't run before any
+        // of the other build scripts have.
+        if build_plan {
+            state.build_plan(cmd, Arc::new(Vec::new()));
+            return Ok(())
+        }
+
         // Make sure that OUT_DIR exists.
         //
         // If we have an old build directory, then just move it into place,
@@ -273,7 +320,10 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
                 linked_libs: &parsed_output.library_links,
                 linked_paths: &library_paths,
                 cfgs: &parsed_output.cfgs,
+                check_cfgs: &parsed_output.check_cfgs,
+                linker_args: &parsed_output.linker_args,
                 env: &parsed_output.env,
+                out_dir: &build_output.display().to_string(),
             });
         }
 
@@ -301,7 +351,9 @@ impl BuildState {
     pub fn new(config: &super::BuildConfig) -> BuildState {
         let mut overrides = HashMap::new();
         let i1 = config.host.overrides.iter().map(|p| (p, Kind::Host));
-        let i2 = config.target.overrides.iter().map(|p| (p, Kind::Target));
+        let i2 = config.targets.iter().enumerate().flat_map(|(i, target)| {
+            target.overrides.iter().map(move |p| (p, Kind::Target(i)))
+        });
         for ((name, output), kind) in i1.chain(i2) {
             overrides.insert((name.clone(), kind), output.clone());
         }
@@ -340,6 +392,8 @@ impl BuildOutput {
         let mut library_paths = Vec::new();
         let mut library_links = Vec::new();
         let mut cfgs = Vec::new();
+        let mut check_cfgs = Vec::new();
+        let mut linker_args = Vec::new();
         let mut env = Vec::new();
         let mut metadata = Vec::new();
         let mut rerun_if_changed = Vec::new();
@@ -381,12 +435,28 @@ impl BuildOutput {
                 }
                 "rustc-link-lib" => library_links.push(value.to_string()),
                 "rustc-link-search" => library_paths.push(PathBuf::from(value)),
+                "rustc-link-arg" => linker_args.push((LinkType::All, value.to_string())),
+                "rustc-link-arg-bins" => linker_args.push((LinkType::Bin, value.to_string())),
+                "rustc-cdylib-link-arg" => {
+                    linker_args.push((LinkType::Cdylib, value.to_string()))
+                }
                 "rustc-cfg" => cfgs.push(value.to_string()),
+                "rustc-check-cfg" => check_cfgs.push(value.to_string()),
                 "rustc-env" => env.push(BuildOutput::parse_rustc_env(value, &whence)?),
+                "metadata" => metadata.push(BuildOutput::parse_metadata(value, &whence)?),
                 "warning" => warnings.push(value.to_string()),
                 "rerun-if-changed" => rerun_if_changed.push(value.to_string()),
                 "rerun-if-env-changed" => rerun_if_env_changed.push(value.to_string()),
-                _ => metadata.push((key.to_string(), value.to_string())),
+                _ => {
+                    // Before `metadata=` existed any other key was metadata,
+                    // so these are still accepted, but a misspelled directive
+                    // shouldn't go unnoticed.
+                    warnings.push(format!("unknown directive `baler:{}` in {} is \
+                                           treated as metadata, print \
+                                           `baler:metadata={}={}` instead",
+                                          key, whence, key, value));
+                    metadata.push((key.to_string(), value.to_string()))
+                }
             }
         }
 
@@ -394,6 +464,8 @@ impl BuildOutput {
             library_paths: library_paths,
             library_links: library_links,
             cfgs: cfgs,
+            check_cfgs: check_cfgs,
+            linker_args: linker_args,
             env: env,
             metadata: metadata,
             rerun_if_changed: rerun_if_changed,
@@ -433,6 +505,17 @@ impl BuildOutput {
         Ok((library_paths, library_links))
     }
 
+    pub fn parse_metadata(value: &str, whence: &str)
+                          -> CargoResult<(String, String)> {
+        let mut iter = value.splitn(2, '=');
+        let key = iter.next();
+        let val = iter.next();
+        match (key, val) {
+            (Some(k), Some(v)) => Ok((k.to_owned(), v.to_owned())),
+            _ => bail!("Variable metadata has no value in {}: {}", whence, value),
+        }
+    }
+
     pub fn parse_rustc_env(value: &str, whence: &str)
                            -> CargoResult<(String, String)> {
         let mut iter = value.splitn(2, '=');
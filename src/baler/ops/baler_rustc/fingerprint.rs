@@ -1,19 +1,24 @@
+use std::collections::HashMap;
 use std::env;
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs::{self, File, OpenOptions};
 use std::hash::{self, Hasher};
 use std::io::prelude::*;
 use std::io::{BufReader, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use filetime::FileTime;
+use hex::ToHex;
 use serde::ser::{self, Serialize};
 use serde::de::{self, Deserialize};
 use serde_json;
 
 use core::{Package, TargetKind};
 use util;
-use util::{Fresh, Dirty, Freshness, internal, profile};
+use util::{Fresh, Dirty, Freshness, Sha256, internal, profile};
 use util::errors::{CargoResult, CargoResultExt};
 use util::paths;
 
@@ -87,17 +92,25 @@ pub fn prepare_target<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
         missing_outputs = !root.join(unit.target.crate_name())
                                .join("index.html").exists();
     } else {
+        let rmeta_required = cx.rmeta_required(unit);
         for &(ref src, ref link_dst, _) in cx.target_filenames(unit)?.iter() {
             missing_outputs |= !src.exists();
             if let Some(ref link_dst) = *link_dst {
                 missing_outputs |= !link_dst.exists();
             }
+            // Dependents are compiled against the metadata of a pipelined
+            // rlib, which an earlier build may not have written.
+            if rmeta_required && src.extension() == Some(OsStr::new("rlib")) {
+                missing_outputs |= !src.with_extension("rmeta").exists();
+            }
         }
     }
 
     let allow_failure = unit.profile.rustc_args.is_some();
+    let hashes = cx.content_hashes.clone();
+    let started = now();
     let write_fingerprint = Work::new(move |_| {
-        match fingerprint.update_local() {
+        match fingerprint.update_local(&hashes, started) {
             Ok(()) => {}
             Err(..) if allow_failure => return Ok(()),
             Err(e) => return Err(e)
@@ -106,6 +119,10 @@ pub fn prepare_target<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     });
 
     let fresh = compare.is_ok() && !missing_outputs;
+    if !fresh {
+        let reason = compare.err().unwrap_or(DirtyReason::MissingOutputs);
+        cx.dirty_reasons.insert(*unit, reason);
+    }
     Ok((if fresh {Fresh} else {Dirty}, write_fingerprint, Work::noop()))
 }
 
@@ -176,12 +193,141 @@ enum LocalFingerprint {
     Precalculated(String),
     MtimeBased(MtimeSlot, PathBuf),
     EnvBased(String, Option<String>),
+    ContentBased(ContentSlot, PathBuf),
+    FilesContentBased(ContentSlot, Vec<PathBuf>),
+}
+
+/// The mtime of a unit's output, if it's newer than all of its inputs.
+///
+/// When an input is newer (or missing), the second field records which one, to
+/// explain the rebuild. It isn't persisted.
+struct MtimeSlot(Mutex<Option<FileTime>>, Option<StaleFile>);
+
+struct StaleFile {
+    path: PathBuf,
+    mtime: Option<FileTime>,
 }
 
-struct MtimeSlot(Mutex<Option<FileTime>>);
+/// Why a unit has to be rebuilt, as shown in verbose output and emitted in
+/// `unit-dirty-reason` messages.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum DirtyReason {
+    NoPreviousBuild,
+    FingerprintUnreadable,
+    RustcChanged,
+    FeaturesChanged { old: String, new: String },
+    TargetChanged,
+    ProfileChanged,
+    RustflagsChanged { old: Vec<String>, new: Vec<String> },
+    LocalFingerprintChanged,
+    PrecalculatedChanged { old: String, new: String },
+    StaleFile {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_mtime")]
+        previous_mtime: Option<FileTime>,
+        #[serde(serialize_with = "serialize_mtime")]
+        current_mtime: Option<FileTime>,
+    },
+    InputFilesMissing,
+    InputFilesChanged,
+    FileContentsChanged { path: PathBuf },
+    EnvVarChanged { name: String, old: Option<String>, new: Option<String> },
+    NumberOfDependenciesChanged,
+    DependencyChanged { name: String },
+    BuildScriptRebuilt,
+    BuildScriptRerun,
+    MissingOutputs,
+    Forced,
+}
+
+fn serialize_mtime<S>(mtime: &Option<FileTime>, s: S) -> Result<S::Ok, S::Error>
+    where S: ser::Serializer,
+{
+    mtime.map(|ft| {
+        ft.seconds_relative_to_1970() as f64 + ft.nanoseconds() as f64 / 1e9
+    }).serialize(s)
+}
+
+impl fmt::Display for DirtyReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DirtyReason::NoPreviousBuild => write!(f, "it hasn't been built before"),
+            DirtyReason::FingerprintUnreadable => {
+                write!(f, "the previous fingerprint couldn't be read")
+            }
+            DirtyReason::RustcChanged => write!(f, "the rust compiler has changed"),
+            DirtyReason::FeaturesChanged { ref old, ref new } => {
+                write!(f, "the features have changed: previously {} now {}", old, new)
+            }
+            DirtyReason::TargetChanged => {
+                write!(f, "the target configuration has changed")
+            }
+            DirtyReason::ProfileChanged => {
+                write!(f, "the profile configuration has changed")
+            }
+            DirtyReason::RustflagsChanged { ref old, ref new } => {
+                write!(f, "the compiler flags have changed: previously {:?} now {:?}",
+                       old, new)
+            }
+            DirtyReason::LocalFingerprintChanged => {
+                write!(f, "the way its sources are tracked has changed")
+            }
+            DirtyReason::PrecalculatedChanged { ref old, ref new } => {
+                write!(f, "its sources have changed: previously {} now {}", old, new)
+            }
+            DirtyReason::StaleFile { ref path, previous_mtime, current_mtime } => {
+                match (previous_mtime, current_mtime) {
+                    (_, None) => write!(f, "the file `{}` is missing", path.display()),
+                    (None, Some(now)) => {
+                        write!(f, "the file `{}` has changed (modified at {})",
+                               path.display(), now)
+                    }
+                    (Some(then), Some(now)) => {
+                        write!(f, "the file `{}` has changed (modified at {}, \
+                                   last built at {})", path.display(), now, then)
+                    }
+                }
+            }
+            DirtyReason::InputFilesMissing => {
+                write!(f, "some of its input files are missing")
+            }
+            DirtyReason::InputFilesChanged => {
+                write!(f, "the set of its input files has changed")
+            }
+            DirtyReason::FileContentsChanged { ref path } => {
+                write!(f, "the contents of `{}` have changed", path.display())
+            }
+            DirtyReason::EnvVarChanged { ref name, ref old, ref new } => {
+                write!(f, "the environment variable `{}` has changed: \
+                           previously {:?} now {:?}", name, old, new)
+            }
+            DirtyReason::NumberOfDependenciesChanged => {
+                write!(f, "the number of dependencies has changed")
+            }
+            DirtyReason::DependencyChanged { ref name } => {
+                write!(f, "the dependency `{}` was rebuilt", name)
+            }
+            DirtyReason::BuildScriptRebuilt => write!(f, "its build script was rebuilt"),
+            DirtyReason::BuildScriptRerun => write!(f, "its build script was run again"),
+            DirtyReason::MissingOutputs => write!(f, "some of its outputs are missing"),
+            DirtyReason::Forced => write!(f, "a rebuild was requested"),
+        }
+    }
+}
+
+/// The hashes of every input file of a unit, in the order the inputs were
+/// listed. `None` if any of them couldn't be read.
+struct ContentSlot(Mutex<Option<Vec<(PathBuf, String)>>>);
 
 impl Fingerprint {
-    fn update_local(&self) -> CargoResult<()> {
+    /// Records the state of the inputs the unit was just built from.
+    ///
+    /// Content hashes are the ones taken before the build where there are
+    /// any, as an input may have been edited while it ran. Inputs only
+    /// learned about afterwards which were modified since `started` get a
+    /// hash matching no contents, so they're checked again next time.
+    fn update_local(&self, hashes: &ContentHashes, started: FileTime) -> CargoResult<()> {
         let mut hash_busted = false;
         for local in self.local.iter() {
             match *local {
@@ -193,6 +339,31 @@ impl Fingerprint {
                     let mtime = FileTime::from_last_modification_time(&meta);
                     *slot.0.lock().unwrap() = Some(mtime);
                 }
+                LocalFingerprint::ContentBased(ref slot, ref dep_info) => {
+                    let paths = parse_dep_info(dep_info)?.ok_or_else(|| {
+                        internal(format!("failed to read dep-info `{}`",
+                                         dep_info.display()))
+                    })?;
+                    let mut slot = slot.0.lock().unwrap();
+                    let before = slot.take().unwrap_or_default()
+                                     .into_iter().collect::<HashMap<_, _>>();
+                    let contents = paths.iter().map(|path| {
+                        let hash = match before.get(path) {
+                            Some(hash) => hash.clone(),
+                            None => hashes.hash_unless_modified(path, started)?,
+                        };
+                        Ok((path.clone(), hash))
+                    }).collect::<CargoResult<Vec<_>>>()?;
+                    *slot = Some(contents);
+                }
+                LocalFingerprint::FilesContentBased(ref slot, ref paths) => {
+                    // These are only known once the build script has run, so
+                    // whatever is in the slot may have been hashed after it.
+                    let contents = paths.iter().map(|path| {
+                        Ok((path.clone(), hashes.hash_unless_modified(path, started)?))
+                    }).collect::<CargoResult<Vec<_>>>()?;
+                    *slot.0.lock().unwrap() = Some(contents);
+                }
                 LocalFingerprint::EnvBased(..) |
                 LocalFingerprint::Precalculated(..) => continue,
             }
@@ -214,71 +385,96 @@ impl Fingerprint {
         ret
     }
 
-    fn compare(&self, old: &Fingerprint) -> CargoResult<()> {
+    fn compare(&self, old: &Fingerprint) -> Result<(), DirtyReason> {
         if self.rustc != old.rustc {
-            bail!("rust compiler has changed")
+            return Err(DirtyReason::RustcChanged)
         }
         if self.features != old.features {
-            bail!("features have changed: {} != {}", self.features, old.features)
+            return Err(DirtyReason::FeaturesChanged {
+                old: old.features.clone(),
+                new: self.features.clone(),
+            })
         }
         if self.target != old.target {
-            bail!("target configuration has changed")
+            return Err(DirtyReason::TargetChanged)
         }
         if self.profile != old.profile {
-            bail!("profile configuration has changed")
+            return Err(DirtyReason::ProfileChanged)
         }
         if self.rustflags != old.rustflags {
-            return Err(internal("RUSTFLAGS has changed"))
+            return Err(DirtyReason::RustflagsChanged {
+                old: old.rustflags.clone(),
+                new: self.rustflags.clone(),
+            })
         }
         if self.local.len() != old.local.len() {
-            bail!("local lens changed");
+            return Err(DirtyReason::LocalFingerprintChanged)
         }
         for (new, old) in self.local.iter().zip(&old.local) {
             match (new, old) {
                 (&LocalFingerprint::Precalculated(ref a),
                  &LocalFingerprint::Precalculated(ref b)) => {
                     if a != b {
-                        bail!("precalculated components have changed: {} != {}",
-                              a, b)
+                        return Err(DirtyReason::PrecalculatedChanged {
+                            old: b.clone(),
+                            new: a.clone(),
+                        })
                     }
                 }
-                (&LocalFingerprint::MtimeBased(ref on_disk_mtime, ref ap),
-                 &LocalFingerprint::MtimeBased(ref previously_built_mtime, ref bp)) => {
-                    let on_disk_mtime = on_disk_mtime.0.lock().unwrap();
-                    let previously_built_mtime = previously_built_mtime.0.lock().unwrap();
+                (&LocalFingerprint::MtimeBased(ref on_disk, ref ap),
+                 &LocalFingerprint::MtimeBased(ref previously_built, _)) => {
+                    let on_disk_mtime = *on_disk.0.lock().unwrap();
+                    let previously_built_mtime = *previously_built.0.lock().unwrap();
 
-                    let should_rebuild = match (*on_disk_mtime, *previously_built_mtime) {
+                    let should_rebuild = match (on_disk_mtime, previously_built_mtime) {
                         (None, None) => false,
                         (Some(_), None) | (None, Some(_)) => true,
                         (Some(on_disk), Some(previously_built)) => on_disk > previously_built,
                     };
 
                     if should_rebuild {
-                        bail!("mtime based components have changed: previously {:?} now {:?}, \
-                               paths are {:?} and {:?}",
-                              *previously_built_mtime, *on_disk_mtime, ap, bp)
+                        // Blame the input which was found to be stale, or
+                        // otherwise the output itself.
+                        let (path, mtime) = match on_disk.1 {
+                            Some(ref stale) => (stale.path.clone(), stale.mtime),
+                            None => (ap.clone(), on_disk_mtime),
+                        };
+                        return Err(DirtyReason::StaleFile {
+                            path: path,
+                            previous_mtime: previously_built_mtime,
+                            current_mtime: mtime,
+                        })
                     }
                 }
                 (&LocalFingerprint::EnvBased(ref akey, ref avalue),
                  &LocalFingerprint::EnvBased(ref bkey, ref bvalue)) => {
                     if *akey != *bkey {
-                        bail!("env vars changed: {} != {}", akey, bkey);
+                        return Err(DirtyReason::LocalFingerprintChanged)
                     }
                     if *avalue != *bvalue {
-                        bail!("env var `{}` changed: previously {:?} now {:?}",
-                              akey, bvalue, avalue)
+                        return Err(DirtyReason::EnvVarChanged {
+                            name: akey.clone(),
+                            old: bvalue.clone(),
+                            new: avalue.clone(),
+                        })
                     }
                 }
-                _ => bail!("local fingerprint type has changed"),
+                (&LocalFingerprint::ContentBased(ref a, _),
+                 &LocalFingerprint::ContentBased(ref b, _)) |
+                (&LocalFingerprint::FilesContentBased(ref a, _),
+                 &LocalFingerprint::FilesContentBased(ref b, _)) => {
+                    compare_contents(a, b)?
+                }
+                _ => return Err(DirtyReason::LocalFingerprintChanged),
             }
         }
 
         if self.deps.len() != old.deps.len() {
-            bail!("number of dependencies has changed")
+            return Err(DirtyReason::NumberOfDependenciesChanged)
         }
         for (a, b) in self.deps.iter().zip(old.deps.iter()) {
             if a.1.hash() != b.1.hash() {
-                bail!("new ({}) != old ({})", a.0, b.0)
+                return Err(DirtyReason::DependencyChanged { name: a.0.clone() })
             }
         }
         Ok(())
@@ -301,6 +497,27 @@ impl hash::Hash for Fingerprint {
     }
 }
 
+fn compare_contents(new: &ContentSlot, old: &ContentSlot) -> Result<(), DirtyReason> {
+    let new = new.0.lock().unwrap();
+    let old = old.0.lock().unwrap();
+    let (new, old) = match (&*new, &*old) {
+        (&Some(ref new), &Some(ref old)) => (new, old),
+        _ => return Err(DirtyReason::InputFilesMissing),
+    };
+    if new.len() != old.len() {
+        return Err(DirtyReason::InputFilesChanged)
+    }
+    for (&(ref np, ref nh), &(ref op, ref oh)) in new.iter().zip(old) {
+        if np != op {
+            return Err(DirtyReason::InputFilesChanged)
+        }
+        if nh != oh {
+            return Err(DirtyReason::FileContentsChanged { path: np.clone() })
+        }
+    }
+    Ok(())
+}
+
 impl hash::Hash for MtimeSlot {
     fn hash<H: Hasher>(&self, h: &mut H) {
         self.0.lock().unwrap().hash(h)
@@ -324,7 +541,147 @@ impl<'de> de::Deserialize<'de> for MtimeSlot {
         let kind: Option<(u64, u32)> = de::Deserialize::deserialize(d)?;
         Ok(MtimeSlot(Mutex::new(kind.map(|(s, n)| {
             FileTime::from_seconds_since_1970(s, n)
-        }))))
+        })), None))
+    }
+}
+
+impl hash::Hash for ContentSlot {
+    fn hash<H: Hasher>(&self, h: &mut H) {
+        self.0.lock().unwrap().hash(h)
+    }
+}
+
+impl ser::Serialize for ContentSlot {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+        where S: ser::Serializer,
+    {
+        self.0.lock().unwrap().serialize(s)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for ContentSlot {
+    fn deserialize<D>(d: D) -> Result<ContentSlot, D::Error>
+        where D: de::Deserializer<'de>,
+    {
+        let contents: Option<Vec<(PathBuf, String)>> = de::Deserialize::deserialize(d)?;
+        Ok(ContentSlot(Mutex::new(contents)))
+    }
+}
+
+/// A cache of the content hashes of build inputs, used when
+/// `build.freshness = "content"`.
+///
+/// Entries are keyed by path and only reused while the size and mtime of the
+/// file are unchanged, so a fresh build only has to stat its inputs and
+/// anything that was touched gets read again. The cache is persisted in the
+/// fingerprint directory across builds.
+///
+/// A file whose mtime isn't older than when it was hashed could have been
+/// changed again within the same tick of the clock without its mtime moving,
+/// so such entries are never reused.
+#[derive(Default)]
+pub struct ContentHashes {
+    hashes: Mutex<HashMap<PathBuf, CachedHash>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedHash {
+    size: u64,
+    mtime: (u64, u32),
+    /// When the file was read, in seconds since the epoch.
+    hashed_at: u64,
+    hash: String,
+}
+
+impl ContentHashes {
+    pub fn new() -> ContentHashes {
+        ContentHashes::default()
+    }
+
+    /// Loads the cache at `path`. A missing or unreadable cache is treated as
+    /// empty, as it'll just be rebuilt.
+    pub fn load(path: &Path) -> ContentHashes {
+        let hashes = paths::read(path).ok().and_then(|s| {
+            serde_json::from_str::<Vec<(PathBuf, CachedHash)>>(&s).ok()
+        }).unwrap_or_default();
+        ContentHashes {
+            hashes: Mutex::new(hashes.into_iter().collect()),
+        }
+    }
+
+    /// Writes the cache out to `path`, dropping entries for files which no
+    /// longer exist.
+    pub fn save(&self, path: &Path) -> CargoResult<()> {
+        let hashes = self.hashes.lock().unwrap();
+        let mut entries = hashes.iter().filter(|&(path, _)| {
+            path.exists()
+        }).collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        paths::write(path, &serde_json::to_vec(&entries).unwrap())
+    }
+
+    /// Returns the hash of the contents of `path`, reading the file only if
+    /// it's not in the cache or has been modified since it was cached.
+    fn hash(&self, path: &Path) -> CargoResult<String> {
+        let meta = fs::metadata(path).chain_err(|| {
+            internal(format!("failed to stat `{}`", path.display()))
+        })?;
+        let mtime = FileTime::from_last_modification_time(&meta);
+        let mtime = (mtime.seconds_relative_to_1970(), mtime.nanoseconds());
+
+        // `rerun-if-changed` may name a directory, which has no contents of
+        // its own to hash, so fall back to its mtime.
+        if meta.is_dir() {
+            return Ok(format!("directory modified at {}.{:09}", mtime.0, mtime.1))
+        }
+
+        if let Some(cached) = self.hashes.lock().unwrap().get(path) {
+            if cached.size == meta.len() && cached.mtime == mtime &&
+               mtime.0 < cached.hashed_at {
+                return Ok(cached.hash.clone())
+            }
+        }
+
+        let hashed_at = now().seconds_relative_to_1970();
+
+        let mut h = Sha256::new();
+        let mut buf = [0; 16 * 1024];
+        (|| -> CargoResult<()> {
+            let mut f = File::open(path)?;
+            loop {
+                match f.read(&mut buf)? {
+                    0 => return Ok(()),
+                    n => h.update(&buf[..n]),
+                }
+            }
+        })().chain_err(|| {
+            format!("failed to calculate checksum of: {}", path.display())
+        })?;
+        let hash = h.finish().to_hex();
+        debug!("hashed {}: {}", path.display(), hash);
+
+        self.hashes.lock().unwrap().insert(path.to_path_buf(), CachedHash {
+            size: meta.len(),
+            mtime: mtime,
+            hashed_at: hashed_at,
+            hash: hash.clone(),
+        });
+        Ok(hash)
+    }
+
+    /// Like `hash`, but for a file which was modified since `started` returns
+    /// a hash that matches no contents, as what was built may have read it
+    /// before it was modified.
+    fn hash_unless_modified(&self, path: &Path, started: FileTime) -> CargoResult<String> {
+        let meta = fs::metadata(path).chain_err(|| {
+            internal(format!("failed to stat `{}`", path.display()))
+        })?;
+        let mtime = FileTime::from_last_modification_time(&meta);
+        if mtime >= started {
+            return Ok(format!("modified during the build at {}.{:09}",
+                              mtime.seconds_relative_to_1970(), mtime.nanoseconds()))
+        }
+        self.hash(path)
     }
 }
 
@@ -365,8 +722,14 @@ fn calculate<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     // And finally, calculate what our own local fingerprint is
     let local = if use_dep_info(unit) {
         let dep_info = dep_info_loc(cx, unit);
-        let mtime = dep_info_mtime_if_fresh(&dep_info)?;
-        LocalFingerprint::MtimeBased(MtimeSlot(Mutex::new(mtime)), dep_info)
+        if cx.build_config.content_freshness {
+            let contents = dep_info_contents(&cx.content_hashes, &dep_info)?;
+            LocalFingerprint::ContentBased(ContentSlot(Mutex::new(contents)),
+                                           dep_info)
+        } else {
+            let mtime = dep_info_mtime_if_fresh(&dep_info)?;
+            LocalFingerprint::MtimeBased(mtime, dep_info)
+        }
     } else {
         let fingerprint = pkg_fingerprint(cx, unit.pkg)?;
         LocalFingerprint::Precalculated(fingerprint)
@@ -380,7 +743,10 @@ fn calculate<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     };
     let fingerprint = Arc::new(Fingerprint {
         rustc: util::hash_u64(&cx.config.rustc()?.verbose_version),
-        target: util::hash_u64(&unit.target),
+        target: match cx.target_spec_hash(unit.kind) {
+            Some(spec) => util::hash_u64(&(&unit.target, spec)),
+            None => util::hash_u64(&unit.target),
+        },
         profile: util::hash_u64(&unit.profile),
         features: format!("{:?}", cx.resolve.features_sorted(unit.pkg.package_id())),
         deps: deps,
@@ -441,6 +807,9 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     };
     let compare = compare_old_fingerprint(&loc, &fingerprint);
     log_compare(unit, &compare);
+    if let Err(ref reason) = compare {
+        cx.dirty_reasons.insert(*unit, reason.clone());
+    }
 
     // When we write out the fingerprint, we may want to actually change the
     // kind of fingerprint being recorded. If we started out, then the previous
@@ -455,6 +824,9 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let state = cx.build_state.clone();
     let key = (unit.pkg.package_id().clone(), unit.kind);
     let root = unit.pkg.root().to_path_buf();
+    let hashes = cx.content_hashes.clone();
+    let content_freshness = cx.build_config.content_freshness;
+    let started = now();
     let write_fingerprint = Work::new(move |_| {
         if let Some(output_path) = output_path {
             let outputs = state.outputs.lock().unwrap();
@@ -462,8 +834,9 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
             if !outputs.rerun_if_changed.is_empty() ||
                !outputs.rerun_if_env_changed.is_empty() {
                 let deps = BuildDeps::new(&output_path, Some(outputs));
-                fingerprint.local = local_fingerprints_deps(&deps, &root);
-                fingerprint.update_local()?;
+                let content = if content_freshness {Some(&*hashes)} else {None};
+                fingerprint.local = local_fingerprints_deps(&deps, &root, content);
+                fingerprint.update_local(&hashes, started)?;
             }
         }
         write_fingerprint(&loc, &fingerprint)
@@ -506,18 +879,35 @@ fn build_script_local_fingerprints<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     // Ok so now we're in "new mode" where we can have files listed as
     // dependencies as well as env vars listed as dependencies. Process them all
     // here.
-    Ok((local_fingerprints_deps(deps, unit.pkg.root()), Some(output)))
+    let content = if cx.build_config.content_freshness {
+        Some(&*cx.content_hashes)
+    } else {
+        None
+    };
+    Ok((local_fingerprints_deps(deps, unit.pkg.root(), content), Some(output)))
 }
 
-fn local_fingerprints_deps(deps: &BuildDeps, root: &Path) -> Vec<LocalFingerprint> {
+fn local_fingerprints_deps(deps: &BuildDeps,
+                           root: &Path,
+                           content: Option<&ContentHashes>)
+                           -> Vec<LocalFingerprint> {
     debug!("new local fingerprints deps");
     let mut local = Vec::new();
     if !deps.rerun_if_changed.is_empty() {
-        let output = &deps.build_script_output;
-        let deps = deps.rerun_if_changed.iter().map(|p| root.join(p));
-        let mtime = mtime_if_fresh(output, deps);
-        let mtime = MtimeSlot(Mutex::new(mtime));
-        local.push(LocalFingerprint::MtimeBased(mtime, output.clone()));
+        let paths = deps.rerun_if_changed.iter().map(|p| root.join(p));
+        match content {
+            Some(hashes) => {
+                let paths = paths.collect::<Vec<_>>();
+                let contents = contents_if_present(hashes, &paths);
+                let contents = ContentSlot(Mutex::new(contents));
+                local.push(LocalFingerprint::FilesContentBased(contents, paths));
+            }
+            None => {
+                let output = &deps.build_script_output;
+                let mtime = mtime_if_fresh(output, paths);
+                local.push(LocalFingerprint::MtimeBased(mtime, output.clone()));
+            }
+        }
     }
 
     for var in deps.rerun_if_env_changed.iter() {
@@ -537,6 +927,13 @@ fn write_fingerprint(loc: &Path, fingerprint: &Fingerprint) -> CargoResult<()> {
     Ok(())
 }
 
+/// Returns the hash of the fingerprint of `unit`, which identifies the inputs
+/// it's built from.
+pub fn fingerprint_hash<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
+                                  -> CargoResult<u64> {
+    Ok(calculate(cx, unit)?.hash())
+}
+
 /// Prepare for work when a package starts to build
 pub fn prepare_init(cx: &mut Context, unit: &Unit) -> CargoResult<()> {
     let new1 = cx.fingerprint_dir(unit);
@@ -545,37 +942,64 @@ pub fn prepare_init(cx: &mut Context, unit: &Unit) -> CargoResult<()> {
         fs::create_dir(&new1)?;
     }
 
+    // Recorded so that `baler clean --stale` can tell which package the
+    // artifacts sharing this directory's hash were built from.
+    let package_loc = package_id_loc(&new1);
+    if fs::metadata(&package_loc).is_err() {
+        paths::write(&package_loc, unit.pkg.package_id().to_string().as_bytes())?;
+    }
+
     Ok(())
 }
 
+/// Returns where the id of the package a unit is built from is recorded in its
+/// fingerprint directory `dir`.
+pub fn package_id_loc(dir: &Path) -> PathBuf {
+    dir.join("package-id")
+}
+
 pub fn dep_info_loc(cx: &mut Context, unit: &Unit) -> PathBuf {
     cx.fingerprint_dir(unit).join(&format!("dep-{}", filename(cx, unit)))
 }
 
+/// Returns where the diagnostics rustc printed while compiling `unit` are
+/// kept, so they can be shown again when it's fresh.
+pub fn output_loc(cx: &mut Context, unit: &Unit) -> PathBuf {
+    cx.fingerprint_dir(unit).join(&format!("output-{}", filename(cx, unit)))
+}
+
 fn compare_old_fingerprint(loc: &Path, new_fingerprint: &Fingerprint)
-                           -> CargoResult<()> {
-    let old_fingerprint_short = paths::read(loc)?;
+                           -> Result<(), DirtyReason> {
+    let old_fingerprint_short = match paths::read(loc) {
+        Ok(s) => s,
+        Err(..) => return Err(DirtyReason::NoPreviousBuild),
+    };
     let new_hash = new_fingerprint.hash();
 
     if util::to_hex(new_hash) == old_fingerprint_short {
         return Ok(())
     }
 
+    let old_fingerprint = match read_old_fingerprint(loc) {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            info!("failed to read old fingerprint at {}: {}", loc.display(), e);
+            return Err(DirtyReason::FingerprintUnreadable)
+        }
+    };
+    new_fingerprint.compare(&old_fingerprint)
+}
+
+fn read_old_fingerprint(loc: &Path) -> CargoResult<Fingerprint> {
     let old_fingerprint_json = paths::read(&loc.with_extension("json"))?;
     let old_fingerprint = serde_json::from_str(&old_fingerprint_json)
         .chain_err(|| internal(format!("failed to deserialize json")))?;
-    new_fingerprint.compare(&old_fingerprint)
+    Ok(old_fingerprint)
 }
 
-fn log_compare(unit: &Unit, compare: &CargoResult<()>) {
-    let ce = match *compare {
-        Ok(..) => return,
-        Err(ref e) => e,
-    };
-    info!("fingerprint error for {}: {}", unit.pkg, ce);
-
-    for cause in ce.iter() {
-        info!("  cause: {}", cause);
+fn log_compare(unit: &Unit, compare: &Result<(), DirtyReason>) {
+    if let Err(ref reason) = *compare {
+        info!("fingerprint error for {}: {}", unit.pkg, reason);
     }
 }
 
@@ -617,14 +1041,43 @@ pub fn parse_dep_info(dep_info: &Path) -> CargoResult<Option<Vec<PathBuf>>> {
     Ok(Some(paths))
 }
 
-fn dep_info_mtime_if_fresh(dep_info: &Path) -> CargoResult<Option<FileTime>> {
+fn dep_info_mtime_if_fresh(dep_info: &Path) -> CargoResult<MtimeSlot> {
     if let Some(paths) = parse_dep_info(dep_info)? {
         Ok(mtime_if_fresh(dep_info, paths.iter()))
+    } else {
+        Ok(MtimeSlot(Mutex::new(None), None))
+    }
+}
+
+fn dep_info_contents(hashes: &ContentHashes, dep_info: &Path)
+                     -> CargoResult<Option<Vec<(PathBuf, String)>>> {
+    if let Some(paths) = parse_dep_info(dep_info)? {
+        Ok(contents_if_present(hashes, &paths))
     } else {
         Ok(None)
     }
 }
 
+fn contents_if_present(hashes: &ContentHashes, paths: &[PathBuf])
+                       -> Option<Vec<(PathBuf, String)>> {
+    let mut contents = Vec::new();
+    for path in paths {
+        match hashes.hash(path) {
+            Ok(hash) => contents.push((path.clone(), hash)),
+            Err(e) => {
+                info!("stale: {} -- {}", path.display(), e);
+                return None
+            }
+        }
+    }
+    Some(contents)
+}
+
+fn now() -> FileTime {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    FileTime::from_seconds_since_1970(now.as_secs(), now.subsec_nanos())
+}
+
 fn pkg_fingerprint(cx: &Context, pkg: &Package) -> CargoResult<String> {
     let source_id = pkg.package_id().source_id();
     let sources = cx.packages.sources();
@@ -635,39 +1088,35 @@ fn pkg_fingerprint(cx: &Context, pkg: &Package) -> CargoResult<String> {
     source.fingerprint(pkg)
 }
 
-fn mtime_if_fresh<I>(output: &Path, paths: I) -> Option<FileTime>
+fn mtime_if_fresh<I>(output: &Path, paths: I) -> MtimeSlot
     where I: IntoIterator,
           I::Item: AsRef<Path>,
 {
     let meta = match fs::metadata(output) {
         Ok(meta) => meta,
-        Err(..) => return None,
+        Err(..) => return MtimeSlot(Mutex::new(None), None),
     };
     let mtime = FileTime::from_last_modification_time(&meta);
 
-    let any_stale = paths.into_iter().any(|path| {
+    for path in paths {
         let path = path.as_ref();
         let meta = match fs::metadata(path) {
             Ok(meta) => meta,
             Err(..) => {
                 info!("stale: {} -- missing", path.display());
-                return true
+                let stale = StaleFile { path: path.to_path_buf(), mtime: None };
+                return MtimeSlot(Mutex::new(None), Some(stale))
             }
         };
         let mtime2 = FileTime::from_last_modification_time(&meta);
         if mtime2 > mtime {
             info!("stale: {} -- {} vs {}", path.display(), mtime2, mtime);
-            true
-        } else {
-            false
+            let stale = StaleFile { path: path.to_path_buf(), mtime: Some(mtime2) };
+            return MtimeSlot(Mutex::new(None), Some(stale))
         }
-    });
-
-    if any_stale {
-        None
-    } else {
-        Some(mtime)
     }
+
+    MtimeSlot(Mutex::new(Some(mtime)), None)
 }
 
 fn filename(cx: &mut Context, unit: &Unit) -> String {
@@ -710,3 +1159,32 @@ pub fn append_current_dir(path: &Path, cwd: &Path) -> CargoResult<()> {
     f.write_all(&contents)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    use filetime::{self, FileTime};
+    use tempdir::TempDir;
+
+    use super::{ContentHashes, now};
+
+    #[test]
+    fn racy_cache_entries_are_rehashed() {
+        let dir = TempDir::new("content-hashes").unwrap();
+        let path = dir.path().join("lib.rs");
+        // An mtime which isn't older than when the file is hashed.
+        let mtime = FileTime::from_seconds_since_1970(now().seconds_relative_to_1970() + 60, 0);
+
+        File::create(&path).unwrap().write_all(b"pub fn a() {}").unwrap();
+        filetime::set_file_times(&path, mtime, mtime).unwrap();
+        let hashes = ContentHashes::new();
+        let before = hashes.hash(&path).unwrap();
+
+        // Same size, same mtime, different contents.
+        File::create(&path).unwrap().write_all(b"pub fn b() {}").unwrap();
+        filetime::set_file_times(&path, mtime, mtime).unwrap();
+        assert!(hashes.hash(&path).unwrap() != before);
+    }
+}
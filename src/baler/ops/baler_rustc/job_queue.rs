@@ -3,18 +3,26 @@ use std::collections::hash_map::HashMap;
 use std::fmt;
 use std::io;
 use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::Instant;
 
 use crossbeam::{self, Scope};
 use jobserver::{Acquired, HelperThread};
+use serde_json;
 
 use core::{PackageId, Target, Profile};
 use util::{Config, DependencyQueue, Fresh, Dirty, Freshness};
 use util::{CargoResult, ProcessBuilder, profile, internal, CargoResultExt};
+use util::machine_message;
 use {handle_error};
 
 use super::{Context, Kind, Unit};
+use super::build_plan::BuildPlan;
+use super::fingerprint::DirtyReason;
 use super::job::Job;
+use super::timings::{Timings, UnitCosts};
 
 /// A management structure of the entire dependency graph to compile.
 ///
@@ -22,7 +30,7 @@ use super::job::Job;
 /// actual compilation step of each package. Packages enqueue units of work and
 /// then later on the entire graph is processed and compiled.
 pub struct JobQueue<'a> {
-    queue: DependencyQueue<Key<'a>, Vec<(Job, Freshness)>>,
+    queue: DependencyQueue<Key<'a>, Artifact, Vec<(Job, Freshness)>>,
     tx: Sender<Message<'a>>,
     rx: Receiver<Message<'a>>,
     active: usize,
@@ -30,7 +38,41 @@ pub struct JobQueue<'a> {
     compiled: HashSet<&'a PackageId>,
     documented: HashSet<&'a PackageId>,
     counts: HashMap<&'a PackageId, usize>,
+    /// Units which have been reported as being rebuilt.
+    dirty: HashSet<Key<'a>>,
+    /// Units which are copied out of the shared artifact cache rather than
+    /// compiled.
+    restored: HashSet<Key<'a>>,
     is_release: bool,
+    /// Whether to keep building the units which don't depend on a failed
+    /// unit, rather than stopping at the first failure.
+    keep_going: bool,
+    build_plan: Option<PlanState<'a>>,
+    timings: Timings<'a>,
+    /// How long units took to build, used to start the most expensive chains
+    /// of units first.
+    costs: UnitCosts,
+    /// When each dirty unit started building.
+    started: HashMap<Key<'a>, Instant>,
+}
+
+/// The build plan being recorded instead of running any jobs.
+struct PlanState<'a> {
+    plan: BuildPlan,
+    /// The invocations standing in for each finished unit. This is the unit's
+    /// own invocation, or the invocations of its dependencies if it doesn't
+    /// run anything itself.
+    indices: HashMap<Key<'a>, Vec<usize>>,
+}
+
+/// The artifacts of a unit which the units depending on it wait for.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+enum Artifact {
+    /// Everything the unit produces.
+    All,
+    /// Only the metadata of a pipelined rlib, which is written before its
+    /// object code.
+    Metadata,
 }
 
 /// A helper structure for metadata about the state of a building package.
@@ -52,13 +94,17 @@ struct Key<'a> {
 
 pub struct JobState<'a> {
     tx: Sender<Message<'a>>,
+    key: Key<'a>,
 }
 
 enum Message<'a> {
     Run(String),
+    BuildPlan(Key<'a>, ProcessBuilder, Arc<Vec<(PathBuf, Option<PathBuf>, bool)>>),
     Stdout(String),
     Stderr(String),
+    Diagnostic(String, bool),
     Token(io::Result<Acquired>),
+    Metadata(Key<'a>),
     Finish(Key<'a>, CargoResult<()>),
 }
 
@@ -67,6 +113,20 @@ impl<'a> JobState<'a> {
         let _ = self.tx.send(Message::Run(cmd.to_string()));
     }
 
+    /// Records `cmd` in the build plan instead of running it, along with the
+    /// `filenames` it would produce.
+    pub fn build_plan(&self,
+                      cmd: ProcessBuilder,
+                      filenames: Arc<Vec<(PathBuf, Option<PathBuf>, bool)>>) {
+        let _ = self.tx.send(Message::BuildPlan(self.key, cmd, filenames));
+    }
+
+    /// Tells the queue that rustc has written the metadata of a pipelined
+    /// rlib, so the units only waiting for that can start.
+    pub fn rmeta_produced(&self) {
+        let _ = self.tx.send(Message::Metadata(self.key));
+    }
+
     pub fn stdout(&self, out: &str) {
         let _ = self.tx.send(Message::Stdout(out.to_string()));
     }
@@ -74,6 +134,13 @@ impl<'a> JobState<'a> {
     pub fn stderr(&self, err: &str) {
         let _ = self.tx.send(Message::Stderr(err.to_string()));
     }
+
+    /// Prints a diagnostic rendered by the compiler through the shell, so it
+    /// respects `--quiet`. `error` is whether it's an error rather than a
+    /// warning or a note.
+    pub fn diagnostic(&self, rendered: &str, error: bool) {
+        let _ = self.tx.send(Message::Diagnostic(rendered.to_string(), error));
+    }
 }
 
 impl<'a> JobQueue<'a> {
@@ -88,7 +155,21 @@ impl<'a> JobQueue<'a> {
             compiled: HashSet::new(),
             documented: HashSet::new(),
             counts: HashMap::new(),
+            dirty: HashSet::new(),
+            restored: HashSet::new(),
             is_release: cx.build_config.release,
+            keep_going: cx.build_config.keep_going,
+            build_plan: if cx.build_config.build_plan {
+                Some(PlanState {
+                    plan: BuildPlan::new(),
+                    indices: HashMap::new(),
+                })
+            } else {
+                None
+            },
+            timings: Timings::new(cx),
+            costs: UnitCosts::default(),
+            started: HashMap::new(),
         }
     }
 
@@ -98,8 +179,19 @@ impl<'a> JobQueue<'a> {
                          job: Job,
                          fresh: Freshness) -> CargoResult<()> {
         let key = Key::new(unit);
-        let deps = key.dependencies(cx)?;
+        let mut deps = Vec::new();
+        for dep in key.dependencies(cx)? {
+            let artifact = if cx.only_requires_rmeta(unit, &dep.unit(cx)?) {
+                Artifact::Metadata
+            } else {
+                Artifact::All
+            };
+            deps.push((dep, artifact));
+        }
         self.queue.queue(Fresh, key, Vec::new(), &deps).push((job, fresh));
+        if cx.restored.contains(unit) {
+            self.restored.insert(key);
+        }
         *self.counts.entry(key.pkg).or_insert(0) += 1;
         Ok(())
     }
@@ -109,7 +201,7 @@ impl<'a> JobQueue<'a> {
     /// This function will spawn off `config.jobs()` workers to build all of the
     /// necessary dependencies, in order. Freshness is propagated as far as
     /// possible along each dependency chain.
-    pub fn execute(&mut self, cx: &mut Context) -> CargoResult<()> {
+    pub fn execute<'cfg>(&mut self, cx: &mut Context<'a, 'cfg>) -> CargoResult<()> {
         let _p = profile::start("executing the job graph");
 
         // We need to give a handle to the send half of our message queue to the
@@ -135,18 +227,33 @@ impl<'a> JobQueue<'a> {
             "failed to create helper thread for jobserver management"
         })?;
 
-        crossbeam::scope(|scope| {
+        // Prioritize the units which the most work is waiting on, using how
+        // long they took last time.
+        self.costs = UnitCosts::load(&cx.unit_costs_path());
+        {
+            let costs = &self.costs;
+            self.queue.queue_finished(&mut |key| costs.get(&key.cost_key(cx)));
+        }
+
+        let result = crossbeam::scope(|scope| {
             self.drain_the_queue(cx, scope, &helper)
-        })
+        });
+        if self.build_plan.is_none() {
+            // The costs only order the next build, so failing to save them
+            // mustn't change the outcome of this one.
+            if let Err(e) = self.costs.save(&cx.unit_costs_path()) {
+                let _ = cx.config.shell().warn(
+                    format!("failed to save how long units took to build: {}", e));
+            }
+        }
+        result
     }
 
-    fn drain_the_queue(&mut self,
-                       cx: &mut Context,
-                       scope: &Scope<'a>,
-                       jobserver_helper: &HelperThread)
-                       -> CargoResult<()> {
-        use std::time::Instant;
-
+    fn drain_the_queue<'cfg>(&mut self,
+                             cx: &mut Context<'a, 'cfg>,
+                             scope: &Scope<'a>,
+                             jobserver_helper: &HelperThread)
+                             -> CargoResult<()> {
         let mut tokens = Vec::new();
         let mut queue = Vec::new();
         trace!("queue: {:#?}", self.queue);
@@ -160,8 +267,11 @@ impl<'a> JobQueue<'a> {
         //
         // After a job has finished we update our internal state if it was
         // successful and otherwise wait for pending work to finish if it failed
-        // and then immediately return.
+        // and then immediately return. With `--keep-going` a failed unit is
+        // never finished instead, so only the units which depend on it are
+        // held back, and all the failures are reported at the end.
         let mut error = None;
+        let mut failures = Vec::new();
         let start_time = Instant::now();
         loop {
             // Dequeue as much work as we can, learning about everything
@@ -189,8 +299,13 @@ impl<'a> JobQueue<'a> {
             // we're able to perform some parallel work.
             while error.is_none() && self.active < tokens.len() + 1 && !queue.is_empty() {
                 let (key, job, fresh) = queue.remove(0);
+                self.timings.unit_start(cx, &key.unit(cx)?, fresh == Fresh)?;
+                if fresh == Dirty {
+                    self.note_dirty(cx, &key)?;
+                }
                 self.run(key, fresh, job, cx.config, scope)?;
             }
+            self.timings.mark_concurrency(self.active, queue.len(), self.queue.len());
 
             // If after all that we're not actually running anything then we're
             // done!
@@ -205,10 +320,25 @@ impl<'a> JobQueue<'a> {
             // to the jobserver itself.
             tokens.truncate(self.active - 1);
 
-            match self.rx.recv().unwrap() {
+            // Anything left in our local queue can't start until a job
+            // finishes or we're handed another token.
+            let blocked = Instant::now();
+            let message = self.rx.recv().unwrap();
+            if !queue.is_empty() {
+                self.timings.token_wait(blocked.elapsed());
+            }
+
+            match message {
                 Message::Run(cmd) => {
                     cx.config.shell().verbose(|c| c.status("Running", &cmd))?;
                 }
+                Message::BuildPlan(key, cmd, filenames) => {
+                    let deps = self.plan_deps(key, cx)?;
+                    let unit = key.unit(cx)?;
+                    let state = self.build_plan.as_mut().unwrap();
+                    let index = state.plan.add(cx, &unit, deps, &cmd, &filenames)?;
+                    state.indices.insert(key, vec![index]);
+                }
                 Message::Stdout(out) => {
                     if cx.config.extra_verbose() {
                         println!("{}", out);
@@ -219,6 +349,15 @@ impl<'a> JobQueue<'a> {
                         writeln!(cx.config.shell().err(), "{}", err)?;
                     }
                 }
+                Message::Diagnostic(rendered, error) => {
+                    cx.config.shell().diagnostic(&rendered, error)?;
+                }
+                Message::Metadata(key) => {
+                    info!("metadata ready: {:?}", key);
+                    let fresh = self.pending[&key].fresh;
+                    self.queue.finish_edge(&key, &Artifact::Metadata, fresh);
+                    self.timings.unit_rmeta_finished(&key.unit(cx)?);
+                }
                 Message::Finish(key, result) => {
                     info!("end: {:?}", key);
                     self.active -= 1;
@@ -232,7 +371,9 @@ impl<'a> JobQueue<'a> {
                             let msg = "The following warnings were emitted during compilation:";
                             self.emit_warnings(Some(msg), key, cx)?;
 
-                            if self.active > 0 {
+                            if self.keep_going {
+                                failures.push(e);
+                            } else if self.active > 0 {
                                 error = Some("build failed".into());
                                 handle_error(e, &mut *cx.config.shell());
                                 cx.config.shell().warn(
@@ -264,7 +405,13 @@ impl<'a> JobQueue<'a> {
         let time_elapsed = format!("{}.{1:.2} secs",
                                    duration.as_secs(),
                                    duration.subsec_nanos() / 10000000);
-        if self.queue.is_empty() {
+        if self.build_plan.is_none() {
+            self.timings.finished(cx)?;
+        }
+        if self.queue.is_empty() && error.is_none() && self.build_plan.is_some() {
+            self.build_plan.as_ref().unwrap().plan.output_plan();
+            Ok(())
+        } else if self.queue.is_empty() {
             let message = format!("{} [{}] target(s) in {}",
                                   build_type,
                                   opt_type,
@@ -273,6 +420,13 @@ impl<'a> JobQueue<'a> {
             Ok(())
         } else if let Some(e) = error {
             Err(e)
+        } else if failures.len() == 1 {
+            Err(failures.pop().unwrap())
+        } else if failures.len() > 1 {
+            let errors = failures.iter().map(|e| {
+                format!("\n  {}", e)
+            }).collect::<String>();
+            bail!("{} units failed to build:{}", failures.len(), errors)
         } else {
             debug!("queue: {:#?}", self.queue);
             Err(internal("finished with jobs still left in the queue"))
@@ -296,12 +450,24 @@ impl<'a> JobQueue<'a> {
         let doit = move || {
             let res = job.run(fresh, &JobState {
                 tx: my_tx.clone(),
+                key: key,
             });
             my_tx.send(Message::Finish(key, res)).unwrap();
         };
+
+        // Jobs only record what they would run for a build plan, so there's
+        // no need for them to run in parallel, nor to report progress.
+        if self.build_plan.is_some() {
+            doit();
+            return Ok(())
+        }
+
         match fresh {
             Freshness::Fresh => doit(),
-            Freshness::Dirty => { scope.spawn(doit); }
+            Freshness::Dirty => {
+                self.started.entry(key).or_insert_with(Instant::now);
+                scope.spawn(doit);
+            }
         }
 
         // Print out some nice progress information
@@ -332,15 +498,88 @@ impl<'a> JobQueue<'a> {
         Ok(())
     }
 
-    fn finish(&mut self, key: Key<'a>, cx: &mut Context) -> CargoResult<()> {
+    fn finish<'cfg>(&mut self, key: Key<'a>, cx: &mut Context<'a, 'cfg>) -> CargoResult<()> {
         if key.profile.run_custom_build && cx.show_warnings(key.pkg) {
             self.emit_warnings(None, key, cx)?;
         }
 
+        if self.build_plan.as_ref().map_or(false, |p| !p.indices.contains_key(&key)) {
+            let deps = self.plan_deps(key, cx)?;
+            self.build_plan.as_mut().unwrap().indices.insert(key, deps);
+        }
+
         let state = self.pending.get_mut(&key).unwrap();
         state.amt -= 1;
         if state.amt == 0 {
             self.queue.finish(&key, state.fresh);
+            self.timings.unit_finished(&key.unit(cx)?);
+            if let Some(start) = self.started.remove(&key) {
+                self.costs.record(key.cost_key(cx), start.elapsed());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the indices in the build plan of the invocations which `key`
+    /// depends on.
+    fn plan_deps<'cfg>(&self, key: Key<'a>, cx: &Context<'a, 'cfg>)
+                       -> CargoResult<Vec<usize>> {
+        let indices = &self.build_plan.as_ref().unwrap().indices;
+        let mut deps = key.dependencies(cx)?.iter().flat_map(|dep| {
+            indices.get(dep).cloned().unwrap_or_default()
+        }).collect::<Vec<_>>();
+        deps.sort();
+        deps.dedup();
+        Ok(deps)
+    }
+
+    /// Explains why a unit is being rebuilt when one of its jobs runs dirty.
+    ///
+    /// Units which are stale themselves have a reason recorded while their
+    /// fingerprint was prepared; otherwise they're only being rebuilt because
+    /// a dependency was. Units which haven't been built before aren't
+    /// reported, and neither are the doc tests which only run later.
+    fn note_dirty<'cfg>(&mut self,
+                        cx: &Context<'a, 'cfg>,
+                        key: &Key<'a>) -> CargoResult<()> {
+        if self.build_plan.is_some() || self.dirty.contains(key) ||
+           (key.profile.doc && key.profile.test) {
+            return Ok(())
+        }
+        let unit = key.unit(cx)?;
+        let reason = match cx.dirty_reasons.get(&unit) {
+            Some(reason) => reason.clone(),
+            None => {
+                let deps = key.dependencies(cx)?;
+                match deps.iter().find(|dep| self.dirty.contains(dep)) {
+                    Some(dep) if dep.pkg == key.pkg && dep.profile.run_custom_build => {
+                        DirtyReason::BuildScriptRerun
+                    }
+                    Some(dep) if dep.pkg == key.pkg && dep.target.is_custom_build() => {
+                        DirtyReason::BuildScriptRebuilt
+                    }
+                    Some(dep) => {
+                        DirtyReason::DependencyChanged { name: dep.pkg.to_string() }
+                    }
+                    None => return Ok(()),
+                }
+            }
+        };
+        if let DirtyReason::NoPreviousBuild = reason {
+            return Ok(())
+        }
+        self.dirty.insert(*key);
+
+        cx.config.shell().verbose(|c| {
+            c.status("Dirty", format!("{}: {}", key.pkg, reason))
+        })?;
+        if cx.build_config.json_messages {
+            machine_message::emit(machine_message::UnitDirtyReason {
+                package_id: key.pkg,
+                target: key.target,
+                dirty_reason: serde_json::to_value(&reason).unwrap(),
+                message: reason.to_string(),
+            });
         }
         Ok(())
     }
@@ -372,6 +611,9 @@ impl<'a> JobQueue<'a> {
                         self.documented.insert(key.pkg);
                         config.shell().status("Documenting", key.pkg)?;
                     }
+                } else if self.restored.contains(key) {
+                    self.compiled.insert(key.pkg);
+                    config.shell().status("Restored", key.pkg)?;
                 } else {
                     self.compiled.insert(key.pkg);
                     config.shell().status("Compiling", key.pkg)?;
@@ -397,14 +639,25 @@ impl<'a> Key<'a> {
         }
     }
 
-    fn dependencies<'cfg>(&self, cx: &Context<'a, 'cfg>)
-                          -> CargoResult<Vec<Key<'a>>> {
-        let unit = Unit {
+    fn unit<'cfg>(&self, cx: &Context<'a, 'cfg>) -> CargoResult<Unit<'a>> {
+        Ok(Unit {
             pkg: cx.get_package(self.pkg)?,
             target: self.target,
             profile: self.profile,
             kind: self.kind,
-        };
+        })
+    }
+
+    /// The name this unit's build time is saved under, which stays the same
+    /// from one build to the next.
+    fn cost_key<'cfg>(&self, cx: &Context<'a, 'cfg>) -> String {
+        format!("{} {} {} {} {}", self.pkg, self.target, self.target.name(),
+                self.profile, cx.triple(self.kind))
+    }
+
+    fn dependencies<'cfg>(&self, cx: &Context<'a, 'cfg>)
+                          -> CargoResult<Vec<Key<'a>>> {
+        let unit = self.unit(cx)?;
         let targets = cx.dep_targets(&unit)?;
         Ok(targets.iter().filter_map(|unit| {
             // Binaries aren't actually needed to *compile* tests, just to run
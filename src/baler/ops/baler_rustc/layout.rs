@@ -64,7 +64,7 @@ pub struct Layout {
     incremental: PathBuf,
     fingerprint: PathBuf,
     examples: PathBuf,
-    _lock: FileLock,
+    _lock: Option<FileLock>,
 }
 
 pub fn is_bad_artifact_name(name: &str) -> bool {
@@ -78,6 +78,21 @@ impl Layout {
     pub fn new(ws: &Workspace,
                triple: Option<&str>,
                dest: &str) -> CargoResult<Layout> {
+        Layout::at(ws.config(), Layout::path(ws, triple, dest)?)
+    }
+
+    /// Returns the layout of a build which only plans what it would do, and
+    /// so neither creates nor locks the directory.
+    pub fn unlocked(ws: &Workspace,
+                    triple: Option<&str>,
+                    dest: &str) -> CargoResult<Layout> {
+        let root = Layout::path(ws, triple, dest)?.into_path_unlocked();
+        Ok(Layout::with_lock(root, None))
+    }
+
+    fn path(ws: &Workspace,
+            triple: Option<&str>,
+            dest: &str) -> CargoResult<Filesystem> {
         let mut path = ws.target_dir();
         // Flexible target specifications often point at filenames, so interpret
         // the target triple as a Path and then just use the file stem as the
@@ -86,7 +101,7 @@ impl Layout {
             path.push(Path::new(triple).file_stem().ok_or_else(|| "target was empty")?);
         }
         path.push(dest);
-        Layout::at(ws.config(), path)
+        Ok(path)
     }
 
     pub fn at(config: &Config, root: Filesystem) -> CargoResult<Layout> {
@@ -95,8 +110,11 @@ impl Layout {
         // compile.
         let lock = root.open_rw(".baler-lock", config, "build directory")?;
         let root = root.into_path_unlocked();
+        Ok(Layout::with_lock(root, Some(lock)))
+    }
 
-        Ok(Layout {
+    fn with_lock(root: PathBuf, lock: Option<FileLock>) -> Layout {
+        Layout {
             deps: root.join("deps"),
             native: root.join("native"),
             build: root.join("build"),
@@ -105,7 +123,7 @@ impl Layout {
             examples: root.join("examples"),
             root: root,
             _lock: lock,
-        })
+        }
     }
 
     pub fn prepare(&mut self) -> io::Result<()> {
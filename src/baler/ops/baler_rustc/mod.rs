@@ -3,7 +3,7 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::io::{self, Write};
-use std::path::{self, PathBuf};
+use std::path::{self, Path, PathBuf};
 use std::sync::Arc;
 
 use serde_json;
@@ -12,20 +12,26 @@ use core::{Package, PackageId, PackageSet, Target, Resolve};
 use core::{Profile, Profiles, Workspace};
 use core::shell::ColorChoice;
 use util::{self, ProcessBuilder, machine_message};
-use util::{Config, internal, profile, join_paths, short_hash};
+use util::{Config, internal, paths, profile, join_paths, short_hash};
 use util::errors::{CargoResult, CargoResultExt};
 use util::Freshness;
 
+use self::artifact_cache::ArtifactCache;
+use self::fingerprint::{ContentHashes, DirtyReason};
 use self::job::{Job, Work};
 use self::job_queue::JobQueue;
 
 use self::output_depinfo::output_depinfo;
 
 pub use self::compilation::Compilation;
-pub use self::context::{Context, Unit};
-pub use self::custom_build::{BuildOutput, BuildMap, BuildScripts};
+pub use self::context::{Context, Unit, is_target_spec, target_short_name};
+pub use self::fingerprint::package_id_loc;
+pub use self::custom_build::{BuildOutput, BuildMap, BuildScripts, LinkType};
 pub use self::layout::is_bad_artifact_name;
+pub use self::process_executor::ProcessExecutor;
 
+mod artifact_cache;
+mod build_plan;
 mod compilation;
 mod context;
 mod custom_build;
@@ -35,21 +41,44 @@ mod job_queue;
 mod layout;
 mod links;
 mod output_depinfo;
+mod process_executor;
+mod timings;
 
+/// Which platform a unit is compiled for: the host, or the requested target
+/// at the given index in `BuildConfig::requested_targets`.
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
-pub enum Kind { Host, Target }
+pub enum Kind { Host, Target(usize) }
 
 #[derive(Default, Clone)]
 pub struct BuildConfig {
     pub host_triple: String,
     pub host: TargetConfig,
-    pub requested_target: Option<String>,
-    pub target: TargetConfig,
+    /// The triples passed with `--target`, empty when building for the host.
+    pub requested_targets: Vec<String>,
+    /// The configuration of each of the `requested_targets`.
+    pub targets: Vec<TargetConfig>,
     pub jobs: u32,
     pub release: bool,
     pub test: bool,
     pub doc_all: bool,
     pub json_messages: bool,
+    /// Whether rustc should print each diagnostic on a single line.
+    pub short_diagnostics: bool,
+    /// Whether diagnostics are printed as rustc renders them even though
+    /// `json_messages` is set.
+    pub render_diagnostics: bool,
+    pub build_plan: bool,
+    pub timings: bool,
+    pub keep_going: bool,
+    /// Where to copy the top-level artifacts to under their unhashed names.
+    pub out_dir: Option<PathBuf>,
+    pub pipelining: bool,
+    /// Whether the diagnostics of each unit are kept and shown again when the
+    /// unit is fresh.
+    pub replay_warnings: bool,
+    pub content_freshness: bool,
+    pub shared_cache: Option<PathBuf>,
+    pub shared_cache_max_size: u64,
 }
 
 #[derive(Clone, Default)]
@@ -84,6 +113,27 @@ pub trait Executor: Send + Sync + 'static {
         Ok(())
     }
 
+    /// Like `exec`, but also given the files and directories the command is
+    /// expected to read, for executors which run it somewhere else.
+    fn exec_with_inputs(&self,
+                        cmd: ProcessBuilder,
+                        id: &PackageId,
+                        _inputs: &[PathBuf]) -> CargoResult<()> {
+        self.exec(cmd, id)
+    }
+
+    /// Like `exec_json`, but also given the files and directories the command
+    /// is expected to read.
+    fn exec_json_with_inputs(&self,
+                             cmd: ProcessBuilder,
+                             id: &PackageId,
+                             _inputs: &[PathBuf],
+                             handle_stdout: &mut FnMut(&str) -> CargoResult<()>,
+                             handle_stderr: &mut FnMut(&str) -> CargoResult<()>)
+                             -> CargoResult<()> {
+        self.exec_json(cmd, id, handle_stdout, handle_stderr)
+    }
+
     /// Queried when queuing each unit of work. If it returns true, then the
     /// unit will always be rebuilt, independent of whether it needs to be.
     fn force_rebuild(&self, _unit: &Unit) -> bool {
@@ -109,21 +159,27 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
                                      profiles: &'a Profiles,
                                      exec: Arc<Executor>)
                                      -> CargoResult<Compilation<'cfg>> {
-    let units = pkg_targets.iter().flat_map(|&(pkg, ref targets)| {
-        let default_kind = if build_config.requested_target.is_some() {
-            Kind::Target
-        } else {
-            Kind::Host
-        };
-        targets.iter().map(move |&(target, profile)| {
-            Unit {
-                pkg: pkg,
-                target: target,
-                profile: profile,
-                kind: if target.for_host() {Kind::Host} else {default_kind},
+    // Everything is built once for each requested target, other than the
+    // targets which are only ever built for the host.
+    let kinds = if build_config.requested_targets.is_empty() {
+        vec![Kind::Host]
+    } else {
+        (0..build_config.requested_targets.len()).map(Kind::Target).collect()
+    };
+    let mut units = Vec::new();
+    for &(pkg, ref targets) in pkg_targets.iter() {
+        for &(target, profile) in targets.iter() {
+            let unit_kinds = if target.for_host() { &[Kind::Host][..] } else { &kinds[..] };
+            for &kind in unit_kinds {
+                units.push(Unit {
+                    pkg: pkg,
+                    target: target,
+                    profile: profile,
+                    kind: kind,
+                });
             }
-        })
-    }).collect::<Vec<_>>();
+        }
+    }
 
     let mut cx = Context::new(ws, resolve, packages, config,
                                    build_config, profiles)?;
@@ -131,8 +187,18 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
     let mut queue = JobQueue::new(&cx);
 
     cx.prepare()?;
+    if cx.build_config.content_freshness {
+        cx.content_hashes = Arc::new(ContentHashes::load(&cx.content_hashes_path()));
+    }
+    if !cx.build_config.build_plan {
+        if let Some(ref root) = cx.build_config.shared_cache {
+            let max_size = cx.build_config.shared_cache_max_size;
+            cx.artifact_cache = Some(Arc::new(ArtifactCache::open(root, max_size)?));
+        }
+    }
     cx.probe_target_info(&units)?;
     cx.build_used_in_plugin_map(&units)?;
+    cx.build_rmeta_required(&units)?;
     custom_build::build_map(&mut cx, &units)?;
 
     for unit in units.iter() {
@@ -145,8 +211,27 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
     }
 
     // Now that we've figured out everything that we're going to do, do it!
-    queue.execute(&mut cx)?;
+    let result = queue.execute(&mut cx);
+    if cx.build_config.json_messages && !cx.build_config.build_plan {
+        machine_message::emit(machine_message::BuildFinished {
+            success: result.is_ok(),
+        });
+    }
+    result?;
 
+    // Nothing was actually built, so there's nothing to collect.
+    if cx.build_config.build_plan {
+        return Ok(cx.compilation)
+    }
+
+    if cx.build_config.content_freshness {
+        cx.content_hashes.save(&cx.content_hashes_path())?;
+    }
+    if let Some(ref cache) = cx.artifact_cache {
+        cache.finish()?;
+    }
+
+    let several_targets = cx.build_config.requested_targets.len() > 1;
     for unit in units.iter() {
         for &(ref dst, ref link_dst, _) in cx.target_filenames(unit)?.iter() {
             let bindst = match *link_dst {
@@ -154,6 +239,19 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
                 None => dst,
             };
 
+            // Only the artifacts which were hard linked out of `deps` under
+            // their unhashed names are worth handing out.
+            if let (Some(out_dir), Some(link_dst)) = (cx.build_config.out_dir.as_ref(),
+                                                      link_dst.as_ref()) {
+                if dst.exists() && !unit.profile.test && !unit.profile.doc {
+                    let mut out_dir = out_dir.clone();
+                    if several_targets {
+                        out_dir.push(cx.triple(unit.kind));
+                    }
+                    copy_to_out_dir(dst, link_dst, &out_dir)?;
+                }
+            }
+
             if unit.profile.test {
                 cx.compilation.tests.push((unit.pkg.clone(),
                                            unit.target.kind().clone(),
@@ -211,7 +309,10 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
             cx.compilation.native_dirs.insert(dir.clone());
         }
     }
-    cx.compilation.target = cx.target_triple().to_string();
+    // Running what was built is only supported for a single target, which
+    // `baler test`, `baler bench` and `baler run` check up front.
+    cx.compilation.target = cx.triple(kinds[0]).to_string();
+    cx.compilation.target_cfg = cx.cfg(kinds[0]).to_vec();
     Ok(cx.compilation)
 }
 
@@ -227,7 +328,9 @@ fn compile<'a, 'cfg: 'a>(cx: &mut Context<'a, 'cfg>,
     // we've got everything constructed.
     let p = profile::start(format!("preparing: {}/{}", unit.pkg,
                                    unit.target.name()));
-    fingerprint::prepare_init(cx, unit)?;
+    if !cx.build_config.build_plan {
+        fingerprint::prepare_init(cx, unit)?;
+    }
     cx.links.validate(unit)?;
 
     let (dirty, fresh, freshness) = if unit.profile.run_custom_build {
@@ -235,6 +338,15 @@ fn compile<'a, 'cfg: 'a>(cx: &mut Context<'a, 'cfg>,
     } else if unit.profile.doc && unit.profile.test {
         // we run these targets later, so this is just a noop for now
         (Work::new(|_| Ok(())), Work::new(|_| Ok(())), Freshness::Fresh)
+    } else if cx.build_config.build_plan {
+        // Every unit goes into the build plan, and as nothing is built no
+        // fingerprints or links should be updated either.
+        let work = if unit.profile.doc {
+            rustdoc(cx, unit)?
+        } else {
+            rustc(cx, unit, exec.clone())?
+        };
+        (work, Work::noop(), Freshness::Dirty)
     } else {
         let (mut freshness, dirty, fresh) = fingerprint::prepare_target(cx, unit)?;
         let work = if unit.profile.doc {
@@ -242,12 +354,20 @@ fn compile<'a, 'cfg: 'a>(cx: &mut Context<'a, 'cfg>,
         } else {
             rustc(cx, unit, exec.clone())?
         };
+        let work = if freshness == Freshness::Dirty && !exec.force_rebuild(unit) {
+            artifact_cache::prepare(cx, unit, work)?
+        } else {
+            work
+        };
         // Need to link targets on both the dirty and fresh
         let dirty = work.then(link_targets(cx, unit, false)?).then(dirty);
-        let fresh = link_targets(cx, unit, true)?.then(fresh);
+        let fresh = link_targets(cx, unit, true)?
+            .then(replay_output(cx, unit)?)
+            .then(fresh);
 
         if exec.force_rebuild(unit) {
             freshness = Freshness::Dirty;
+            cx.dirty_reasons.insert(*unit, DirtyReason::Forced);
         }
 
         (dirty, fresh, freshness)
@@ -311,8 +431,15 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     rustc.args(&cx.incremental_args(unit)?);
     rustc.args(&cx.rustflags_args(unit)?);
     let json_messages = cx.build_config.json_messages;
+    let render_diagnostics = cx.build_config.render_diagnostics;
+    let build_plan = cx.build_config.build_plan;
+    let pipelined = cx.rmeta_required(unit);
+    let replay = cx.build_config.replay_warnings;
+    let output_loc = fingerprint::output_loc(cx, unit);
     let package_id = unit.pkg.package_id().clone();
     let target = unit.target.clone();
+    let test = unit.profile.test;
+    let mut inputs = rustc_inputs(cx, unit)?;
 
     exec.init(cx);
     let exec = exec.clone();
@@ -327,15 +454,25 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
         // located somewhere in there.
         // Finally, if custom environment variables have been produced by
         // previous build scripts, we include them in the rustc invocation.
+        //
+        // Build scripts don't run when making a build plan, so none of this
+        // is known yet.
         if let Some(build_deps) = build_deps {
             let build_state = build_state.outputs.lock().unwrap();
-            add_native_deps(&mut rustc, &build_state, &build_deps,
-                                 pass_l_flag, &current_id)?;
-            add_plugin_deps(&mut rustc, &build_state, &build_deps,
-                                 &root_output)?;
+            if !build_plan {
+                add_native_deps(&mut rustc, &build_state, &build_deps,
+                                     pass_l_flag, &current_id, &target, test)?;
+                add_plugin_deps(&mut rustc, &build_state, &build_deps,
+                                     &root_output)?;
+            }
             add_custom_env(&mut rustc, &build_state, &current_id, kind)?;
         }
 
+        if build_plan {
+            state.build_plan(rustc, filenames.clone());
+            return Ok(())
+        }
+
         // FIXME(rust-lang/rust#18913): we probably shouldn't have to do
         //                              this manually
         for &(ref filename, ref _link_dst, _linkable) in filenames.iter() {
@@ -346,6 +483,11 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
             if dsts[0].extension() == Some(&OsStr::new("rmeta")) {
                 dsts.push(root.join(filename).with_extension("rlib"));
             }
+            // Likewise dependents of a pipelined rlib are handed its rmeta, so
+            // an old one must not be left behind.
+            if pipelined && dsts[0].extension() == Some(&OsStr::new("rlib")) {
+                dsts.push(root.join(filename).with_extension("rmeta"));
+            }
             for dst in &dsts {
                 if fs::metadata(dst).is_ok() {
                     fs::remove_file(dst).chain_err(|| {
@@ -355,9 +497,22 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
             }
         }
 
+        if let Some(out_dir) = rustc.get_env("OUT_DIR") {
+            inputs.push(PathBuf::from(out_dir));
+        }
+
+        // Whatever was kept from an earlier build of this unit is out of date
+        // now, even if replaying isn't enabled for this one.
+        if output_loc.exists() {
+            fs::remove_file(&output_loc).chain_err(|| {
+                format!("failed to remove: {}", output_loc.display())
+            })?;
+        }
+
         state.running(&rustc);
-        if json_messages {
-            exec.exec_json(rustc, &package_id,
+        if json_messages || pipelined || replay {
+            let mut diagnostics = Vec::new();
+            exec.exec_json_with_inputs(rustc, &package_id, &inputs,
                 &mut |line| if !line.is_empty() {
                     Err(internal(&format!("compiler stdout is not empty: `{}`", line)))
                 } else {
@@ -367,9 +522,31 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
                     // stderr from rustc can have a mix of JSON and non-JSON output
                     if line.starts_with('{') {
                         // Handle JSON lines
-                        let compiler_message = serde_json::from_str(line).map_err(|_| {
-                            internal(&format!("compiler produced invalid json: `{}`", line))
-                        })?;
+                        let compiler_message: serde_json::Value =
+                            serde_json::from_str(line).map_err(|_| {
+                                internal(&format!("compiler produced invalid json: `{}`",
+                                                  line))
+                            })?;
+
+                        // Dependents waiting on our metadata can start now.
+                        if let Some(artifact) = compiler_message.get("artifact") {
+                            if artifact.as_str().map_or(false, |a| a.ends_with(".rmeta")) {
+                                state.rmeta_produced();
+                            }
+                            return Ok(())
+                        }
+                        if replay {
+                            diagnostics.push(without_colors(compiler_message.clone()));
+                        }
+                        // The user didn't ask for JSON diagnostics, so print
+                        // the diagnostic as rustc would have.
+                        if !json_messages || render_diagnostics {
+                            if let Some(rendered) = compiler_message.get("rendered")
+                                                                    .and_then(|r| r.as_str()) {
+                                state.diagnostic(rendered, is_error(&compiler_message));
+                            }
+                            return Ok(())
+                        }
 
                         machine_message::emit(machine_message::FromCompiler {
                             package_id: &package_id,
@@ -385,8 +562,15 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
             ).chain_err(|| {
                 format!("Could not compile `{}`.", name)
             })?;
+
+            if !diagnostics.is_empty() {
+                diagnostics.push(String::new());
+                paths::write(&output_loc, diagnostics.join("\n").as_bytes())?;
+            }
         } else {
-            exec.exec(rustc, &package_id).map_err(|e| e.into_internal()).chain_err(|| {
+            exec.exec_with_inputs(rustc, &package_id, &inputs).map_err(|e| {
+                e.into_internal()
+            }).chain_err(|| {
                 format!("Could not compile `{}`.", name)
             })?;
         }
@@ -421,7 +605,9 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
                        build_state: &BuildMap,
                        build_scripts: &BuildScripts,
                        pass_l_flag: bool,
-                       current_id: &PackageId) -> CargoResult<()> {
+                       current_id: &PackageId,
+                       target: &Target,
+                       test: bool) -> CargoResult<()> {
         for key in build_scripts.to_link.iter() {
             let output = build_state.get(key).ok_or_else(|| {
                 internal(format!("couldn't find build state for {}/{:?}",
@@ -434,6 +620,15 @@ fn rustc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
                 for cfg in &output.cfgs {
                     rustc.arg("--cfg").arg(cfg);
                 }
+                for check_cfg in &output.check_cfgs {
+                    rustc.arg("--check-cfg").arg(check_cfg);
+                }
+                // Linker arguments are only for the package's own targets.
+                for &(link_type, ref arg) in &output.linker_args {
+                    if link_type.applies_to(target, test) {
+                        rustc.arg("-C").arg(format!("link-arg={}", arg));
+                    }
+                }
                 if pass_l_flag {
                     for name in output.library_links.iter() {
                         rustc.arg("-l").arg(name);
@@ -473,6 +668,9 @@ fn link_targets<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
         .map(|s| s.to_owned())
         .collect();
     let json_messages = cx.build_config.json_messages;
+    let target_triple = cx.triple(unit.kind).to_string();
+    let executable = (unit.target.is_bin() || unit.target.is_bin_example() ||
+                      unit.profile.test) && !unit.profile.check && !unit.profile.doc;
 
     Ok(Work::new(move |_| {
         // If we're a "root crate", e.g. the target of this compilation, then we
@@ -513,12 +711,19 @@ fn link_targets<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
         }
 
         if json_messages {
+            let executable = if executable {
+                destinations.first().cloned()
+            } else {
+                None
+            };
             machine_message::emit(machine_message::Artifact {
                 package_id: &package_id,
                 target: &target,
+                target_triple: &target_triple,
                 profile: &profile,
                 features: features,
                 filenames: destinations,
+                executable: executable,
                 fresh: fresh,
             });
         }
@@ -526,6 +731,154 @@ fn link_targets<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     }))
 }
 
+/// Shows the diagnostics kept from the last time `unit` was compiled, as
+/// `compiler-message`s when JSON messages were asked for.
+fn replay_output<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
+                           unit: &Unit<'a>) -> CargoResult<Work> {
+    if !cx.build_config.replay_warnings || unit.profile.doc {
+        return Ok(Work::noop())
+    }
+    let output_loc = fingerprint::output_loc(cx, unit);
+    let json_messages = cx.build_config.json_messages &&
+                        !cx.build_config.render_diagnostics;
+    let package_id = unit.pkg.package_id().clone();
+    let target = unit.target.clone();
+
+    Ok(Work::new(move |state| {
+        let output = match paths::read(&output_loc) {
+            Ok(output) => output,
+            Err(..) => return Ok(()),
+        };
+        for line in output.lines() {
+            let compiler_message: serde_json::Value =
+                serde_json::from_str(line).map_err(|_| {
+                    internal(&format!("invalid json in `{}`: `{}`",
+                                      output_loc.display(), line))
+                })?;
+            if json_messages {
+                machine_message::emit(machine_message::FromCompiler {
+                    package_id: &package_id,
+                    target: &target,
+                    message: compiler_message,
+                });
+            } else if let Some(rendered) = compiler_message.get("rendered")
+                                                           .and_then(|r| r.as_str()) {
+                state.diagnostic(rendered, is_error(&compiler_message));
+            }
+        }
+        Ok(())
+    }))
+}
+
+/// Returns whether the compiler diagnostic `message` is an error.
+fn is_error(message: &serde_json::Value) -> bool {
+    message.get("level").and_then(|l| l.as_str()).map_or(false, |l| l.starts_with("error"))
+}
+
+/// Returns `message` as a line of JSON to keep for replaying, with any
+/// colors taken out of its rendering. Whether they're wanted depends on where
+/// and how it's replayed, which isn't known yet.
+fn without_colors(mut message: serde_json::Value) -> String {
+    if let Some(rendered) = message.get_mut("rendered") {
+        let plain = rendered.as_str().map(strip_ansi_escapes);
+        if let Some(plain) = plain {
+            *rendered = serde_json::Value::String(plain);
+        }
+    }
+    message.to_string()
+}
+
+/// Removes the ANSI escape sequences rustc colors diagnostics with.
+fn strip_ansi_escapes(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            ret.push(c);
+        } else if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if '@' <= c && c <= '~' {
+                    break
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Copies `src` into `out_dir` under the file name of `link_dst`.
+///
+/// The artifact is first hard linked or copied next to its destination and
+/// then renamed over it, so an existing file is replaced atomically.
+fn copy_to_out_dir(src: &Path, link_dst: &Path, out_dir: &Path) -> CargoResult<()> {
+    let name = link_dst.file_name().unwrap();
+    let dst = out_dir.join(name);
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    let tmp = out_dir.join(tmp_name);
+
+    fs::create_dir_all(out_dir).chain_err(|| {
+        format!("failed to create directory `{}`", out_dir.display())
+    })?;
+    if tmp.exists() {
+        fs::remove_file(&tmp).chain_err(|| {
+            format!("failed to remove: {}", tmp.display())
+        })?;
+    }
+    debug!("copying {} to {}", src.display(), dst.display());
+    fs::hard_link(src, &tmp)
+        .or_else(|err| {
+            debug!("hard link failed {}. falling back to fs::copy", err);
+            fs::copy(src, &tmp).map(|_| ())
+        })
+        .chain_err(|| {
+            format!("failed to link or copy `{}` to `{}`",
+                    src.display(), tmp.display())
+        })?;
+    fs::rename(&tmp, &dst).chain_err(|| {
+        format!("failed to move `{}` to `{}`", tmp.display(), dst.display())
+    })?;
+    Ok(())
+}
+
+/// Returns the files and directories rustc is expected to read when compiling
+/// `unit`, for executors which run it somewhere else.
+///
+/// These are the package's sources, files outside of it which the previous
+/// build read according to its dep-info, and the directories holding the
+/// artifacts of dependencies. Without a previous build, the target's source
+/// and the roots of path dependencies stand in for what the dep-info lists.
+fn rustc_inputs<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
+                          -> CargoResult<Vec<PathBuf>> {
+    let root = unit.pkg.root().to_path_buf();
+    let mut inputs = vec![root.clone()];
+    let dep_info = fingerprint::dep_info_loc(cx, unit);
+    match fingerprint::parse_dep_info(&dep_info)? {
+        Some(paths) => {
+            inputs.extend(paths.into_iter().filter(|p| !p.starts_with(&root)));
+        }
+        None => {
+            let src = util::normalize_path(unit.target.src_path());
+            if !src.starts_with(&root) {
+                inputs.push(src);
+            }
+            for dep in cx.dep_targets(unit)? {
+                let dep_root = dep.pkg.root();
+                if dep.pkg.package_id().source_id().is_path() &&
+                   !inputs.iter().any(|i| i == dep_root) {
+                    inputs.push(dep_root.to_path_buf());
+                }
+            }
+        }
+    }
+    inputs.push(cx.deps_dir(unit).to_path_buf());
+    if cx.host_deps() != cx.deps_dir(unit) {
+        inputs.push(cx.host_deps().to_path_buf());
+    }
+    Ok(inputs)
+}
+
 fn load_build_deps(cx: &Context, unit: &Unit) -> Option<Arc<BuildScripts>> {
     cx.build_scripts.get(unit).cloned()
 }
@@ -607,9 +960,7 @@ fn rustdoc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
            .arg(&root_path(cx, unit));
 
     if unit.kind != Kind::Host {
-        if let Some(target) = cx.requested_target() {
-            rustdoc.arg("--target").arg(target);
-        }
+        rustdoc.arg("--target").arg(cx.rustc_target(unit.kind));
     }
 
     let doc_dir = cx.out_dir(unit);
@@ -617,7 +968,9 @@ fn rustdoc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     // Create the documentation directory ahead of time as rustdoc currently has
     // a bug where concurrent invocations will race to create this directory if
     // it doesn't already exist.
-    fs::create_dir_all(&doc_dir)?;
+    if !cx.build_config.build_plan {
+        fs::create_dir_all(&doc_dir)?;
+    }
 
     rustdoc.arg("-o").arg(doc_dir);
 
@@ -636,6 +989,7 @@ fn rustdoc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     let name = unit.pkg.name().to_string();
     let build_state = cx.build_state.clone();
     let key = (unit.pkg.package_id().clone(), unit.kind);
+    let build_plan = cx.build_config.build_plan;
 
     Ok(Work::new(move |state| {
         if let Some(output) = build_state.outputs.lock().unwrap().get(&key) {
@@ -646,6 +1000,10 @@ fn rustdoc<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
                 rustdoc.env(name, value);
             }
         }
+        if build_plan {
+            state.build_plan(rustdoc, Arc::new(Vec::new()));
+            return Ok(())
+        }
         state.running(&rustdoc);
         rustdoc.exec().chain_err(|| format!("Could not document `{}`.", name))
     }))
@@ -672,10 +1030,10 @@ fn root_path(cx: &Context, unit: &Unit) -> PathBuf {
     }
 }
 
-fn build_base_args(cx: &mut Context,
-                   cmd: &mut ProcessBuilder,
-                   unit: &Unit,
-                   crate_types: &[&str]) {
+fn build_base_args<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
+                             cmd: &mut ProcessBuilder,
+                             unit: &Unit<'a>,
+                             crate_types: &[&str]) {
     let Profile {
         ref opt_level, lto, codegen_units, ref rustc_args, debuginfo,
         debug_assertions, overflow_checks, rpath, test, doc: _doc,
@@ -696,8 +1054,34 @@ fn build_base_args(cx: &mut Context,
         ColorChoice::CargoAuto => {}
     }
 
-    if cx.build_config.json_messages {
+    // Pipelined units need rustc's JSON output to learn when the metadata is
+    // ready, so their diagnostics are rendered by rustc but printed by us.
+    // The same goes for units whose diagnostics are kept to be replayed, and
+    // for JSON messages with diagnostics rendered by rustc.
+    let pipelined = cx.rmeta_required(unit);
+    let replay = cx.build_config.replay_warnings;
+    let short = cx.build_config.short_diagnostics;
+    let error_json = cx.build_config.json_messages || pipelined || replay;
+    let rendered = !cx.build_config.json_messages || cx.build_config.render_diagnostics;
+    if error_json {
         cmd.arg("--error-format").arg("json");
+    } else if short {
+        cmd.arg("--error-format").arg("short");
+    }
+    let mut json = Vec::new();
+    if pipelined {
+        json.push("artifacts");
+    }
+    if error_json && rendered {
+        if short {
+            json.push("diagnostic-short");
+        }
+        if cx.config.shell().supports_color() {
+            json.push("diagnostic-rendered-ansi");
+        }
+    }
+    if !json.is_empty() && cx.supports_json(unit.kind) {
+        cmd.arg(format!("--json={}", json.join(",")));
     }
 
     if !test {
@@ -708,6 +1092,8 @@ fn build_base_args(cx: &mut Context,
 
     if check {
         cmd.arg("--emit=dep-info,metadata");
+    } else if pipelined {
+        cmd.arg("--emit=dep-info,metadata,link");
     } else {
         cmd.arg("--emit=dep-info,link");
     }
@@ -821,8 +1207,8 @@ fn build_base_args(cx: &mut Context,
         }
     }
 
-    if unit.kind == Kind::Target {
-        opt(cmd, "--target", "", cx.requested_target().map(|s| s.as_ref()));
+    if unit.kind != Kind::Host {
+        cmd.arg("--target").arg(cx.rustc_target(unit.kind));
     }
 
     opt(cmd, "-C", "ar=", cx.ar(unit.kind).map(|s| s.as_ref()));
@@ -841,7 +1227,7 @@ fn build_deps_args<'a, 'cfg>(cmd: &mut ProcessBuilder,
 
     // Be sure that the host path is also listed. This'll ensure that proc-macro
     // dependencies are correctly found (for reexported macros).
-    if let Kind::Target = unit.kind {
+    if let Kind::Target(..) = unit.kind {
         cmd.arg("-L").arg(&{
             let mut deps = OsString::from("dependency=");
             deps.push(cx.host_deps());
@@ -849,12 +1235,18 @@ fn build_deps_args<'a, 'cfg>(cmd: &mut ProcessBuilder,
         });
     }
 
-    for unit in cx.dep_targets(unit)?.iter() {
-        if unit.profile.run_custom_build {
-            cmd.env("OUT_DIR", &cx.build_script_out_dir(unit));
+    for dep in cx.dep_targets(unit)?.iter() {
+        if dep.profile.run_custom_build {
+            cmd.env("OUT_DIR", &cx.build_script_out_dir(dep));
+        }
+        if dep.target.linkable() && !dep.profile.doc {
+            link_to(cmd, cx, unit, dep)?;
         }
-        if unit.target.linkable() && !unit.profile.doc {
-            link_to(cmd, cx, unit)?;
+        // Integration tests and benchmarks are told where the package's
+        // binaries, which are built before them, end up.
+        if dep.target.is_bin() && (unit.target.is_test() || unit.target.is_bench()) {
+            let exe = cx.bin_exe(dep)?;
+            cmd.env(&format!("BALER_BIN_EXE_{}", dep.target.name()), exe);
         }
     }
 
@@ -862,11 +1254,20 @@ fn build_deps_args<'a, 'cfg>(cmd: &mut ProcessBuilder,
 
     fn link_to<'a, 'cfg>(cmd: &mut ProcessBuilder,
                          cx: &mut Context<'a, 'cfg>,
+                         parent: &Unit<'a>,
                          unit: &Unit<'a>) -> CargoResult<()> {
+        let only_rmeta = cx.only_requires_rmeta(parent, unit);
         for &(ref dst, _, ref linkable) in cx.target_filenames(unit)?.iter() {
             if !*linkable {
                 continue
             }
+            // The parent may be started before the rlib exists, as soon as
+            // rustc has written the metadata next to where it will be.
+            let dst = if only_rmeta {
+                dst.with_extension("rmeta")
+            } else {
+                dst.clone()
+            };
             let mut v = OsString::new();
             v.push(&unit.target.crate_name());
             v.push("=");
@@ -893,8 +1294,8 @@ impl Kind {
         // that needs to be on the host we lift ourselves up to `Host`
         match *self {
             Kind::Host => Kind::Host,
-            Kind::Target if target.for_host() => Kind::Host,
-            Kind::Target => Kind::Target,
+            Kind::Target(..) if target.for_host() => Kind::Host,
+            Kind::Target(i) => Kind::Target(i),
         }
     }
 }
@@ -0,0 +1,224 @@
+//! Running the compiler through an external executor process.
+//!
+//! The `build.executor` configuration key can name an executable (optionally
+//! followed by arguments) which runs each compiler invocation on baler's
+//! behalf, for example on a remote build farm.
+//!
+//! For each invocation baler runs the executor and writes a single JSON
+//! request to its stdin, closing it afterwards:
+//!
+//! ```json
+//! {
+//!     "v": 1,
+//!     "program": "rustc",
+//!     "args": ["--crate-name", "foo", "src/lib.rs", "--out-dir", "/path/to/target/debug/deps"],
+//!     "env": {"CARGO_PKG_NAME": "foo", "RUST_LOG": null},
+//!     "cwd": "/path/to/foo",
+//!     "inputs": ["/path/to/foo", "/path/to/target/debug/deps"]
+//! }
+//! ```
+//!
+//! Environment variables with a `null` value are to be removed rather than
+//! set. The `inputs` are the files and directories (including everything
+//! beneath them) the compiler is expected to read.
+//!
+//! Once the command has run the executor responds with its exit status,
+//! output, and the files it produced:
+//!
+//! ```json
+//! {"status": 0, "stdout": "", "stderr": "", "outputs": ["/tmp/job/libfoo.rlib"]}
+//! ```
+//!
+//! The `outputs` are copied into the `--out-dir` of the command, so they need
+//! to be readable by baler but can be anywhere. The executor signals that it
+//! couldn't run the command at all either by exiting unsuccessfully or by
+//! responding with `{"error": "..."}`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use serde_json;
+
+use core::PackageId;
+use util::{Config, ProcessBuilder, ProcessError, process};
+use util::config::Definition;
+use util::errors::{CargoErrorKind, CargoResult, CargoResultExt};
+
+use super::Executor;
+
+/// The version of the protocol spoken to executor processes.
+const PROTOCOL_VERSION: u32 = 1;
+
+pub struct ProcessExecutor {
+    path: PathBuf,
+    args: Vec<String>,
+    definition: Definition,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    v: u32,
+    program: String,
+    args: Vec<String>,
+    env: BTreeMap<&'a str, Option<String>>,
+    cwd: Option<&'a Path>,
+    inputs: &'a [PathBuf],
+}
+
+#[derive(Deserialize)]
+struct Response {
+    status: Option<i32>,
+    #[serde(default)]
+    stdout: String,
+    #[serde(default)]
+    stderr: String,
+    #[serde(default)]
+    outputs: Vec<PathBuf>,
+    error: Option<String>,
+}
+
+impl ProcessExecutor {
+    /// Returns the executor configured by `build.executor`, if any.
+    pub fn from_config(config: &Config) -> CargoResult<Option<ProcessExecutor>> {
+        let val = match config.get_path_and_args("build.executor")? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let (path, args) = val.val;
+        Ok(Some(ProcessExecutor {
+            path: path,
+            args: args,
+            definition: val.definition,
+        }))
+    }
+
+    /// Has the executor run `cmd`, copying the files it produces to where
+    /// the command would have written them.
+    fn run(&self, cmd: &ProcessBuilder, inputs: &[PathBuf]) -> CargoResult<Response> {
+        let request = serde_json::to_vec(&Request {
+            v: PROTOCOL_VERSION,
+            program: cmd.get_program().to_string_lossy().into_owned(),
+            args: cmd.get_args().iter().map(|a| {
+                a.to_string_lossy().into_owned()
+            }).collect(),
+            env: cmd.get_envs().iter().map(|(key, value)| {
+                (&key[..], value.as_ref().map(|v| v.to_string_lossy().into_owned()))
+            }).collect(),
+            cwd: cmd.get_cwd(),
+            inputs: inputs,
+        })?;
+        let mut executor = process(&self.path);
+        executor.args(&self.args);
+
+        let response = (|| -> CargoResult<Response> {
+            let output = executor.exec_with_input(&request)?;
+            let stdout = str::from_utf8(&output.stdout).map_err(|_| {
+                "executor did not respond with valid utf-8"
+            })?;
+            let response: Response = serde_json::from_str(stdout).chain_err(|| {
+                format!("executor responded with invalid JSON: {}", stdout.trim())
+            })?;
+            if let Some(ref error) = response.error {
+                bail!("{}", error)
+            }
+            if response.status.is_none() {
+                bail!("executor responded without an exit status")
+            }
+            Ok(response)
+        })().chain_err(|| {
+            format!("failed to run `{}` using the executor `{}` (configured by \
+                     `build.executor` in {})",
+                    cmd, self.path.display(), self.definition)
+        })?;
+
+        if !response.outputs.is_empty() {
+            let out_dir = out_dir(cmd).ok_or_else(|| {
+                format!("executor produced files for `{}`, which has no \
+                         `--out-dir`", cmd)
+            })?;
+            for output in response.outputs.iter() {
+                let dst = match output.file_name() {
+                    Some(name) => out_dir.join(name),
+                    None => bail!("executor produced an invalid output `{}`",
+                                  output.display()),
+                };
+                fs::copy(output, &dst).chain_err(|| {
+                    format!("failed to copy `{}` produced by the executor to `{}`",
+                            output.display(), dst.display())
+                })?;
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl Executor for ProcessExecutor {
+    fn exec(&self, cmd: ProcessBuilder, id: &PackageId) -> CargoResult<()> {
+        self.exec_with_inputs(cmd, id, &[])
+    }
+
+    fn exec_json(&self,
+                 cmd: ProcessBuilder,
+                 id: &PackageId,
+                 handle_stdout: &mut FnMut(&str) -> CargoResult<()>,
+                 handle_stderr: &mut FnMut(&str) -> CargoResult<()>)
+                 -> CargoResult<()> {
+        self.exec_json_with_inputs(cmd, id, &[], handle_stdout, handle_stderr)
+    }
+
+    fn exec_with_inputs(&self,
+                        cmd: ProcessBuilder,
+                        _id: &PackageId,
+                        inputs: &[PathBuf]) -> CargoResult<()> {
+        let response = self.run(&cmd, inputs)?;
+        io::stdout().write_all(response.stdout.as_bytes())?;
+        io::stderr().write_all(response.stderr.as_bytes())?;
+        check_status(&cmd, &response)
+    }
+
+    fn exec_json_with_inputs(&self,
+                             cmd: ProcessBuilder,
+                             _id: &PackageId,
+                             inputs: &[PathBuf],
+                             handle_stdout: &mut FnMut(&str) -> CargoResult<()>,
+                             handle_stderr: &mut FnMut(&str) -> CargoResult<()>)
+                             -> CargoResult<()> {
+        let response = self.run(&cmd, inputs)?;
+        for line in response.stdout.lines() {
+            handle_stdout(line)?;
+        }
+        for line in response.stderr.lines() {
+            handle_stderr(line)?;
+        }
+        check_status(&cmd, &response)
+    }
+}
+
+/// Returns the `--out-dir` passed to `cmd`, if any.
+fn out_dir(cmd: &ProcessBuilder) -> Option<PathBuf> {
+    let mut args = cmd.get_args().iter();
+    while let Some(arg) = args.next() {
+        if arg == "--out-dir" {
+            return args.next().map(PathBuf::from)
+        }
+    }
+    None
+}
+
+fn check_status(cmd: &ProcessBuilder, response: &Response) -> CargoResult<()> {
+    match response.status {
+        Some(0) => Ok(()),
+        status => {
+            let desc = format!("process didn't exit successfully: `{}` (exit code: {})",
+                               cmd, status.unwrap_or(-1));
+            Err(CargoErrorKind::ProcessErrorKind(ProcessError {
+                desc: desc,
+                exit: None,
+                output: None,
+            }).into())
+        }
+    }
+}
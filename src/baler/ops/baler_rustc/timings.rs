@@ -0,0 +1,445 @@
+//! The timing report written by `--timings`.
+//!
+//! While the job queue runs, this records when each unit starts and finishes,
+//! how many units are running or waiting at any point, and how long units
+//! which were ready to run spent waiting for a jobserver token. Once the build
+//! is over this is written out as a self-contained HTML page for people and a
+//! JSON file for tools in `target/baler-timings`.
+//!
+//! Separately, how long every unit took to build is always remembered in
+//! `UnitCosts`, so the next build can start the most expensive chains first.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use util::{CargoResult, CargoResultExt, machine_message};
+use util::paths;
+
+use super::{Context, Unit};
+
+/// The width in pixels of the graphs in the HTML report.
+const GRAPH_WIDTH: f64 = 1000.0;
+
+/// The height in pixels of each unit in the timeline of the HTML report.
+const ROW_HEIGHT: f64 = 16.0;
+
+pub struct Timings<'a> {
+    enabled: bool,
+    json_messages: bool,
+    start: Instant,
+    /// When the build started, in seconds since the Unix epoch. This is used
+    /// to name the report files.
+    start_stamp: u64,
+    profile: &'static str,
+    jobs: u32,
+    units: Vec<UnitTime>,
+    /// The index in `units` of every unit which has started.
+    indices: HashMap<Unit<'a>, usize>,
+    concurrency: Vec<Concurrency>,
+    /// Total seconds during which units were ready to run, but couldn't be
+    /// started for lack of a jobserver token.
+    token_wait: f64,
+}
+
+#[derive(Serialize)]
+struct UnitTime {
+    name: String,
+    version: String,
+    target: String,
+    mode: &'static str,
+    fresh: bool,
+    /// Seconds since the start of the build.
+    start: f64,
+    duration: f64,
+    /// Seconds after `start` at which the metadata of a pipelined rlib was
+    /// ready, letting the units depending on it start.
+    rmeta_time: Option<f64>,
+    /// The indices of the units which had to finish before this one started.
+    deps: Vec<usize>,
+}
+
+/// How many units were in each state at some point of the build.
+#[derive(Serialize)]
+struct Concurrency {
+    t: f64,
+    /// Units which are running.
+    active: usize,
+    /// Units which are ready to run, but waiting for a jobserver token.
+    waiting: usize,
+    /// Units which are waiting on their dependencies.
+    inactive: usize,
+}
+
+#[derive(Serialize)]
+struct Report<'t> {
+    start: u64,
+    duration: f64,
+    profile: &'static str,
+    jobs: u32,
+    token_wait: f64,
+    units: &'t [UnitTime],
+    concurrency: &'t [Concurrency],
+    /// The indices of the chain of units which determined the length of the
+    /// build, in the order they ran.
+    critical_path: Vec<usize>,
+}
+
+impl<'a> Timings<'a> {
+    pub fn new<'cfg>(cx: &Context<'a, 'cfg>) -> Timings<'a> {
+        let start_stamp = SystemTime::now().duration_since(UNIX_EPOCH)
+                                           .map(|d| d.as_secs())
+                                           .unwrap_or(0);
+        Timings {
+            enabled: cx.build_config.timings,
+            json_messages: cx.build_config.json_messages,
+            start: Instant::now(),
+            start_stamp: start_stamp,
+            profile: if cx.build_config.release { "release" } else { "dev" },
+            jobs: cx.jobs(),
+            units: Vec::new(),
+            indices: HashMap::new(),
+            concurrency: Vec::new(),
+            token_wait: 0.0,
+        }
+    }
+
+    /// Records that the first job of `unit` is starting.
+    pub fn unit_start<'cfg>(&mut self,
+                            cx: &Context<'a, 'cfg>,
+                            unit: &Unit<'a>,
+                            fresh: bool) -> CargoResult<()> {
+        if !self.enabled || self.indices.contains_key(unit) {
+            return Ok(())
+        }
+        let mut deps = cx.dep_targets(unit)?.iter().filter_map(|dep| {
+            self.indices.get(dep).cloned()
+        }).collect::<Vec<_>>();
+        deps.sort();
+        deps.dedup();
+        self.units.push(UnitTime {
+            name: unit.pkg.name().to_string(),
+            version: unit.pkg.version().to_string(),
+            target: unit.target.name().to_string(),
+            mode: mode(unit),
+            fresh: fresh,
+            start: secs(self.start.elapsed()),
+            duration: 0.0,
+            rmeta_time: None,
+            deps: deps,
+        });
+        self.indices.insert(*unit, self.units.len() - 1);
+        Ok(())
+    }
+
+    /// Records that the metadata of `unit` is ready.
+    pub fn unit_rmeta_finished(&mut self, unit: &Unit<'a>) {
+        if !self.enabled {
+            return
+        }
+        if let Some(&index) = self.indices.get(unit) {
+            let time = &mut self.units[index];
+            time.rmeta_time = Some(secs(self.start.elapsed()) - time.start);
+        }
+    }
+
+    /// Records that the last job of `unit` has finished.
+    pub fn unit_finished(&mut self, unit: &Unit<'a>) {
+        if !self.enabled {
+            return
+        }
+        let index = match self.indices.get(unit) {
+            Some(&index) => index,
+            None => return,
+        };
+        let time = &mut self.units[index];
+        time.duration = secs(self.start.elapsed()) - time.start;
+        if self.json_messages {
+            machine_message::emit(machine_message::TimingInfo {
+                package_id: unit.pkg.package_id(),
+                target: unit.target,
+                mode: time.mode,
+                duration: time.duration,
+                fresh: time.fresh,
+            });
+        }
+    }
+
+    /// Records how many units are in each state right now.
+    pub fn mark_concurrency(&mut self, active: usize, waiting: usize, inactive: usize) {
+        if !self.enabled {
+            return
+        }
+        self.concurrency.push(Concurrency {
+            t: secs(self.start.elapsed()),
+            active: active,
+            waiting: waiting,
+            inactive: inactive,
+        });
+    }
+
+    /// Records that units which were ready to run spent `duration` waiting
+    /// for a jobserver token.
+    pub fn token_wait(&mut self, duration: Duration) {
+        self.token_wait += secs(duration);
+    }
+
+    /// Writes the HTML and JSON reports.
+    pub fn finished<'cfg>(&self, cx: &Context<'a, 'cfg>) -> CargoResult<()> {
+        if !self.enabled {
+            return Ok(())
+        }
+        let duration = secs(self.start.elapsed());
+        let report = Report {
+            start: self.start_stamp,
+            duration: duration,
+            profile: self.profile,
+            jobs: self.jobs,
+            token_wait: self.token_wait,
+            units: &self.units,
+            concurrency: &self.concurrency,
+            critical_path: self.critical_path(),
+        };
+
+        let dir = cx.ws.target_dir().join("baler-timings").into_path_unlocked();
+        fs::create_dir_all(&dir).chain_err(|| {
+            format!("failed to create directory `{}`", dir.display())
+        })?;
+        let html = render_html(&report);
+        let json = serde_json::to_string(&report).unwrap();
+        let name = format!("baler-timing-{}", self.start_stamp);
+        for &(ext, ref contents) in [("html", &html), ("json", &json)].iter() {
+            // Keep every report, but also make the latest one easy to find.
+            for name in [&name[..], "baler-timing"].iter() {
+                paths::write(&dir.join(format!("{}.{}", name, ext)), contents.as_bytes())?;
+            }
+        }
+        let path = dir.join(format!("{}.html", name));
+        cx.config.shell().status("Timing", format!("report saved to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Follows the dependencies which finished last back from the unit which
+    /// finished last, as these are the units which held up the build.
+    fn critical_path(&self) -> Vec<usize> {
+        let end = |i: &usize| self.units[*i].start + self.units[*i].duration;
+        let mut path = Vec::new();
+        let mut next = (0..self.units.len()).max_by(|a, b| {
+            end(a).partial_cmp(&end(b)).unwrap()
+        });
+        while let Some(i) = next {
+            path.push(i);
+            next = self.units[i].deps.iter().cloned().max_by(|a, b| {
+                end(a).partial_cmp(&end(b)).unwrap()
+            });
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// How long units took to build, in milliseconds, keyed by a description of
+/// the unit.
+///
+/// This is persisted in the fingerprint directory across builds. Entries for
+/// units which weren't built this time are kept, so switching between
+/// profiles or features doesn't lose them.
+#[derive(Default)]
+pub struct UnitCosts {
+    costs: HashMap<String, u64>,
+}
+
+impl UnitCosts {
+    /// Loads the costs at `path`. Missing or unreadable costs are treated as
+    /// unknown, as they're only used to order the build.
+    pub fn load(path: &Path) -> UnitCosts {
+        let costs = paths::read(path).ok().and_then(|s| {
+            serde_json::from_str::<Vec<(String, u64)>>(&s).ok()
+        }).unwrap_or_default();
+        UnitCosts {
+            costs: costs.into_iter().collect(),
+        }
+    }
+
+    pub fn get(&self, unit: &str) -> Option<u64> {
+        self.costs.get(unit).cloned()
+    }
+
+    pub fn record(&mut self, unit: String, duration: Duration) {
+        let millis = duration.as_secs() * 1000 +
+                     (duration.subsec_nanos() / 1_000_000) as u64;
+        self.costs.insert(unit, millis);
+    }
+
+    pub fn save(&self, path: &Path) -> CargoResult<()> {
+        let mut entries = self.costs.iter().collect::<Vec<_>>();
+        entries.sort();
+        paths::write(path, &serde_json::to_vec(&entries).unwrap())
+    }
+}
+
+fn mode(unit: &Unit) -> &'static str {
+    if unit.profile.run_custom_build {
+        "run-custom-build"
+    } else if unit.target.is_custom_build() {
+        "build-script"
+    } else if unit.profile.doc {
+        "doc"
+    } else if unit.profile.check {
+        "check"
+    } else if unit.profile.test {
+        "test"
+    } else {
+        "build"
+    }
+}
+
+fn secs(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+fn render_html(report: &Report) -> String {
+    let mut html = String::new();
+    let total = if report.duration > 0.0 { report.duration } else { 1.0 };
+    let scale = GRAPH_WIDTH / total;
+    let fresh = report.units.iter().filter(|u| u.fresh).count();
+    let max_concurrency = report.concurrency.iter().map(|c| c.active).max().unwrap_or(0);
+    let critical = report.critical_path.iter().map(|&i| {
+        report.units[i].duration
+    }).fold(0.0, |a, b| a + b);
+
+    html.push_str(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Baler Build Timings</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+td, th { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+td.num { text-align: right; }
+svg text { font-size: 11px; }
+.unit { fill: #95cce8; }
+.unit.fresh { fill: #ddd; }
+.unit.critical { fill: #f0a050; }
+.rmeta { fill: #000; fill-opacity: 0.15; }
+.active { stroke: #3070b0; fill: none; }
+.waiting { stroke: #c03030; fill: none; }
+.inactive { stroke: #999; fill: none; }
+</style>
+</head>
+<body>
+<h1>Baler Build Timings</h1>
+"#);
+
+    html.push_str("<table>\n");
+    let summary = [
+        ("Profile", report.profile.to_string()),
+        ("Total units", report.units.len().to_string()),
+        ("Fresh units", fresh.to_string()),
+        ("Dirty units", (report.units.len() - fresh).to_string()),
+        ("Total time", format!("{:.1}s", report.duration)),
+        ("Critical path", format!("{:.1}s", critical)),
+        ("Max concurrency", format!("{} (jobs={})", max_concurrency, report.jobs)),
+        ("Waiting for tokens", format!("{:.1}s", report.token_wait)),
+    ];
+    for &(name, ref value) in summary.iter() {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", name, value);
+    }
+    html.push_str("</table>\n");
+
+    // The timeline, one row per unit in the order they started.
+    html.push_str("<h2>Timeline</h2>\n");
+    let height = ROW_HEIGHT * report.units.len() as f64;
+    let _ = writeln!(html, "<svg width=\"{}\" height=\"{}\">",
+                     GRAPH_WIDTH + 300.0, height);
+    for (i, unit) in report.units.iter().enumerate() {
+        let class = if report.critical_path.contains(&i) {
+            "unit critical"
+        } else if unit.fresh {
+            "unit fresh"
+        } else {
+            "unit"
+        };
+        let x = unit.start * scale;
+        let y = i as f64 * ROW_HEIGHT;
+        let _ = writeln!(html,
+                         "<rect class=\"{}\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                          height=\"{:.1}\"><title>{} {:.2}s</title></rect>",
+                         class, x, y, (unit.duration * scale).max(1.0),
+                         ROW_HEIGHT - 2.0, escape(&unit_name(unit)), unit.duration);
+        // Mark where the units depending on a pipelined rlib could start.
+        if let Some(rmeta_time) = unit.rmeta_time {
+            let _ = writeln!(html,
+                             "<rect class=\"rmeta\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                              height=\"{:.1}\"/>",
+                             x, y, rmeta_time * scale, ROW_HEIGHT - 2.0);
+        }
+        let _ = writeln!(html, "<text x=\"{:.1}\" y=\"{:.1}\">{} {:.1}s</text>",
+                         x + (unit.duration * scale).max(1.0) + 4.0,
+                         y + ROW_HEIGHT - 4.0, escape(&unit_name(unit)), unit.duration);
+    }
+    html.push_str("</svg>\n");
+
+    // How many units were in each state over time.
+    html.push_str("<h2>Concurrency</h2>\n");
+    let max = report.concurrency.iter().map(|c| {
+        c.active.max(c.waiting).max(c.inactive)
+    }).max().unwrap_or(0).max(1) as f64;
+    let graph_height = 200.0;
+    let _ = writeln!(html, "<svg width=\"{}\" height=\"{}\">", GRAPH_WIDTH, graph_height);
+    for class in ["active", "waiting", "inactive"].iter() {
+        let mut points = String::new();
+        for c in report.concurrency.iter() {
+            let count = match *class {
+                "active" => c.active,
+                "waiting" => c.waiting,
+                _ => c.inactive,
+            };
+            let y = graph_height - count as f64 / max * (graph_height - 10.0);
+            let _ = write!(points, "{:.1},{:.1} ", c.t * scale, y);
+        }
+        let _ = writeln!(html, "<polyline class=\"{}\" points=\"{}\"/>",
+                         class, points.trim_right());
+    }
+    html.push_str("</svg>\n");
+    html.push_str("<p>Blue: running units, red: units waiting for a jobserver \
+                   token, grey: units waiting on their dependencies.</p>\n");
+
+    // Every unit, slowest first.
+    html.push_str("<h2>Units</h2>\n<table>\n");
+    html.push_str("<tr><th>Unit</th><th>Mode</th><th>Start</th><th>Duration</th>\
+                   <th>Fresh</th><th>Critical</th></tr>\n");
+    let mut order = (0..report.units.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        report.units[b].duration.partial_cmp(&report.units[a].duration).unwrap()
+    });
+    for i in order {
+        let unit = &report.units[i];
+        let _ = writeln!(html,
+                         "<tr><td>{}</td><td>{}</td><td class=\"num\">{:.2}s</td>\
+                          <td class=\"num\">{:.2}s</td><td>{}</td><td>{}</td></tr>",
+                         escape(&unit_name(unit)), unit.mode, unit.start,
+                         unit.duration,
+                         if unit.fresh { "yes" } else { "" },
+                         if report.critical_path.contains(&i) { "yes" } else { "" });
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn unit_name(unit: &UnitTime) -> String {
+    if unit.name == unit.target {
+        format!("{} v{} ({})", unit.name, unit.version, unit.mode)
+    } else {
+        format!("{} v{} {} ({})", unit.name, unit.version, unit.target, unit.mode)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
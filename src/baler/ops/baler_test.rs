@@ -70,6 +70,13 @@ pub fn run_benches(ws: &Workspace,
 fn compile_tests<'a>(ws: &Workspace<'a>,
                      options: &TestOptions<'a>)
                      -> CargoResult<Compilation<'a>> {
+    // What's compiled only describes how to run the tests for one target.
+    let targets = ops::requested_targets(ws.config(), options.compile_opts.targets)?;
+    if !options.no_run && targets.len() > 1 {
+        bail!("tests can only be run for one target at a time, but {} were \
+               requested; pass `--no-run` to only build them",
+              targets.len())
+    }
     let mut compilation = ops::compile(ws, &options.compile_opts)?;
     compilation.tests.sort_by(|a, b| {
         (a.0.package_id(), &a.1, &a.2).cmp(&(b.0.package_id(), &b.1, &b.2))
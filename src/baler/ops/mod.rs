@@ -1,11 +1,14 @@
 pub use self::baler_clean::{clean, CleanOptions};
 pub use self::baler_compile::{compile, compile_with_exec, compile_ws, CompileOptions};
+pub use self::baler_compile::requested_targets;
 pub use self::baler_compile::{CompileFilter, CompileMode, MessageFormat, Packages};
 pub use self::baler_read_manifest::{read_package, read_packages};
 pub use self::baler_rustc::{compile_targets, Compilation, Kind, Unit};
 pub use self::baler_rustc::{Context, is_bad_artifact_name};
-pub use self::baler_rustc::{BuildOutput, BuildConfig, TargetConfig};
-pub use self::baler_rustc::{Executor, DefaultExecutor};
+pub use self::baler_rustc::{is_target_spec, target_short_name};
+pub use self::baler_rustc::{BuildOutput, BuildConfig, TargetConfig, LinkType};
+pub use self::baler_rustc::{Executor, DefaultExecutor, ProcessExecutor};
+pub use self::baler_rustc::package_id_loc;
 pub use self::baler_run::run;
 pub use self::baler_install::{install, install_list, uninstall};
 pub use self::baler_new::{new, init, NewOptions, VersionControl};
@@ -17,9 +20,12 @@ pub use self::lockfile::{load_pkg_lockfile, write_pkg_lockfile};
 pub use self::baler_test::{run_tests, run_benches, TestOptions};
 pub use self::baler_package::{package, PackageOpts};
 pub use self::registry::{publish, registry_configuration, RegistryConfig};
-pub use self::registry::{registry_login, search, http_proxy_exists, http_handle};
+pub use self::registry::{registry_login, registry_logout, registry_source_id, search};
+pub use self::registry::{http_proxy_exists, http_handle};
+pub use self::registry::needs_custom_http_transport;
 pub use self::registry::{modify_owners, yank, OwnersOptions, PublishOpts};
 pub use self::baler_fetch::fetch;
+pub use self::baler_fix::{fix, FixOptions};
 pub use self::baler_pkgid::pkgid;
 pub use self::resolve::{resolve_ws, resolve_ws_precisely, resolve_with_previous};
 pub use self::baler_output_metadata::{output_metadata, OutputMetadataOptions, ExportInfo};
@@ -28,6 +34,7 @@ mod baler_clean;
 mod baler_compile;
 mod baler_doc;
 mod baler_fetch;
+mod baler_fix;
 mod baler_generate_lockfile;
 mod baler_install;
 mod baler_new;
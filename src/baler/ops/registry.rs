@@ -1,9 +1,10 @@
 use std::env;
 use std::fs::{self, File};
 use std::iter::repeat;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use curl::easy::{Easy, SslOpt};
+use curl::easy::{Easy, SslOpt, SslVersion};
 use git2;
 use registry::{Registry, NewCrate, NewCrateDependency};
 
@@ -16,7 +17,9 @@ use core::dependency::Kind;
 use core::manifest::ManifestMetadata;
 use ops;
 use sources::{RegistrySource};
-use util::config::{self, Config};
+use util::config::{self, Config, ConfigValue as CV};
+use util::credential;
+use util::network;
 use util::paths;
 use util::ToUrl;
 use util::errors::{CargoError, CargoResult, CargoResultExt};
@@ -190,10 +193,13 @@ pub fn registry(config: &Config,
         token: token_config,
         index: _index_config,
     } = registry_configuration(config)?;
-    let token = token.or(token_config);
-    let sid = match index {
-        Some(index) => SourceId::for_registry(&index.to_url()?),
-        None => SourceId::crates_io(config)?,
+    let sid = registry_source_id(config, index)?;
+    let token = match token {
+        Some(token) => Some(token),
+        None => match credential::credential_process(config)? {
+            Some(process) => process.get(sid.url().as_str())?,
+            None => token_config,
+        },
     };
     let api_host = {
         let mut src = RegistrySource::remote(&sid, config);
@@ -206,6 +212,16 @@ pub fn registry(config: &Config,
     Ok((Registry::new_handle(api_host, token, handle), sid))
 }
 
+/// Returns the source of the registry whose index is at `index`, or of
+/// crates.io when no index is given.
+pub fn registry_source_id(config: &Config,
+                          index: Option<String>) -> CargoResult<SourceId> {
+    match index {
+        Some(index) => Ok(SourceId::for_registry(&index.to_url()?)),
+        None => SourceId::crates_io(config),
+    }
+}
+
 /// Create a new HTTP handle with appropriate global configuration for baler.
 pub fn http_handle(config: &Config) -> CargoResult<Easy> {
     if !config.network_allowed() {
@@ -225,8 +241,39 @@ pub fn http_handle(config: &Config) -> CargoResult<Easy> {
     if let Some(proxy) = http_proxy(config)? {
         handle.proxy(&proxy)?;
     }
-    if let Some(cainfo) = config.get_path("http.cainfo")? {
-        handle.cainfo(&cainfo.val)?;
+    if let Some(cainfo) = http_file(config, "http.cainfo")? {
+        handle.cainfo(&cainfo)?;
+    }
+    let client_cert = http_file(config, "http.client-cert")?;
+    let client_key = http_file(config, "http.client-key")?;
+    match (client_cert, client_key) {
+        (Some(cert), key) => {
+            handle.ssl_cert(&cert).chain_err(|| {
+                "failed to use the certificate configured by `http.client-cert`"
+            })?;
+            if let Some(key) = key {
+                handle.ssl_key(&key).chain_err(|| {
+                    "failed to use the key configured by `http.client-key`"
+                })?;
+            }
+        }
+        (None, Some(..)) => {
+            bail!("`http.client-key` is configured without `http.client-cert`")
+        }
+        (None, None) => {}
+    }
+    match http_ssl_version(config)? {
+        Some(SslVersionConfig::Single(version)) => {
+            handle.ssl_version(version).chain_err(|| {
+                "failed to set the TLS version configured by `http.ssl-version`"
+            })?;
+        }
+        Some(SslVersionConfig::Range(min, max)) => {
+            handle.ssl_min_max_version(min, max).chain_err(|| {
+                "failed to set the TLS versions configured by `http.ssl-version`"
+            })?;
+        }
+        None => {}
     }
     if let Some(check) = config.get_bool("http.check-revoke")? {
         handle.ssl_options(SslOpt::new().no_revoke(!check.val))?;
@@ -238,6 +285,90 @@ pub fn http_handle(config: &Config) -> CargoResult<Easy> {
     Ok(handle)
 }
 
+/// Returns the file configured by `key`, checking that it exists.
+///
+/// libcurl only complains about missing certificate files once a connection
+/// is made, and without mentioning where the path came from.
+fn http_file(config: &Config, key: &str) -> CargoResult<Option<PathBuf>> {
+    let path = match config.get_path(key)? {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    if !path.val.is_file() {
+        bail!("the file `{}` configured by `{}` in {} does not exist",
+              path.val.display(), key, path.definition)
+    }
+    Ok(Some(path.val))
+}
+
+enum SslVersionConfig {
+    Single(SslVersion),
+    Range(SslVersion, SslVersion),
+}
+
+/// Parses `http.ssl-version`, which is either a single TLS version or a table
+/// with `min` and `max` keys.
+fn http_ssl_version(config: &Config) -> CargoResult<Option<SslVersionConfig>> {
+    let http = match config.get_table("http")? {
+        Some(http) => http,
+        None => return Ok(None),
+    };
+    let parse = |s: &str, path: &Path| -> CargoResult<SslVersion> {
+        Ok(match s {
+            "default" => SslVersion::Default,
+            "tlsv1" => SslVersion::Tlsv1,
+            "tlsv1.0" => SslVersion::Tlsv10,
+            "tlsv1.1" => SslVersion::Tlsv11,
+            "tlsv1.2" => SslVersion::Tlsv12,
+            "tlsv1.3" => SslVersion::Tlsv13,
+            _ => bail!("invalid TLS version `{}` for `http.ssl-version` in {}, \
+                        expected one of `default`, `tlsv1`, `tlsv1.0`, \
+                        `tlsv1.1`, `tlsv1.2` or `tlsv1.3`",
+                       s, path.display()),
+        })
+    };
+    match http.val.get("ssl-version") {
+        Some(&CV::String(ref s, ref path)) => {
+            Ok(Some(SslVersionConfig::Single(parse(s, path)?)))
+        }
+        Some(&CV::Table(ref table, ref path)) => {
+            let bound = |key: &str| -> CargoResult<SslVersion> {
+                match table.get(key) {
+                    Some(&CV::String(ref s, ref path)) => parse(s, path),
+                    Some(val) => {
+                        bail!("expected a string for `http.ssl-version.{}`, \
+                               but found {} in {}",
+                              key, val.desc(), path.display())
+                    }
+                    None => Ok(SslVersion::Default),
+                }
+            };
+            let min = bound("min")?;
+            let max = bound("max")?;
+            Ok(Some(SslVersionConfig::Range(min, max)))
+        }
+        Some(val) => {
+            bail!("expected a string or table for `http.ssl-version`, but \
+                   found {} in {}", val.desc(), val.definition_path().display())
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns whether HTTP requests made by git need to go through libcurl
+/// rather than libgit2's own transport.
+///
+/// libgit2 doesn't support proxies, nor any of the TLS options that baler
+/// applies to its own HTTP handles.
+pub fn needs_custom_http_transport(config: &Config) -> CargoResult<bool> {
+    Ok(http_proxy_exists(config)? ||
+       http_ssl_version(config)?.is_some() ||
+       config.get_path("http.cainfo")?.is_some() ||
+       config.get_path("http.client-cert")?.is_some() ||
+       config.get_path("http.client-key")?.is_some() ||
+       config.get_bool("http.check-revoke")?.is_some())
+}
+
 /// Find an explicit HTTP proxy if one is available.
 ///
 /// Favor baler's `http.proxy`, then git's `http.proxy`. Proxies specified
@@ -280,7 +411,14 @@ pub fn http_timeout(config: &Config) -> CargoResult<Option<i64>> {
     Ok(env::var("HTTP_TIMEOUT").ok().and_then(|s| s.parse().ok()))
 }
 
-pub fn registry_login(config: &Config, token: String) -> CargoResult<()> {
+pub fn registry_login(config: &Config,
+                      token: String,
+                      index: Option<String>) -> CargoResult<()> {
+    if let Some(process) = credential::credential_process(config)? {
+        let sid = registry_source_id(config, index)?;
+        return process.store(sid.url().as_str(), &token)
+    }
+
     let RegistryConfig { index: _, token: old_token } = registry_configuration(config)?;
     if let Some(old_token) = old_token {
         if old_token == token {
@@ -291,6 +429,15 @@ pub fn registry_login(config: &Config, token: String) -> CargoResult<()> {
     config::save_credentials(config, token)
 }
 
+pub fn registry_logout(config: &Config, index: Option<String>) -> CargoResult<()> {
+    if let Some(process) = credential::credential_process(config)? {
+        let sid = registry_source_id(config, index)?;
+        return process.erase(sid.url().as_str())
+    }
+
+    config::erase_credentials(config)
+}
+
 pub struct OwnersOptions {
     pub krate: Option<String>,
     pub token: Option<String>,
@@ -317,7 +464,9 @@ pub fn modify_owners(config: &Config, opts: &OwnersOptions) -> CargoResult<()> {
         let v = v.iter().map(|s| &s[..]).collect::<Vec<_>>();
         config.shell().status("Owner", format!("adding {:?} to crate {}",
                                                     v, name))?;
-        registry.add_owners(&name, &v).map_err(|e| {
+        network::with_retry(config, || {
+            registry.add_owners(&name, &v).map_err(CargoError::from)
+        }).map_err(|e| {
             CargoError::from(format!("failed to add owners to crate {}: {}", name, e))
         })?;
     }
@@ -326,13 +475,17 @@ pub fn modify_owners(config: &Config, opts: &OwnersOptions) -> CargoResult<()> {
         let v = v.iter().map(|s| &s[..]).collect::<Vec<_>>();
         config.shell().status("Owner", format!("removing {:?} from crate {}",
                                                     v, name))?;
-        registry.remove_owners(&name, &v).map_err(|e| {
+        network::with_retry(config, || {
+            registry.remove_owners(&name, &v).map_err(CargoError::from)
+        }).map_err(|e| {
             CargoError::from(format!("failed to remove owners from crate {}: {}", name, e))
         })?;
     }
 
     if opts.list {
-        let owners = registry.list_owners(&name).map_err(|e| {
+        let owners = network::with_retry(config, || {
+            registry.list_owners(&name).map_err(CargoError::from)
+        }).map_err(|e| {
             CargoError::from(format!("failed to list owners of crate {}: {}", name, e))
         })?;
         for owner in owners.iter() {
@@ -398,7 +551,9 @@ pub fn search(query: &str,
     }
 
     let (mut registry, _) = registry(config, None, index)?;
-    let (crates, total_crates) = registry.search(query, limit).map_err(|e| {
+    let (crates, total_crates) = network::with_retry(config, || {
+        registry.search(query, limit).map_err(CargoError::from)
+    }).map_err(|e| {
         CargoError::from(format!("failed to retrieve search results from the registry: {}", e))
     })?;
 
@@ -11,6 +11,7 @@ use url::Url;
 
 use core::{Source, SourceId};
 use sources::ReplacedSource;
+use sources::registry::SignaturePolicy;
 use util::{Config, ToUrl};
 use util::config::ConfigValue;
 use util::errors::{CargoError, CargoResult, CargoResultExt};
@@ -27,6 +28,8 @@ pub struct SourceConfigMap<'cfg> {
 /// [source.crates-io]
 /// registry = 'https://github.com/rust-lang/crates.io-index'
 /// replace-with = 'foo'    # optional
+/// signing-keys = ['...']  # optional
+/// require-signatures = true # optional
 /// ```
 struct SourceConfig {
     // id this source corresponds to, inferred from the various defined keys in
@@ -37,6 +40,13 @@ struct SourceConfig {
     // is a tuple of (name, path) where path is where this configuration key was
     // defined (the literal `.baler/config` file).
     replace_with: Option<(String, PathBuf)>,
+
+    // Hex-encoded ed25519 public keys whose signatures are trusted for
+    // packages from this source.
+    signing_keys: Vec<String>,
+
+    // Whether packages without a valid signature are rejected.
+    require_signatures: bool,
 }
 
 impl<'cfg> SourceConfigMap<'cfg> {
@@ -59,6 +69,8 @@ impl<'cfg> SourceConfigMap<'cfg> {
         base.add("crates-io", SourceConfig {
             id: SourceId::crates_io(config)?,
             replace_with: None,
+            signing_keys: Vec::new(),
+            require_signatures: false,
         });
         Ok(base)
     }
@@ -121,6 +133,22 @@ a lock file compatible with `{orig}` cannot be generated in this situation
         Ok(Box::new(ReplacedSource::new(id, &new_id, new_src)))
     }
 
+    /// Returns the signature policy configured for the source `id`.
+    ///
+    /// Sources without any signature configuration get a policy which
+    /// doesn't check anything.
+    pub fn signature_policy(&self, id: &SourceId) -> CargoResult<SignaturePolicy> {
+        let name = match self.id2name.get(id) {
+            Some(name) => name,
+            None => return Ok(SignaturePolicy::default()),
+        };
+        let cfg = &self.cfgs[name];
+        SignaturePolicy::new(&cfg.signing_keys, cfg.require_signatures)
+            .chain_err(|| {
+                format!("invalid signature configuration for `source.{}`", name)
+            })
+    }
+
     fn add(&mut self, name: &str, cfg: SourceConfig) {
         self.id2name.insert(cfg.id.clone(), name.to_string());
         self.cfgs.insert(name.to_string(), cfg);
@@ -173,9 +201,24 @@ a lock file compatible with `{orig}` cannot be generated in this situation
             replace_with = Some((s.to_string(), path.to_path_buf()));
         }
 
+        let mut signing_keys = Vec::new();
+        if let Some(val) = table.get("signing-keys") {
+            let keys = val.list(&format!("source.{}.signing-keys", name))?;
+            signing_keys.extend(keys.iter().map(|&(ref s, _)| s.clone()));
+        }
+
+        let mut require_signatures = false;
+        if let Some(val) = table.get("require-signatures") {
+            let (b, _) = val.boolean(&format!("source.{}.require-signatures",
+                                              name))?;
+            require_signatures = b;
+        }
+
         self.add(name, SourceConfig {
             id: src,
             replace_with: replace_with,
+            signing_keys: signing_keys,
+            require_signatures: require_signatures,
         });
 
         return Ok(());
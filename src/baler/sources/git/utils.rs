@@ -8,6 +8,7 @@ use serde::ser::{self, Serialize};
 use url::Url;
 
 use core::GitReference;
+use ops;
 use util::{ToUrl, internal, Config, network};
 use util::errors::{CargoResult, CargoResultExt, CargoError};
 
@@ -566,6 +567,12 @@ pub fn fetch(repo: &git2::Repository,
                was specified")
     }
 
+    // Mistakes in the HTTP configuration are ignored when the git transport
+    // is set up at startup, so report them now that they matter.
+    if ops::needs_custom_http_transport(config)? {
+        ops::http_handle(config)?;
+    }
+
     with_authentication(url, &repo.config()?, |f| {
         let mut cb = git2::RemoteCallbacks::new();
         cb.credentials(f);
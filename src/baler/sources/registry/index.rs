@@ -9,6 +9,7 @@ use core::dependency::Dependency;
 use core::{SourceId, Summary, PackageId};
 use sources::registry::{RegistryPackage, INDEX_LOCK};
 use sources::registry::RegistryData;
+use sources::registry::signature::{self, Signatures};
 use util::{CargoError, CargoResult, internal, Filesystem, Config};
 
 pub struct RegistryIndex<'cfg> {
@@ -73,35 +74,11 @@ impl<'cfg> RegistryIndex<'cfg> {
                       name: &str,
                       load: &mut RegistryData)
                       -> CargoResult<Vec<(Summary, bool)>> {
-        let (root, _lock) = if self.locked {
-            let lock = self.path.open_ro(Path::new(INDEX_LOCK),
-                                         self.config,
-                                         "the registry index");
-            match lock {
-                Ok(lock) => {
-                    (lock.path().parent().unwrap().to_path_buf(), Some(lock))
-                }
-                Err(_) => return Ok(Vec::new()),
-            }
-        } else {
-            (self.path.clone().into_path_unlocked(), None)
-        };
-
-        let fs_name = name.chars().flat_map(|c| {
-            c.to_lowercase()
-        }).collect::<String>();
-
-        // see module comment for why this is structured the way it is
-        let path = match fs_name.len() {
-            1 => format!("1/{}", fs_name),
-            2 => format!("2/{}", fs_name),
-            3 => format!("3/{}/{}", &fs_name[..1], fs_name),
-            _ => format!("{}/{}/{}", &fs_name[0..2], &fs_name[2..4], fs_name),
-        };
+        let path = index_file(name);
+        let index = self.path.clone();
+        let (config, locked) = (self.config, self.locked);
         let mut ret = Vec::new();
-        let mut hit_closure = false;
-        let err = load.load(&root, Path::new(&path), &mut |contents| {
-            hit_closure = true;
+        load_file(&index, config, locked, &path, load, &mut |contents| {
             let contents = str::from_utf8(contents).map_err(|_| {
                 CargoError::from("registry index file was not valid utf-8")
             })?;
@@ -120,16 +97,47 @@ impl<'cfg> RegistryIndex<'cfg> {
             }));
 
             Ok(())
-        });
+        })?;
+        Ok(ret)
+    }
 
-        // We ignore lookup failures as those are just crates which don't exist
-        // or we haven't updated the registry yet. If we actually ran the
-        // closure though then we care about those errors.
-        if hit_closure {
-            err?;
+    /// Returns the raw index line for a package along with the detached
+    /// signatures listed for it in the index, if any.
+    pub fn signed_entry(&mut self,
+                        pkg: &PackageId,
+                        load: &mut RegistryData)
+                        -> CargoResult<(String, Option<Signatures>)> {
+        #[derive(Deserialize)]
+        struct Entry {
+            vers: Version,
         }
 
-        Ok(ret)
+        let path = index_file(pkg.name());
+        let mut entry = None;
+        load_file(&self.path, self.config, self.locked, &path, load,
+                  &mut |contents| {
+            let contents = str::from_utf8(contents).map_err(|_| {
+                CargoError::from("registry index file was not valid utf-8")
+            })?;
+            entry = contents.lines().map(|s| s.trim()).find(|line| {
+                match serde_json::from_str::<Entry>(line) {
+                    Ok(e) => e.vers == *pkg.version(),
+                    Err(..) => false,
+                }
+            }).map(|s| s.to_string());
+            Ok(())
+        })?;
+        let entry = entry.ok_or_else(|| {
+            internal(format!("no index entry listed for {}", pkg))
+        })?;
+
+        let mut signatures = HashMap::new();
+        load_file(&self.path, self.config, self.locked,
+                  &format!("{}.sig", path), load, &mut |contents| {
+            signatures = signature::parse(contents);
+            Ok(())
+        })?;
+        Ok((entry, signatures.remove(pkg.version())))
     }
 
     /// Parse a line from the registry's index file into a Summary for a
@@ -190,3 +198,56 @@ impl<'cfg> RegistryIndex<'cfg> {
         Ok(())
     }
 }
+
+/// Returns the path of the index file for the package `name`.
+fn index_file(name: &str) -> String {
+    let fs_name = name.chars().flat_map(|c| {
+        c.to_lowercase()
+    }).collect::<String>();
+
+    // see module comment for why this is structured the way it is
+    match fs_name.len() {
+        1 => format!("1/{}", fs_name),
+        2 => format!("2/{}", fs_name),
+        3 => format!("3/{}/{}", &fs_name[..1], fs_name),
+        _ => format!("{}/{}/{}", &fs_name[0..2], &fs_name[2..4], fs_name),
+    }
+}
+
+/// Loads the file at `path` in the index, taking the index lock if `locked`.
+///
+/// Lookup failures are ignored as those are just files which don't exist or
+/// that we haven't updated the registry for yet, so `data` is simply not
+/// called for them.
+fn load_file(index: &Filesystem,
+             config: &Config,
+             locked: bool,
+             path: &str,
+             load: &mut RegistryData,
+             data: &mut FnMut(&[u8]) -> CargoResult<()>) -> CargoResult<()> {
+    let (root, _lock) = if locked {
+        let lock = index.open_ro(Path::new(INDEX_LOCK),
+                                 config,
+                                 "the registry index");
+        match lock {
+            Ok(lock) => {
+                (lock.path().parent().unwrap().to_path_buf(), Some(lock))
+            }
+            Err(_) => return Ok(()),
+        }
+    } else {
+        (index.clone().into_path_unlocked(), None)
+    };
+
+    let mut hit_closure = false;
+    let err = load.load(&root, Path::new(path), &mut |contents| {
+        hit_closure = true;
+        data(contents)
+    });
+
+    // If we actually ran the closure then we care about its errors.
+    if hit_closure {
+        err?;
+    }
+    Ok(())
+}
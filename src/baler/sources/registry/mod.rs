@@ -161,7 +161,9 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::SeekFrom;
+use std::io::prelude::*;
 use std::path::{PathBuf, Path};
 
 use flate2::read::GzDecoder;
@@ -171,8 +173,9 @@ use tar::Archive;
 
 use core::{Source, SourceId, PackageId, Package, Summary, Registry};
 use core::dependency::{Dependency, Kind};
-use sources::PathSource;
-use util::{CargoResult, Config, internal, FileLock, Filesystem};
+use sources::{PathSource, SourceConfigMap};
+use util::{CargoResult, Config, FileLock, Filesystem, LazyCell};
+use util::archive;
 use util::errors::CargoResultExt;
 use util::hex;
 
@@ -187,6 +190,7 @@ pub struct RegistrySource<'cfg> {
     ops: Box<RegistryData + 'cfg>,
     index: index::RegistryIndex<'cfg>,
     index_locked: bool,
+    signatures: LazyCell<SignaturePolicy>,
 }
 
 #[derive(Deserialize)]
@@ -239,9 +243,12 @@ pub trait RegistryData {
                 checksum: &str) -> CargoResult<FileLock>;
 }
 
+pub use self::signature::SignaturePolicy;
+
 mod index;
 mod remote;
 mod local;
+mod signature;
 
 fn short_name(id: &SourceId) -> String {
     let hash = hex::short_hash(id);
@@ -281,6 +288,7 @@ impl<'cfg> RegistrySource<'cfg> {
                                              index_locked),
             index_locked: index_locked,
             ops: ops,
+            signatures: LazyCell::new(),
         }
     }
 
@@ -311,13 +319,47 @@ impl<'cfg> RegistrySource<'cfg> {
             return Ok(dst)
         }
 
+        let prefix = format!("{}-{}", pkg.name(), pkg.version());
+        let limit = self.config.max_unpacked_size()?;
         let gz = GzDecoder::new(tarball.file())?;
         let mut tar = Archive::new(gz);
-        tar.unpack(dst.parent().unwrap())?;
+        if let Err(e) = archive::unpack(&mut tar, dst.parent().unwrap(),
+                                        &prefix, limit) {
+            // Don't leave anything from a rejected tarball lying around.
+            let _ = fs::remove_dir_all(&dst);
+            return Err(e)
+        }
         File::create(&ok)?;
         Ok(dst)
     }
 
+    /// Checks the detached signatures of a downloaded package against the keys
+    /// trusted for this source.
+    ///
+    /// This happens before the package is unpacked so nothing from an
+    /// untrusted tarball ever touches the filesystem.
+    fn verify_signatures(&mut self,
+                         pkg: &PackageId,
+                         tarball: &mut FileLock) -> CargoResult<()> {
+        let config = self.config;
+        let source_id = &self.source_id;
+        let policy = self.signatures.get_or_try_init(|| {
+            SourceConfigMap::new(config)?.signature_policy(source_id)
+        })?;
+        if !policy.is_enabled() {
+            return Ok(())
+        }
+
+        let (entry, signatures) = self.index.signed_entry(pkg,
+                                                          &mut *self.ops)?;
+        let mut krate = Vec::new();
+        tarball.read_to_end(&mut krate).chain_err(|| {
+            format!("failed to read `{}`", tarball.path().display())
+        })?;
+        tarball.seek(SeekFrom::Start(0))?;
+        policy.check(pkg, signatures.as_ref(), &entry, &krate)
+    }
+
     fn do_update(&mut self) -> CargoResult<()> {
         self.ops.update_index()?;
         let path = self.ops.index_path();
@@ -379,9 +421,10 @@ impl<'cfg> Source for RegistrySource<'cfg> {
 
     fn download(&mut self, package: &PackageId) -> CargoResult<Package> {
         let hash = self.index.hash(package, &mut *self.ops)?;
-        let path = self.ops.download(package, &hash)?;
+        let mut path = self.ops.download(package, &hash)?;
+        self.verify_signatures(package, &mut path)?;
         let path = self.unpack_package(package, &path).chain_err(|| {
-            internal(format!("failed to unpack package `{}`", package))
+            format!("failed to unpack package `{}`", package)
         })?;
         let mut src = PathSource::new(&path, &self.source_id, self.config);
         src.update()?;
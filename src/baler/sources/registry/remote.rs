@@ -249,6 +249,7 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
         network::with_retry(self.config, || {
             state = Sha256::new();
             body = Vec::new();
+            let mut headers = Vec::new();
             {
                 let mut handle = handle.transfer();
                 handle.write_function(|buf| {
@@ -256,12 +257,19 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
                     body.extend_from_slice(buf);
                     Ok(buf.len())
                 })?;
+                handle.header_function(|data| {
+                    headers.push(String::from_utf8_lossy(data).into_owned());
+                    true
+                })?;
                 handle.perform()?;
             }
             let code = handle.response_code()?;
             if code != 200 && code != 0 {
                 let url = handle.effective_url()?.unwrap_or(&url);
-                Err(CargoErrorKind::HttpNot200(code, url.to_string()).into())
+                let retry_after = network::retry_after(&headers);
+                let kind = CargoErrorKind::HttpNot200(code, url.to_string(),
+                                                      retry_after);
+                Err(kind.into())
             } else {
                 Ok(())
             }
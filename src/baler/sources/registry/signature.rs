@@ -0,0 +1,147 @@
+//! Verification of detached ed25519 signatures published by a registry.
+//!
+//! The checksums listed in a registry's index only prove that a `.crate` file
+//! matches the index, so a compromised index defeats them. Registries which
+//! want stronger guarantees can publish a detached signature file next to each
+//! file in the index. For an index file such as `se/rd/serde` the signatures
+//! live in `se/rd/serde.sig`, with one line per signed version:
+//!
+//! ```notrust
+//! <version> <signature of the index line> <signature of the .crate file>
+//! ```
+//!
+//! Both signatures are hex-encoded ed25519 signatures. The index line is
+//! signed exactly as it appears in the index (minus surrounding whitespace)
+//! and the crate signature covers the raw bytes of the `.crate` file.
+//!
+//! Which keys are trusted is configured per source in `.baler/config`:
+//!
+//! ```toml
+//! [source.my-registry]
+//! registry = "https://example.com/index"
+//! signing-keys = ["<hex-encoded ed25519 public key>"]
+//! require-signatures = true
+//! ```
+
+use std::collections::HashMap;
+use std::str;
+
+use hex::FromHex;
+use ring::signature;
+use semver::Version;
+use untrusted;
+
+use core::PackageId;
+use util::CargoResult;
+
+/// Policy for how signatures of a source should be checked.
+#[derive(Clone, Debug, Default)]
+pub struct SignaturePolicy {
+    keys: Vec<Vec<u8>>,
+    require: bool,
+}
+
+/// The signatures listed for one version of a package.
+pub struct Signatures {
+    pub entry: String,
+    pub krate: String,
+}
+
+impl SignaturePolicy {
+    /// Creates a new policy trusting the hex-encoded public `keys`.
+    ///
+    /// If `require` is set then packages without a valid signature from one
+    /// of the keys are rejected.
+    pub fn new(keys: &[String], require: bool) -> CargoResult<SignaturePolicy> {
+        let keys = keys.iter().map(|key| {
+            let bytes = Vec::<u8>::from_hex(key.trim()).map_err(|e| {
+                format!("invalid signing key `{}`: {}", key, e)
+            })?;
+            if bytes.len() != 32 {
+                bail!("invalid signing key `{}`: ed25519 public keys are 32 \
+                       bytes long, found {}", key, bytes.len())
+            }
+            Ok(bytes)
+        }).collect::<CargoResult<Vec<_>>>()?;
+        if require && keys.is_empty() {
+            bail!("signatures are required but no `signing-keys` are configured")
+        }
+        Ok(SignaturePolicy {
+            keys: keys,
+            require: require,
+        })
+    }
+
+    /// Returns whether any signature checking should happen at all.
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Checks the signatures of a package against its raw index line and
+    /// `.crate` file contents.
+    ///
+    /// Missing signatures are only an error if signatures are required, but a
+    /// signature which is present and invalid is always an error.
+    pub fn check(&self,
+                 pkg: &PackageId,
+                 signatures: Option<&Signatures>,
+                 entry: &str,
+                 krate: &[u8]) -> CargoResult<()> {
+        let signatures = match signatures {
+            Some(s) => s,
+            None if self.require => {
+                bail!("no signature was found for `{}` and this source \
+                       requires signatures", pkg)
+            }
+            None => return Ok(()),
+        };
+        if !self.verify(entry.as_bytes(), &signatures.entry) {
+            bail!("failed to verify the signature of the index entry for `{}`",
+                  pkg)
+        }
+        if !self.verify(krate, &signatures.krate) {
+            bail!("failed to verify the signature of the crate file for `{}`",
+                  pkg)
+        }
+        Ok(())
+    }
+
+    fn verify(&self, msg: &[u8], sig: &str) -> bool {
+        let sig = match Vec::<u8>::from_hex(sig) {
+            Ok(sig) => sig,
+            Err(..) => return false,
+        };
+        self.keys.iter().any(|key| {
+            signature::verify(&signature::ED25519,
+                              untrusted::Input::from(key),
+                              untrusted::Input::from(msg),
+                              untrusted::Input::from(&sig)).is_ok()
+        })
+    }
+}
+
+/// Parses the contents of a `.sig` file in the index.
+///
+/// Lines that can't be understood are ignored, in the same way that the index
+/// itself ignores lines it doesn't understand.
+pub fn parse(contents: &[u8]) -> HashMap<Version, Signatures> {
+    let contents = match str::from_utf8(contents) {
+        Ok(s) => s,
+        Err(..) => return HashMap::new(),
+    };
+    contents.lines().filter_map(|line| {
+        let mut parts = line.split_whitespace();
+        let vers = parts.next().and_then(|v| Version::parse(v).ok());
+        let entry = parts.next();
+        let krate = parts.next();
+        match (vers, entry, krate, parts.next()) {
+            (Some(vers), Some(entry), Some(krate), None) => {
+                Some((vers, Signatures {
+                    entry: entry.to_string(),
+                    krate: krate.to_string(),
+                }))
+            }
+            _ => None,
+        }
+    }).collect()
+}
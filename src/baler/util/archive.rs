@@ -0,0 +1,227 @@
+//! Safety checks for the `.crate` tarballs that baler builds and unpacks.
+//!
+//! Every entry of a `.crate` file is expected to live under a single
+//! `<name>-<version>` directory. Both building and unpacking an archive go
+//! through the checks here so a tarball which `baler package` would refuse to
+//! create is also refused when it's downloaded from a registry.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use tar::{Archive, EntryType};
+
+use util::CargoResult;
+use util::errors::CargoResultExt;
+
+/// Checks that `path`, the path of an entry in an archive, is a relative path
+/// living under the `prefix` directory.
+pub fn check_path(path: &Path, prefix: &str) -> CargoResult<()> {
+    let components = resolve(&[], path).ok_or_else(|| {
+        format!("invalid path in archive: `{}` is absolute or escapes the \
+                 archive", path.display())
+    })?;
+    if components.first().cloned() != Some(OsStr::new(prefix)) {
+        bail!("invalid path in archive: `{}` is not under the `{}` directory",
+              path.display(), prefix)
+    }
+    Ok(())
+}
+
+/// Checks that a link at `path` in an archive pointing to `target` stays
+/// within the `prefix` directory.
+///
+/// Symbolic link targets are relative to the directory containing the link,
+/// whereas hard link targets are relative to the root of the archive.
+///
+/// A `..` is only accepted at the start of a symbolic link's target, and not
+/// at all in a hard link's target. Anywhere else it could climb out of a
+/// directory which is itself a symbolic link, and we can't know that from the
+/// paths alone.
+pub fn check_link(path: &Path,
+                  target: &Path,
+                  symlink: bool,
+                  prefix: &str) -> CargoResult<()> {
+    let mut descended = !symlink;
+    for component in target.components() {
+        match component {
+            Component::ParentDir if descended => {
+                bail!("invalid link in archive: `{}` points to `{}` which \
+                       uses `..` in a position that isn't allowed",
+                      path.display(), target.display())
+            }
+            Component::Normal(..) => descended = true,
+            _ => {}
+        }
+    }
+
+    let base = if symlink {
+        resolve(&[], path.parent().unwrap_or(Path::new("")))
+    } else {
+        Some(Vec::new())
+    };
+    let resolved = base.and_then(|base| resolve(&base, target));
+    let inside = match resolved {
+        Some(ref components) => {
+            components.first().cloned() == Some(OsStr::new(prefix))
+        }
+        None => false,
+    };
+    if !inside {
+        bail!("invalid link in archive: `{}` points to `{}` which is outside \
+               of the `{}` directory",
+              path.display(), target.display(), prefix)
+    }
+    Ok(())
+}
+
+/// Checks that the total unpacked size of an archive stays below `limit`.
+pub fn check_size(total: u64, limit: u64) -> CargoResult<()> {
+    if total > limit {
+        bail!("archive is larger than the maximum unpacked size of {} bytes \
+               (configured by `registry.max-unpacked-size`)", limit)
+    }
+    Ok(())
+}
+
+/// Unpacks `archive` into `dst`, which will end up containing the `prefix`
+/// directory.
+///
+/// Unlike `Archive::unpack` every entry is checked first: entries must live
+/// under `prefix`, links must not point outside of it, device files and other
+/// special entries are rejected and the total unpacked size may not exceed
+/// `limit` bytes.
+pub fn unpack<R: Read>(archive: &mut Archive<R>,
+                       dst: &Path,
+                       prefix: &str,
+                       limit: u64) -> CargoResult<()> {
+    let mut total = 0u64;
+    let mut symlinks = HashSet::new();
+    for entry in archive.entries()? {
+        let mut entry = entry.chain_err(|| "failed to iterate over archive")?;
+        let path = entry.path()
+                        .chain_err(|| "failed to read entry path")?
+                        .into_owned();
+        let kind = entry.header().entry_type();
+        if kind.is_pax_global_extensions() || kind.is_pax_local_extensions() {
+            continue
+        }
+        check_path(&path, prefix)?;
+        let path = normalize(&path);
+
+        // A symlink to a directory inside the package could otherwise be used
+        // to place later entries somewhere else entirely.
+        let mut parent = path.parent();
+        while let Some(p) = parent {
+            if symlinks.contains(p) {
+                bail!("invalid path in archive: `{}` is located inside of a \
+                       symbolic link", path.display())
+            }
+            parent = p.parent();
+        }
+
+        let link = match kind {
+            EntryType::Regular | EntryType::Continuous | EntryType::Directory => None,
+            EntryType::Symlink | EntryType::Link => {
+                let target = entry.link_name()
+                                  .chain_err(|| "failed to read link target")?
+                                  .ok_or_else(|| {
+                    format!("invalid link in archive: `{}` has no target",
+                            path.display())
+                })?.into_owned();
+                check_link(&path, &target, kind.is_symlink(), prefix)?;
+                Some(target)
+            }
+            _ => {
+                bail!("invalid entry in archive: `{}` is not a regular file, \
+                       directory or link", path.display())
+            }
+        };
+
+        total = total.saturating_add(entry.header().size()?);
+        check_size(total, limit)?;
+
+        let file = dst.join(&path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).chain_err(|| {
+                format!("failed to create `{}`", parent.display())
+            })?;
+        }
+
+        match link {
+            // Hard link targets are relative to the root of the archive, which
+            // isn't where `Entry::unpack` would look for them.
+            Some(ref target) if kind.is_hard_link() => {
+                fs::hard_link(dst.join(normalize(target)), &file).chain_err(|| {
+                    format!("failed to unpack `{}`", path.display())
+                })?;
+            }
+            _ => {
+                entry.unpack(&file).chain_err(|| {
+                    format!("failed to unpack `{}`", path.display())
+                })?;
+            }
+        }
+        if kind.is_symlink() {
+            symlinks.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns `path` with all `.` and `..` components resolved, which must have
+/// been checked by `resolve` first.
+fn normalize(path: &Path) -> PathBuf {
+    resolve(&[], path).unwrap_or_default().iter().collect()
+}
+
+/// Applies the components of `path` on top of `base`, returning `None` if
+/// `path` is absolute or would escape `base`'s root.
+fn resolve<'a>(base: &[&'a OsStr], path: &'a Path) -> Option<Vec<&'a OsStr>> {
+    let mut ret = base.to_vec();
+    for component in path.components() {
+        match component {
+            Component::Prefix(..) | Component::RootDir => return None,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if ret.pop().is_none() {
+                    return None
+                }
+            }
+            Component::Normal(c) => ret.push(c),
+        }
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{check_path, check_link};
+
+    #[test]
+    fn paths() {
+        assert!(check_path(Path::new("foo-0.1.0/src/lib.rs"), "foo-0.1.0").is_ok());
+        assert!(check_path(Path::new("./foo-0.1.0/a/../b"), "foo-0.1.0").is_ok());
+        assert!(check_path(Path::new("/foo-0.1.0/src/lib.rs"), "foo-0.1.0").is_err());
+        assert!(check_path(Path::new("foo-0.1.0/../x"), "foo-0.1.0").is_err());
+        assert!(check_path(Path::new("../foo-0.1.0/x"), "foo-0.1.0").is_err());
+        assert!(check_path(Path::new("bar-0.1.0/x"), "foo-0.1.0").is_err());
+    }
+
+    #[test]
+    fn links() {
+        let link = Path::new("foo-0.1.0/src/link");
+        assert!(check_link(link, Path::new("lib.rs"), true, "foo-0.1.0").is_ok());
+        assert!(check_link(link, Path::new("../Baler.toml"), true, "foo-0.1.0").is_ok());
+        assert!(check_link(link, Path::new("../../x"), true, "foo-0.1.0").is_err());
+        assert!(check_link(link, Path::new("/etc/passwd"), true, "foo-0.1.0").is_err());
+        assert!(check_link(link, Path::new("dir/../lib.rs"), true, "foo-0.1.0").is_err());
+        assert!(check_link(link, Path::new("foo-0.1.0/src/lib.rs"), false, "foo-0.1.0").is_ok());
+        assert!(check_link(link, Path::new("foo-0.1.0/../x"), false, "foo-0.1.0").is_err());
+        assert!(check_link(link, Path::new("lib.rs"), false, "foo-0.1.0").is_err());
+    }
+}
@@ -359,6 +359,35 @@ impl Config {
         }
     }
 
+    /// The delay in milliseconds before the first retry of a network call,
+    /// which is doubled for every further retry.
+    pub fn net_retry_delay(&self) -> CargoResult<u64> {
+        match self.get_i64("net.retry-delay")? {
+            Some(v) => {
+                if v.val < 0 {
+                    bail!("net.retry-delay must not be negative, but found {} \
+                           in {}", v.val, v.definition)
+                }
+                Ok(v.val as u64)
+            }
+            None => Ok(500),
+        }
+    }
+
+    /// The maximum number of bytes a `.crate` file may unpack to.
+    pub fn max_unpacked_size(&self) -> CargoResult<u64> {
+        match self.get_i64("registry.max-unpacked-size")? {
+            Some(v) => {
+                if v.val <= 0 {
+                    bail!("registry.max-unpacked-size must be positive, but \
+                           found {} in {}", v.val, v.definition)
+                }
+                Ok(v.val as u64)
+            }
+            None => Ok(512 * 1024 * 1024),
+        }
+    }
+
     pub fn expected<T>(&self, ty: &str, key: &str, val: CV) -> CargoResult<T> {
         val.expected(ty, key).map_err(|e| {
             format!("invalid configuration for key `{}`\n{}", key, e).into()
@@ -877,3 +906,26 @@ pub fn save_credentials(cfg: &Config,
         Ok(())
     }
 }
+
+pub fn erase_credentials(cfg: &Config) -> CargoResult<()> {
+    let home_path = cfg.home_path.clone().into_path_unlocked();
+    if !fs::metadata(home_path.join("credentials")).is_ok() {
+        return Ok(());
+    }
+    let mut file = cfg.home_path.open_rw(Path::new("credentials"), cfg,
+                                         "credentials' config file")?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).chain_err(|| {
+        format!("failed to read configuration file `{}`",
+                      file.path().display())
+    })?;
+    let mut toml = baler_toml::parse(&contents, file.path(), cfg)?;
+    toml.as_table_mut().unwrap().remove("token");
+
+    let contents = toml.to_string();
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(contents.as_bytes())?;
+    file.file().set_len(contents.len() as u64)?;
+    Ok(())
+}
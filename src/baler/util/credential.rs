@@ -0,0 +1,126 @@
+//! Storing registry tokens with an external credential process.
+//!
+//! Instead of keeping tokens in plaintext in `~/.baler/credentials`, the
+//! `registry.credential-process` configuration key can name an executable
+//! (optionally followed by arguments) which stores them on baler's behalf.
+//!
+//! For each operation baler runs the process and writes a single JSON request
+//! to its stdin, closing it afterwards:
+//!
+//! ```json
+//! {"v": 1, "action": "get", "index-url": "https://github.com/rust-lang/crates.io-index"}
+//! ```
+//!
+//! The `action` is one of:
+//!
+//! * `get` - respond with the token for `index-url`, as in `{"token": "..."}`,
+//!   or with `{"token": null}` if no token is known.
+//! * `store` - save the `token` field of the request for `index-url` and
+//!   respond with `{}`.
+//! * `erase` - forget the token for `index-url` and respond with `{}`.
+//!
+//! The process signals a failure either by exiting unsuccessfully or by
+//! responding with `{"error": "..."}`.
+
+use std::path::PathBuf;
+use std::str;
+
+use serde_json;
+
+use util::{Config, process};
+use util::config::Definition;
+use util::errors::{CargoResult, CargoResultExt};
+
+/// The version of the protocol spoken to credential processes.
+const PROTOCOL_VERSION: u32 = 1;
+
+pub struct CredentialProcess {
+    path: PathBuf,
+    args: Vec<String>,
+    definition: Definition,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    v: u32,
+    action: &'a str,
+    #[serde(rename = "index-url")]
+    index_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    token: Option<String>,
+    error: Option<String>,
+}
+
+/// Returns the credential process configured by `registry.credential-process`,
+/// if any.
+pub fn credential_process(config: &Config) -> CargoResult<Option<CredentialProcess>> {
+    let val = match config.get_path_and_args("registry.credential-process")? {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let (path, args) = val.val;
+    Ok(Some(CredentialProcess {
+        path: path,
+        args: args,
+        definition: val.definition,
+    }))
+}
+
+impl CredentialProcess {
+    /// Returns the token stored for the registry with the index `index_url`.
+    pub fn get(&self, index_url: &str) -> CargoResult<Option<String>> {
+        let response = self.run("get", index_url, None)?;
+        Ok(response.token)
+    }
+
+    /// Stores `token` for the registry with the index `index_url`.
+    pub fn store(&self, index_url: &str, token: &str) -> CargoResult<()> {
+        self.run("store", index_url, Some(token))?;
+        Ok(())
+    }
+
+    /// Removes the token stored for the registry with the index `index_url`.
+    pub fn erase(&self, index_url: &str) -> CargoResult<()> {
+        self.run("erase", index_url, None)?;
+        Ok(())
+    }
+
+    fn run(&self,
+           action: &str,
+           index_url: &str,
+           token: Option<&str>) -> CargoResult<Response> {
+        let request = serde_json::to_vec(&Request {
+            v: PROTOCOL_VERSION,
+            action: action,
+            index_url: index_url,
+            token: token,
+        })?;
+        let mut cmd = process(&self.path);
+        cmd.args(&self.args);
+
+        (|| -> CargoResult<Response> {
+            let output = cmd.exec_with_input(&request)?;
+            let stdout = str::from_utf8(&output.stdout).map_err(|_| {
+                "credential process did not respond with valid utf-8"
+            })?;
+            let response: Response = serde_json::from_str(stdout).chain_err(|| {
+                format!("credential process responded with invalid JSON: {}",
+                        stdout.trim())
+            })?;
+            if let Some(ref error) = response.error {
+                bail!("{}", error)
+            }
+            Ok(response)
+        })().chain_err(|| {
+            format!("failed to `{}` the token for `{}` using the credential \
+                     process `{}` (configured by `registry.credential-process` \
+                     in {})",
+                    action, index_url, self.path.display(), self.definition)
+        })
+    }
+}
@@ -3,6 +3,16 @@
 //!
 //! This structure is used to store the dependency graph and dynamically update
 //! it to figure out when a dependency should be built.
+//!
+//! Each dependency edge is labelled with the kind of artifact it waits for, so
+//! a package can finish in stages and release the packages which only need an
+//! earlier stage before it is done.
+//!
+//! Once everything is queued, each package is given a priority: the total cost
+//! of building it and everything which transitively depends on it. Of the
+//! packages ready to be built, the one with the highest priority is dequeued
+//! first, so the long chains which hold up the end of a build start as early as
+//! possible.
 
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::{HashMap, HashSet};
@@ -11,20 +21,21 @@ use std::hash::Hash;
 pub use self::Freshness::{Fresh, Dirty};
 
 #[derive(Debug)]
-pub struct DependencyQueue<K: Eq + Hash, V> {
+pub struct DependencyQueue<K: Eq + Hash, E: Eq + Hash, V> {
     /// A list of all known keys to build.
     ///
-    /// The value of the hash map is list of dependencies which still need to be
-    /// built before the package can be built. Note that the set is dynamically
+    /// The value of the hash map is list of dependencies, along with the
+    /// artifact of each which is waited for, which still need to be built
+    /// before the package can be built. Note that the set is dynamically
     /// updated as more dependencies are built.
-    dep_map: HashMap<K, (HashSet<K>, V)>,
+    dep_map: HashMap<K, (HashSet<(K, E)>, V)>,
 
     /// A reverse mapping of a package to all packages that depend on that
-    /// package.
+    /// package, by the artifact they wait for.
     ///
     /// This map is statically known and does not get updated throughout the
     /// lifecycle of the DependencyQueue.
-    reverse_dep_map: HashMap<K, HashSet<K>>,
+    reverse_dep_map: HashMap<K, HashMap<E, HashSet<K>>>,
 
     /// A set of dirty packages.
     ///
@@ -34,6 +45,14 @@ pub struct DependencyQueue<K: Eq + Hash, V> {
     /// The packages which are currently being built, waiting for a call to
     /// `finish`.
     pending: HashSet<K>,
+
+    /// The order in which packages were queued, used to break ties between
+    /// packages of the same priority so dequeueing is deterministic.
+    order: HashMap<K, usize>,
+
+    /// The cost of building each package plus all of its transitive
+    /// dependents, as calculated by `queue_finished`.
+    priority: HashMap<K, u64>,
 }
 
 /// Indication of the freshness of a package.
@@ -52,26 +71,29 @@ impl Freshness {
     }
 }
 
-impl<K: Hash + Eq + Clone, V> DependencyQueue<K, V> {
+impl<K: Hash + Eq + Clone, E: Hash + Eq + Clone, V> DependencyQueue<K, E, V> {
     /// Creates a new dependency queue with 0 packages.
-    pub fn new() -> DependencyQueue<K, V> {
+    pub fn new() -> DependencyQueue<K, E, V> {
         DependencyQueue {
             dep_map: HashMap::new(),
             reverse_dep_map: HashMap::new(),
             dirty: HashSet::new(),
             pending: HashSet::new(),
+            order: HashMap::new(),
+            priority: HashMap::new(),
         }
     }
 
     /// Adds a new package to this dependency queue.
     ///
     /// It is assumed that any dependencies of this package will eventually also
-    /// be added to the dependency queue.
+    /// be added to the dependency queue. Each dependency is listed with the
+    /// artifact of it which this package waits for.
     pub fn queue(&mut self,
                  fresh: Freshness,
                  key: K,
                  value: V,
-                 dependencies: &[K]) -> &mut V {
+                 dependencies: &[(K, E)]) -> &mut V {
         let slot = match self.dep_map.entry(key.clone()) {
             Occupied(v) => return &mut v.into_mut().1,
             Vacant(v) => v,
@@ -80,27 +102,89 @@ impl<K: Hash + Eq + Clone, V> DependencyQueue<K, V> {
         if fresh == Dirty {
             self.dirty.insert(key.clone());
         }
+        let order = self.order.len();
+        self.order.insert(key.clone(), order);
 
         let mut my_dependencies = HashSet::new();
-        for dep in dependencies {
-            my_dependencies.insert(dep.clone());
+        for &(ref dep, ref edge) in dependencies {
+            my_dependencies.insert((dep.clone(), edge.clone()));
             let rev = self.reverse_dep_map.entry(dep.clone())
+                                          .or_insert_with(HashMap::new)
+                                          .entry(edge.clone())
                                           .or_insert_with(HashSet::new);
             rev.insert(key.clone());
         }
         &mut slot.insert((my_dependencies, value)).1
     }
 
+    /// Indicates that all packages have been queued, working out the priority
+    /// of each from the `cost` of building it.
+    ///
+    /// Packages without a known cost are assumed to cost the average of those
+    /// with one, or 1 if none have one, in which case a package's priority is
+    /// the number of packages which depend on it.
+    pub fn queue_finished(&mut self, cost: &mut FnMut(&K) -> Option<u64>) {
+        let costs = self.dep_map.keys().map(|key| {
+            (key.clone(), cost(key))
+        }).collect::<HashMap<_, _>>();
+        let known = costs.values().filter_map(|c| *c).collect::<Vec<_>>();
+        let default = if known.is_empty() {
+            1
+        } else {
+            known.iter().sum::<u64>() / known.len() as u64
+        };
+
+        let mut dependents = HashMap::new();
+        for key in self.dep_map.keys() {
+            let all = transitive_dependents(key, &self.reverse_dep_map, &mut dependents);
+            let total = all.iter().map(|dep| {
+                costs.get(dep).and_then(|c| *c).unwrap_or(default)
+            }).sum();
+            self.priority.insert(key.clone(), total);
+        }
+
+        fn transitive_dependents<K, E>(key: &K,
+                                       reverse_dep_map: &HashMap<K, HashMap<E, HashSet<K>>>,
+                                       results: &mut HashMap<K, HashSet<K>>)
+                                       -> HashSet<K>
+            where K: Hash + Eq + Clone, E: Hash + Eq
+        {
+            if let Some(all) = results.get(key) {
+                return all.clone()
+            }
+            let mut all = HashSet::new();
+            all.insert(key.clone());
+            if let Some(edges) = reverse_dep_map.get(key) {
+                for dep in edges.values().flat_map(|deps| deps.iter()) {
+                    all.extend(transitive_dependents(dep, reverse_dep_map, results));
+                }
+            }
+            results.insert(key.clone(), all.clone());
+            all
+        }
+    }
+
     /// Dequeues a package that is ready to be built.
     ///
-    /// A package is ready to be built when it has 0 un-built dependencies. If
-    /// `None` is returned then no packages are ready to be built.
+    /// A package is ready to be built when it has 0 un-built dependencies. Of
+    /// those, the one with the highest priority is returned, or the one queued
+    /// first if several share it. If `None` is returned then no packages are
+    /// ready to be built.
     pub fn dequeue(&mut self) -> Option<(Freshness, K, V)> {
-        let key = match self.dep_map.iter()
-                                    .find(|&(_, &(ref deps, _))| deps.is_empty())
-                                    .map(|(key, _)| key.clone()) {
-            Some(key) => key,
-            None => return None
+        let key = {
+            let priority = &self.priority;
+            let order = &self.order;
+            let ready = self.dep_map.iter()
+                                    .filter(|&(_, &(ref deps, _))| deps.is_empty())
+                                    .map(|(key, _)| key);
+            let next = ready.max_by_key(|key| {
+                let priority = priority.get(*key).cloned().unwrap_or(0);
+                (priority, !order[*key])
+            });
+            match next {
+                Some(key) => key.clone(),
+                None => return None
+            }
         };
         let (_, data) = self.dep_map.remove(&key).unwrap();
         let fresh = if self.dirty.contains(&key) {Dirty} else {Fresh};
@@ -118,21 +202,94 @@ impl<K: Hash + Eq + Clone, V> DependencyQueue<K, V> {
         self.dep_map.len() + self.pending.len()
     }
 
+    /// Indicate that the `edge` artifact of a package which is still being
+    /// built is ready.
+    ///
+    /// This releases the packages waiting for only that artifact, possibly
+    /// allowing the next invocation of `dequeue` to return a package.
+    pub fn finish_edge(&mut self, key: &K, edge: &E, fresh: Freshness) {
+        assert!(self.pending.contains(key));
+        let reverse_deps = match self.reverse_dep_map.get(key)
+                                                     .and_then(|r| r.get(edge)) {
+            Some(deps) => deps,
+            None => return,
+        };
+        let dep_edge = (key.clone(), edge.clone());
+        for dep in reverse_deps.iter() {
+            if fresh == Dirty {
+                self.dirty.insert(dep.clone());
+            }
+            assert!(self.dep_map.get_mut(dep).unwrap().0.remove(&dep_edge));
+        }
+    }
+
     /// Indicate that a package has been built.
     ///
     /// This function will update the dependency queue with this information,
     /// possibly allowing the next invocation of `dequeue` to return a package.
+    /// Every package depending on this one is released, other than those
+    /// already released through `finish_edge`.
     pub fn finish(&mut self, key: &K, fresh: Freshness) {
         assert!(self.pending.remove(key));
         let reverse_deps = match self.reverse_dep_map.get(key) {
             Some(deps) => deps,
             None => return,
         };
-        for dep in reverse_deps.iter() {
-            if fresh == Dirty {
-                self.dirty.insert(dep.clone());
+        for (edge, deps) in reverse_deps.iter() {
+            let dep_edge = (key.clone(), edge.clone());
+            for dep in deps.iter() {
+                // Packages released earlier may have been dequeued already.
+                if let Some(&mut (ref mut waiting, _)) = self.dep_map.get_mut(dep) {
+                    if waiting.remove(&dep_edge) && fresh == Dirty {
+                        self.dirty.insert(dep.clone());
+                    }
+                }
             }
-            assert!(self.dep_map.get_mut(dep).unwrap().0.remove(key));
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{DependencyQueue, Fresh};
+
+    fn dequeue_all(q: &mut DependencyQueue<&'static str, (), ()>) -> Vec<&'static str> {
+        let mut ret = Vec::new();
+        while let Some((_, key, _)) = q.dequeue() {
+            q.finish(&key, Fresh);
+            ret.push(key);
+        }
+        ret
+    }
+
+    #[test]
+    fn long_chains_first() {
+        let mut q = DependencyQueue::new();
+        q.queue(Fresh, "a", (), &[]);
+        q.queue(Fresh, "b", (), &[]);
+        q.queue(Fresh, "c", (), &[("b", ())]);
+        q.queue(Fresh, "d", (), &[("c", ())]);
+        q.queue_finished(&mut |_| None);
+        assert_eq!(dequeue_all(&mut q), vec!["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn costs_are_used() {
+        let mut q = DependencyQueue::new();
+        q.queue(Fresh, "a", (), &[]);
+        q.queue(Fresh, "b", (), &[]);
+        q.queue(Fresh, "c", (), &[("b", ())]);
+        q.queue_finished(&mut |key| if *key == "a" { Some(100) } else { Some(1) });
+        assert_eq!(dequeue_all(&mut q), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn ties_in_queue_order() {
+        let mut q = DependencyQueue::new();
+        for key in ["e", "d", "c", "b", "a"].iter() {
+            q.queue(Fresh, *key, (), &[]);
+        }
+        q.queue_finished(&mut |_| None);
+        assert_eq!(dequeue_all(&mut q), vec!["e", "d", "c", "b", "a"]);
+    }
+}
@@ -53,7 +53,7 @@ error_chain! {
             description(&test_err.desc)
             display("{}", &test_err.desc)
         }
-        HttpNot200(code: u32, url: String) {
+        HttpNot200(code: u32, url: String, retry_after: Option<u64>) {
             description("failed to get a 200 response")
             display("failed to get 200 response from `{}`, got {}", url, code)
         }
@@ -2,6 +2,7 @@ use serde::ser;
 use serde_json::{self, Value};
 
 use core::{PackageId, Target, Profile};
+use ops::LinkType;
 
 pub trait Message: ser::Serialize {
     fn reason(&self) -> &str;
@@ -30,9 +31,13 @@ impl<'a> Message for FromCompiler<'a> {
 pub struct Artifact<'a> {
     pub package_id: &'a PackageId,
     pub target: &'a Target,
+    pub target_triple: &'a str,
     pub profile: &'a Profile,
     pub features: Vec<String>,
     pub filenames: Vec<String>,
+    /// The path of the binary this unit produced, if it produced one which
+    /// can be run.
+    pub executable: Option<String>,
     pub fresh: bool,
 }
 
@@ -48,7 +53,10 @@ pub struct BuildScript<'a> {
     pub linked_libs: &'a [String],
     pub linked_paths: &'a [String],
     pub cfgs: &'a [String],
+    pub check_cfgs: &'a [String],
+    pub linker_args: &'a [(LinkType, String)],
     pub env: &'a [(String, String)],
+    pub out_dir: &'a str,
 }
 
 impl<'a> Message for BuildScript<'a> {
@@ -56,3 +64,43 @@ impl<'a> Message for BuildScript<'a> {
         "build-script-executed"
     }
 }
+
+#[derive(Serialize)]
+pub struct BuildFinished {
+    pub success: bool,
+}
+
+impl Message for BuildFinished {
+    fn reason(&self) -> &str {
+        "build-finished"
+    }
+}
+
+#[derive(Serialize)]
+pub struct UnitDirtyReason<'a> {
+    pub package_id: &'a PackageId,
+    pub target: &'a Target,
+    pub dirty_reason: serde_json::Value,
+    pub message: String,
+}
+
+impl<'a> Message for UnitDirtyReason<'a> {
+    fn reason(&self) -> &str {
+        "unit-dirty-reason"
+    }
+}
+
+#[derive(Serialize)]
+pub struct TimingInfo<'a> {
+    pub package_id: &'a PackageId,
+    pub target: &'a Target,
+    pub mode: &'a str,
+    pub duration: f64,
+    pub fresh: bool,
+}
+
+impl<'a> Message for TimingInfo<'a> {
+    fn reason(&self) -> &str {
+        "timing-info"
+    }
+}
@@ -19,7 +19,9 @@ pub use self::to_url::ToUrl;
 pub use self::vcs::{GitRepo, HgRepo, PijulRepo, FossilRepo};
 pub use self::read2::read2;
 
+pub mod archive;
 pub mod config;
+pub mod credential;
 pub mod errors;
 pub mod graph;
 pub mod hex;
@@ -1,60 +1,143 @@
 use std;
+use std::cmp;
 use std::error::Error;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use error_chain::ChainedError;
 
+use registry;
 use util::Config;
 use util::errors::{CargoError, CargoErrorKind, CargoResult};
 
 use git2;
-fn maybe_spurious<E, EKind>(err: &E) -> bool
+
+/// The longest delay between two retries, whether it comes from backing off
+/// or from a `Retry-After` header.
+const MAX_DELAY_MS: u64 = 60_000;
+
+//Error inspection in non-verbose mode requires inspecting the
+//error kind to avoid printing Internal errors. The downcasting
+//machinery requires &(Error + 'static), but the iterator (and
+//underlying `cause`) return &Error. Because the borrows are
+//constrained to this handling method, and because the original
+//error object is constrained to be 'static, we're casting away
+//the borrow's actual lifetime for purposes of downcasting and
+//inspecting the error chain
+fn baler_errors<'a, E, EKind>(err: &'a E) -> Vec<&'a CargoError>
     where E: ChainedError<ErrorKind=EKind> + 'static {
-    //Error inspection in non-verbose mode requires inspecting the
-    //error kind to avoid printing Internal errors. The downcasting
-    //machinery requires &(Error + 'static), but the iterator (and
-    //underlying `cause`) return &Error. Because the borrows are
-    //constrained to this handling method, and because the original
-    //error object is constrained to be 'static, we're casting away
-    //the borrow's actual lifetime for purposes of downcasting and
-    //inspecting the error chain
     unsafe fn extend_lifetime(r: &Error) -> &(Error + 'static) {
-        std::mem::transmute::<&Error, &Error>(r)    
+        std::mem::transmute::<&Error, &Error>(r)
     }
 
-    for e in err.iter() {
+    err.iter().filter_map(|e| {
         let e = unsafe { extend_lifetime(e) };
-        if let Some(baler_err) = e.downcast_ref::<CargoError>() {
-            match baler_err.kind() {
-                &CargoErrorKind::Git(ref git_err) => {
-                    match git_err.class() {
-                        git2::ErrorClass::Net |
-                        git2::ErrorClass::Os => return true,
-                        _ => ()
-                    }
-                }
-                &CargoErrorKind::Curl(ref curl_err) 
-                    if curl_err.is_couldnt_connect() ||
-                        curl_err.is_couldnt_resolve_proxy() ||
-                        curl_err.is_couldnt_resolve_host() ||
-                        curl_err.is_operation_timedout() ||
-                        curl_err.is_recv_error() => {
-                    return true
-                }
-                &CargoErrorKind::HttpNot200(code, ref _url) if 500 <= code && code < 600 => {
-                    return true
+        e.downcast_ref::<CargoError>()
+    }).collect()
+}
+
+fn maybe_spurious<E, EKind>(err: &E) -> bool
+    where E: ChainedError<ErrorKind=EKind> + 'static {
+    for baler_err in baler_errors(err) {
+        match baler_err.kind() {
+            &CargoErrorKind::Git(ref git_err) => {
+                match git_err.class() {
+                    git2::ErrorClass::Net |
+                    git2::ErrorClass::Os => return true,
+                    _ => ()
                 }
-                _ => ()
             }
+            &CargoErrorKind::Curl(ref curl_err) |
+            &CargoErrorKind::CrateRegistry(registry::ErrorKind::Curl(ref curl_err))
+                if spurious_curl_error(curl_err) => {
+                return true
+            }
+            &CargoErrorKind::HttpNot200(code, ..) |
+            &CargoErrorKind::CrateRegistry(registry::ErrorKind::NotOkResponse(code, ..))
+                if spurious_http_code(code) => {
+                return true
+            }
+            _ => ()
         }
     }
     false
 }
 
+fn spurious_curl_error(err: &::curl::Error) -> bool {
+    err.is_couldnt_connect() ||
+        err.is_couldnt_resolve_proxy() ||
+        err.is_couldnt_resolve_host() ||
+        err.is_operation_timedout() ||
+        err.is_recv_error() ||
+        err.is_send_error() ||
+        err.is_got_nothing() ||
+        err.is_partial_file()
+}
+
+/// Too many requests, or a server error.
+fn spurious_http_code(code: u32) -> bool {
+    code == 429 || (500 <= code && code < 600)
+}
+
+/// Returns how long the server asked us to wait before retrying, if the error
+/// came from a response with a `Retry-After` header.
+fn server_delay<E, EKind>(err: &E) -> Option<Duration>
+    where E: ChainedError<ErrorKind=EKind> + 'static {
+    baler_errors(err).into_iter().filter_map(|baler_err| {
+        match baler_err.kind() {
+            &CargoErrorKind::HttpNot200(_, _, retry_after) => retry_after,
+            &CargoErrorKind::CrateRegistry(
+                registry::ErrorKind::NotOkResponse(_, ref headers, _)) => {
+                retry_after(headers)
+            }
+            _ => None,
+        }
+    }).next().map(Duration::from_secs)
+}
+
+/// Parses the `Retry-After` header out of the raw `headers` of a response.
+///
+/// Only the number-of-seconds form of the header is understood. A date is
+/// ignored, in which case we fall back to our own backoff.
+pub fn retry_after(headers: &[String]) -> Option<u64> {
+    headers.iter().filter_map(|header| {
+        let mut parts = header.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => {
+                if name.trim().to_lowercase() == "retry-after" {
+                    value.trim().parse().ok()
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }).next()
+}
+
+/// Returns how long to wait before the retry following `attempt` failed
+/// attempts, given the delay in milliseconds before the first retry.
+///
+/// The delay doubles with every attempt, and is then picked at random from
+/// its upper half so many clients failing at once don't retry in lockstep.
+fn backoff(initial_delay_ms: u64, attempt: u32) -> Duration {
+    let max = initial_delay_ms.saturating_mul(1 << cmp::min(attempt, 16));
+    let max = cmp::min(max, MAX_DELAY_MS);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+                                 .map(|d| d.subsec_nanos() as u64)
+                                 .unwrap_or(0);
+    Duration::from_millis(max / 2 + nanos % (max / 2 + 1))
+}
+
 /// Wrapper method for network call retry logic.
 ///
 /// Retry counts provided by Config object `net.retry`. Config shell outputs
 /// a warning on per retry.
 ///
+/// Between attempts this sleeps with a jittered exponential backoff starting
+/// at `net.retry-delay`, unless the server said how long to wait with a
+/// `Retry-After` header.
+///
 /// Closure must return a CargoResult.
 ///
 /// # Examples
@@ -63,32 +146,47 @@ fn maybe_spurious<E, EKind>(err: &E) -> bool
 /// use util::network;
 /// baler_result = network.with_retry(&config, || something.download());
 /// ```
-pub fn with_retry<T, F>(config: &Config, mut callback: F) -> CargoResult<T>
+pub fn with_retry<T, F>(config: &Config, callback: F) -> CargoResult<T>
     where F: FnMut() -> CargoResult<T>
 {
-    let mut remaining = config.net_retry()?;
+    with_retry_delay(config, config.net_retry_delay()?, callback)
+}
+
+/// Like `with_retry`, but waiting `initial_delay_ms` before the first retry.
+fn with_retry_delay<T, F>(config: &Config, initial_delay_ms: u64, mut callback: F)
+                          -> CargoResult<T>
+    where F: FnMut() -> CargoResult<T>
+{
+    let retries = config.net_retry()? as u32;
+    let mut attempt = 0;
     loop {
         match callback() {
             Ok(ret) => return Ok(ret),
-            Err(ref e) if maybe_spurious(e) && remaining > 0 => {
-                let msg = format!("spurious network error ({} tries \
-                          remaining): {}", remaining, e);
+            Err(ref e) if maybe_spurious(e) && attempt < retries => {
+                let msg = format!("spurious network error on attempt {}/{}: {}",
+                                  attempt + 1, retries + 1, e);
                 config.shell().warn(msg)?;
-                remaining -= 1;
+                let delay = match server_delay(e) {
+                    Some(delay) => cmp::min(delay, Duration::from_millis(MAX_DELAY_MS)),
+                    None => backoff(initial_delay_ms, attempt),
+                };
+                thread::sleep(delay);
+                attempt += 1;
             }
             //todo impl from
             Err(e) => return Err(e.into()),
         }
     }
 }
+
 #[test]
 fn with_retry_repeats_the_call_then_works() {
     //Error HTTP codes (5xx) are considered maybe_spurious and will prompt retry
-    let error1 = CargoErrorKind::HttpNot200(501, "Uri".to_string()).into();
-    let error2 = CargoErrorKind::HttpNot200(502, "Uri".to_string()).into();
+    let error1 = CargoErrorKind::HttpNot200(501, "Uri".to_string(), None).into();
+    let error2 = CargoErrorKind::HttpNot200(502, "Uri".to_string(), None).into();
     let mut results: Vec<CargoResult<()>> = vec![Ok(()), Err(error1), Err(error2)];
     let config = Config::default().unwrap();
-    let result = with_retry(&config, || results.pop().unwrap());
+    let result = with_retry_delay(&config, 0, || results.pop().unwrap());
     assert_eq!(result.unwrap(), ())
 }
 
@@ -96,12 +194,40 @@ fn with_retry_repeats_the_call_then_works() {
 fn with_retry_finds_nested_spurious_errors() {
     //Error HTTP codes (5xx) are considered maybe_spurious and will prompt retry
     //String error messages are not considered spurious
-    let error1 : CargoError = CargoErrorKind::HttpNot200(501, "Uri".to_string()).into();
+    let error1 : CargoError = CargoErrorKind::HttpNot200(501, "Uri".to_string(), None).into();
     let error1 = CargoError::with_chain(error1, "A non-spurious wrapping err");
-    let error2 = CargoError::from_kind(CargoErrorKind::HttpNot200(502, "Uri".to_string()));
+    let error2 = CargoError::from_kind(CargoErrorKind::HttpNot200(502, "Uri".to_string(), None));
     let error2 = CargoError::with_chain(error2, "A second chained error");
     let mut results: Vec<CargoResult<()>> = vec![Ok(()), Err(error1), Err(error2)];
     let config = Config::default().unwrap();
-    let result = with_retry(&config, || results.pop().unwrap());
+    let result = with_retry_delay(&config, 0, || results.pop().unwrap());
     assert_eq!(result.unwrap(), ())
 }
+
+#[test]
+fn with_retry_honours_retry_after() {
+    //Too many requests is spurious, and the server's delay replaces ours
+    let error1 = CargoErrorKind::HttpNot200(429, "Uri".to_string(), Some(0)).into();
+    let mut results: Vec<CargoResult<()>> = vec![Ok(()), Err(error1)];
+    let config = Config::default().unwrap();
+    let result = with_retry_delay(&config, 0, || results.pop().unwrap());
+    assert_eq!(result.unwrap(), ())
+}
+
+#[test]
+fn with_retry_gives_up_on_client_errors() {
+    let error1 = CargoErrorKind::HttpNot200(404, "Uri".to_string(), None).into();
+    let mut results: Vec<CargoResult<()>> = vec![Ok(()), Err(error1)];
+    let config = Config::default().unwrap();
+    let result = with_retry_delay(&config, 0, || results.pop().unwrap());
+    assert!(result.is_err())
+}
+
+#[test]
+fn parses_retry_after() {
+    let headers = vec!["HTTP/1.1 503 Service Unavailable\r\n".to_string(),
+                       "retry-after: 3\r\n".to_string()];
+    assert_eq!(retry_after(&headers), Some(3));
+    let headers = vec!["Retry-After: Wed, 21 Oct 2015 07:28:00 GMT\r\n".to_string()];
+    assert_eq!(retry_after(&headers), None);
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::env;
 use std::ffi::{OsString, OsStr};
 use std::fmt;
+use std::io::Write;
 use std::path::Path;
 use std::process::{Command, Stdio, Output};
 
@@ -61,6 +62,10 @@ impl ProcessBuilder {
         self
     }
 
+    pub fn get_program(&self) -> &OsString {
+        &self.program
+    }
+
     pub fn get_args(&self) -> &[OsString] {
         &self.args
     }
@@ -133,6 +138,34 @@ impl ProcessBuilder {
         }
     }
 
+    /// Like `exec_with_output`, but also writes `input` to the process's
+    /// stdin before closing it.
+    pub fn exec_with_input(&self, input: &[u8]) -> CargoResult<Output> {
+        let mut command = self.build_command();
+        command.stdin(Stdio::piped())
+               .stdout(Stdio::piped())
+               .stderr(Stdio::piped());
+
+        let output = (|| {
+            let mut child = command.spawn()?;
+            child.stdin.take().unwrap().write_all(input)?;
+            child.wait_with_output()
+        })().chain_err(|| {
+            CargoErrorKind::ProcessErrorKind(
+                process_error(
+                    &format!("could not execute process `{}`", self.debug_string()),
+                          None, None))
+        })?;
+
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(CargoErrorKind::ProcessErrorKind(process_error(
+                &format!("process didn't exit successfully: `{}`", self.debug_string()),
+                Some(&output.status), Some(&output))).into())
+        }
+    }
+
     pub fn exec_with_streaming(&self,
                                on_stdout_line: &mut FnMut(&str) -> CargoResult<()>,
                                on_stderr_line: &mut FnMut(&str) -> CargoResult<()>,
@@ -107,6 +107,7 @@ macro_rules! each_subcommand{
         $mac!(clean);
         $mac!(doc);
         $mac!(fetch);
+        $mac!(fix);
         $mac!(generate_lockfile);
         $mac!(git_checkout);
         $mac!(help);
@@ -114,6 +115,7 @@ macro_rules! each_subcommand{
         $mac!(install);
         $mac!(locate_project);
         $mac!(login);
+        $mac!(logout);
         $mac!(metadata);
         $mac!(new);
         $mac!(owner);
@@ -394,17 +396,24 @@ fn search_directories(config: &Config) -> Vec<PathBuf> {
 }
 
 fn init_git_transports(config: &Config) {
-    // Only use a custom transport if a proxy is configured, right now libgit2
-    // doesn't support proxies and we have to use a custom transport in this
-    // case. The custom transport, however, is not as well battle-tested.
-    match baler::ops::http_proxy_exists(config) {
+    // Only use a custom transport if a proxy or TLS options are configured,
+    // right now libgit2 doesn't support those and we have to use a custom
+    // transport in this case. The custom transport, however, is not as well
+    // battle-tested.
+    //
+    // Errors in the configuration are ignored here, commands which use the
+    // network report them when they create their own HTTP handles.
+    match baler::ops::needs_custom_http_transport(config) {
         Ok(true) => {}
         _ => return,
     }
 
     let handle = match baler::ops::http_handle(config) {
         Ok(handle) => handle,
-        Err(..) => return,
+        Err(e) => {
+            debug!("not using a custom git transport: {}", e);
+            return
+        }
     };
 
     // The unsafety of the registration function derives from two aspects:
@@ -11,12 +11,13 @@ pub struct Options {
     flag_features: Vec<String>,
     flag_all_features: bool,
     flag_no_default_features: bool,
-    flag_target: Option<String>,
+    flag_target: Vec<String>,
     flag_manifest_path: Option<String>,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_message_format: MessageFormat,
+    flag_timings: bool,
     flag_lib: bool,
     flag_bin: Vec<String>,
     flag_bins: bool,
@@ -59,12 +60,13 @@ Options:
     --features FEATURES          Space-separated list of features to also build
     --all-features               Build all available features
     --no-default-features        Do not build the `default` feature
-    --target TRIPLE              Build for the target triple
+    --target TRIPLE ...          Build for the target triple(s)
     --manifest-path PATH         Path to the manifest to build benchmarks for
     -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
-    --message-format FMT         Error format: human, json [default: human]
+    --message-format FMT         Error format: human, short, json, json-render-diagnostics [default: human]
+    --timings                    Write a report of how long each unit took to target/baler-timings
     --no-fail-fast               Run all benchmarks regardless of failure
     --frozen                     Require Baler.lock and cache are up to date
     --locked                     Require Baler.lock is up to date
@@ -106,7 +108,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
         compile_opts: ops::CompileOptions {
             config: config,
             jobs: options.flag_jobs,
-            target: options.flag_target.as_ref().map(|s| &s[..]),
+            targets: &options.flag_target,
             features: &options.flag_features,
             all_features: options.flag_all_features,
             no_default_features: options.flag_no_default_features,
@@ -121,6 +123,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
             message_format: options.flag_message_format,
             target_rustdoc_args: None,
             target_rustc_args: None,
+            build_plan: false,
+            timings: options.flag_timings,
+            keep_going: false,
+            out_dir: None,
         },
     };
 
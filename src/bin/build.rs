@@ -12,12 +12,15 @@ pub struct Options {
     flag_features: Vec<String>,
     flag_all_features: bool,
     flag_no_default_features: bool,
-    flag_target: Option<String>,
+    flag_target: Vec<String>,
     flag_manifest_path: Option<String>,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_message_format: MessageFormat,
+    flag_timings: bool,
+    flag_keep_going: bool,
+    flag_out_dir: Option<String>,
     flag_release: bool,
     flag_lib: bool,
     flag_bin: Vec<String>,
@@ -32,6 +35,7 @@ pub struct Options {
     flag_frozen: bool,
     flag_all: bool,
     flag_exclude: Vec<String>,
+    flag_build_plan: bool,
 }
 
 pub const USAGE: &'static str = "
@@ -59,12 +63,16 @@ Options:
     --features FEATURES          Space-separated list of features to also build
     --all-features               Build all available features
     --no-default-features        Do not build the `default` feature
-    --target TRIPLE              Build for the target triple
+    --target TRIPLE ...          Build for the target triple(s)
     --manifest-path PATH         Path to the manifest to compile
     -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
-    --message-format FMT         Error format: human, json [default: human]
+    --message-format FMT         Error format: human, short, json, json-render-diagnostics [default: human]
+    --timings                    Write a report of how long each unit took to target/baler-timings
+    --keep-going                 Keep building units which don't depend on a failed one
+    --out-dir PATH               Copy the final artifacts to this directory
+    --build-plan                 Output the build plan in JSON without running anything
     --frozen                     Require Baler.lock and cache are up to date
     --locked                     Require Baler.lock is up to date
 
@@ -80,6 +88,13 @@ Note that `--exclude` has to be specified in conjunction with the `--all` flag.
 Compilation can be configured via the use of profiles which are configured in
 the manifest. The default profile for this command is `dev`, but passing
 the --release flag will use the `release` profile instead.
+
+With --out-dir, or `build.out-dir` in the configuration, the binaries and
+libraries which were asked for are copied into the given directory once the
+build succeeds, without the hashes in their names.
+
+With --build-plan, every process the build would run is printed to stdout as
+JSON instead, in the order they would run, and nothing is compiled.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult {
@@ -97,10 +112,15 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
                                     &options.flag_exclude,
                                     &options.flag_package)?;
 
+    let out_dir = match options.flag_out_dir {
+        Some(ref dir) => Some(config.cwd().join(dir)),
+        None => config.get_path("build.out-dir")?.map(|v| v.val),
+    };
+
     let opts = CompileOptions {
         config: config,
         jobs: options.flag_jobs,
-        target: options.flag_target.as_ref().map(|t| &t[..]),
+        targets: &options.flag_target,
         features: &options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
@@ -115,6 +135,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
         message_format: options.flag_message_format,
         target_rustdoc_args: None,
         target_rustc_args: None,
+        build_plan: options.flag_build_plan,
+        timings: options.flag_timings,
+        keep_going: options.flag_keep_going,
+        out_dir: out_dir.as_ref().map(|d| d.as_path()),
     };
 
     let ws = Workspace::new(&root, config)?;
@@ -30,12 +30,14 @@ Options:
     --features FEATURES          Space-separated list of features to also check
     --all-features               Check all available features
     --no-default-features        Do not check the `default` feature
-    --target TRIPLE              Check for the target triple
+    --target TRIPLE ...          Check for the target triple(s)
     --manifest-path PATH         Path to the manifest to compile
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
-    --message-format FMT         Error format: human, json [default: human]
+    --message-format FMT         Error format: human, short, json, json-render-diagnostics [default: human]
+    --timings                    Write a report of how long each unit took to target/baler-timings
+    --keep-going                 Keep building units which don't depend on a failed one
     --frozen                     Require Baler.lock and cache are up to date
     --locked                     Require Baler.lock is up to date
 
@@ -56,12 +58,14 @@ pub struct Options {
     flag_features: Vec<String>,
     flag_all_features: bool,
     flag_no_default_features: bool,
-    flag_target: Option<String>,
+    flag_target: Vec<String>,
     flag_manifest_path: Option<String>,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_message_format: MessageFormat,
+    flag_timings: bool,
+    flag_keep_going: bool,
     flag_release: bool,
     flag_lib: bool,
     flag_bin: Vec<String>,
@@ -98,7 +102,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
     let opts = CompileOptions {
         config: config,
         jobs: options.flag_jobs,
-        target: options.flag_target.as_ref().map(|t| &t[..]),
+        targets: &options.flag_target,
         features: &options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
@@ -113,6 +117,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
         message_format: options.flag_message_format,
         target_rustdoc_args: None,
         target_rustc_args: None,
+        build_plan: false,
+        timings: options.flag_timings,
+        keep_going: options.flag_keep_going,
+        out_dir: None,
     };
 
     ops::compile(&ws, &opts)?;
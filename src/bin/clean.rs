@@ -14,6 +14,8 @@ pub struct Options {
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_release: bool,
+    flag_stale: bool,
+    flag_dry_run: bool,
     flag_frozen: bool,
     flag_locked: bool,
 }
@@ -30,6 +32,8 @@ Options:
     --manifest-path PATH         Path to the manifest to the package to clean
     --target TRIPLE              Target triple to clean output for (default all)
     --release                    Whether or not to clean release artifacts
+    --stale                      Only remove artifacts the workspace no longer builds
+    --dry-run                    With --stale, only report what would be removed
     -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
@@ -40,6 +44,12 @@ If the --package argument is given, then SPEC is a package id specification
 which indicates which package's artifacts should be cleaned out. If it is not
 given, then all packages' artifacts are removed. For more information on SPEC
 and its format, see the `baler help pkgid` command.
+
+With --stale, the files in the layout selected by --target and --release are
+kept if they were built from a package, at its current version, which is still
+part of the workspace's resolve, whatever features or profile they were built
+with. Everything else left behind by earlier versions of the dependencies or of
+the package is removed, and the number of files and their size is summarized.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult {
@@ -56,6 +66,8 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
         spec: &options.flag_package,
         target: options.flag_target.as_ref().map(|s| &s[..]),
         release: options.flag_release,
+        stale: options.flag_stale,
+        dry_run: options.flag_dry_run,
     };
     let ws = Workspace::new(&root, config)?;
     ops::clean(&ws, &opts)?;
@@ -52,7 +52,7 @@ Options:
     -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
-    --message-format FMT         Error format: human, json [default: human]
+    --message-format FMT         Error format: human, short, json, json-render-diagnostics [default: human]
     --frozen                     Require Baler.lock and cache are up to date
     --locked                     Require Baler.lock is up to date
 
@@ -84,12 +84,13 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
     };
 
     let empty = Vec::new();
+    let targets = options.flag_target.iter().cloned().collect::<Vec<_>>();
     let doc_opts = ops::DocOptions {
         open_result: options.flag_open,
         compile_opts: ops::CompileOptions {
             config: config,
             jobs: options.flag_jobs,
-            target: options.flag_target.as_ref().map(|t| &t[..]),
+            targets: &targets,
             features: &options.flag_features,
             all_features: options.flag_all_features,
             no_default_features: options.flag_no_default_features,
@@ -105,6 +106,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
                 deps: !options.flag_no_deps,
             },
             target_rustc_args: None,
+            build_plan: false,
+            timings: false,
+            keep_going: false,
+            out_dir: None,
             target_rustdoc_args: None,
         },
     };
@@ -0,0 +1,127 @@
+use std::env;
+
+use baler::core::Workspace;
+use baler::ops::{self, CompileOptions, MessageFormat, Packages};
+use baler::util::{CliResult, Config};
+use baler::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(Deserialize)]
+pub struct Options {
+    flag_package: Vec<String>,
+    flag_jobs: Option<u32>,
+    flag_features: Vec<String>,
+    flag_all_features: bool,
+    flag_no_default_features: bool,
+    flag_target: Vec<String>,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_release: bool,
+    flag_lib: bool,
+    flag_bin: Vec<String>,
+    flag_bins: bool,
+    flag_example: Vec<String>,
+    flag_examples: bool,
+    flag_test: Vec<String>,
+    flag_tests: bool,
+    flag_bench: Vec<String>,
+    flag_benches: bool,
+    flag_allow_dirty: bool,
+    flag_locked: bool,
+    flag_frozen: bool,
+    flag_all: bool,
+    flag_exclude: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Automatically apply the fixes rustc suggests for its warnings
+
+Usage:
+    baler fix [options]
+
+Options:
+    -h, --help                   Print this message
+    -p SPEC, --package SPEC ...  Package(s) to fix
+    --all                        Fix all packages in the workspace
+    --exclude SPEC ...           Exclude packages from fixing
+    -j N, --jobs N               Number of parallel jobs, defaults to # of CPUs
+    --lib                        Fix only this package's library
+    --bin NAME                   Fix only the specified binary
+    --bins                       Fix all binaries
+    --example NAME               Fix only the specified example
+    --examples                   Fix all examples
+    --test NAME                  Fix only the specified test target
+    --tests                      Fix all tests
+    --bench NAME                 Fix only the specified bench target
+    --benches                    Fix all benches
+    --release                    Check artifacts in release mode, with optimizations
+    --features FEATURES          Space-separated list of features to also check
+    --all-features               Check all available features
+    --no-default-features        Do not check the `default` feature
+    --target TRIPLE ...          Check for the target triple(s)
+    --allow-dirty                Fix code even if the working directory has changes
+    --manifest-path PATH         Path to the manifest to fix
+    -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
+    -q, --quiet                  No output printed to stdout
+    --color WHEN                 Coloring: auto, always, never
+    --frozen                     Require Baler.lock and cache are up to date
+    --locked                     Require Baler.lock is up to date
+
+This command checks the selected packages like `baler check` and applies the
+suggestions rustc is certain about to their source files. The packages are
+checked again after every round of fixes, until there's nothing left to fix.
+
+Files which have changes that were not yet committed into git are left alone,
+and fixing is refused, unless --allow-dirty is passed. If a round of fixes
+stops the packages from compiling, the files it fixed are reverted one at a
+time, in order of their paths, until they compile again.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult {
+    debug!("executing; cmd=baler-fix; args={:?}",
+           env::args().collect::<Vec<_>>());
+
+    config.configure(options.flag_verbose,
+                     options.flag_quiet,
+                     &options.flag_color,
+                     options.flag_frozen,
+                     options.flag_locked)?;
+
+    let root = find_root_manifest_for_wd(options.flag_manifest_path, config.cwd())?;
+    let ws = Workspace::new(&root, config)?;
+
+    let spec = Packages::from_flags(options.flag_all,
+                                    &options.flag_exclude,
+                                    &options.flag_package)?;
+
+    let opts = ops::FixOptions {
+        compile_opts: CompileOptions {
+            config: config,
+            jobs: options.flag_jobs,
+            targets: &options.flag_target,
+            features: &options.flag_features,
+            all_features: options.flag_all_features,
+            no_default_features: options.flag_no_default_features,
+            spec: spec,
+            mode: ops::CompileMode::Check,
+            release: options.flag_release,
+            filter: ops::CompileFilter::new(options.flag_lib,
+                                            &options.flag_bin, options.flag_bins,
+                                            &options.flag_test, options.flag_tests,
+                                            &options.flag_example, options.flag_examples,
+                                            &options.flag_bench, options.flag_benches,),
+            message_format: MessageFormat::Human,
+            target_rustdoc_args: None,
+            target_rustc_args: None,
+            build_plan: false,
+            timings: false,
+            keep_going: false,
+            out_dir: None,
+        },
+        allow_dirty: options.flag_allow_dirty,
+    };
+
+    ops::fix(&ws, &opts)?;
+    Ok(())
+}
@@ -108,7 +108,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
     let compile_opts = ops::CompileOptions {
         config: config,
         jobs: options.flag_jobs,
-        target: None,
+        targets: &[],
         features: &options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
@@ -122,6 +122,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
                                         &[], false),
         message_format: ops::MessageFormat::Human,
         target_rustc_args: None,
+        build_plan: false,
+        timings: false,
+        keep_going: false,
+        out_dir: None,
         target_rustdoc_args: None,
     };
 
@@ -2,12 +2,13 @@ use std::io::prelude::*;
 use std::io;
 
 use baler::ops;
-use baler::core::{SourceId, Source};
+use baler::core::Source;
 use baler::sources::RegistrySource;
 use baler::util::{CliResult, CargoResultExt, Config};
 
 #[derive(Deserialize)]
 pub struct Options {
+    flag_index: Option<String>,
     flag_host: Option<String>,
     arg_token: Option<String>,
     flag_verbose: u32,
@@ -25,6 +26,7 @@ Usage:
 
 Options:
     -h, --help               Print this message
+    --index INDEX            Registry index to save the token for
     --host HOST              Host to set the token for
     -v, --verbose ...        Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet              No output printed to stdout
@@ -40,10 +42,11 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
                      &options.flag_color,
                      options.flag_frozen,
                      options.flag_locked)?;
+    let index = options.flag_index.clone();
     let token = match options.arg_token.clone() {
         Some(token) => token,
         None => {
-            let src = SourceId::crates_io(config)?;
+            let src = ops::registry_source_id(config, index.clone())?;
             let mut src = RegistrySource::remote(&src, config);
             src.update()?;
             let config = src.config()?.unwrap();
@@ -59,7 +62,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
     };
 
     let token = token.trim().to_string();
-    ops::registry_login(config, token)?;
+    ops::registry_login(config, token, index)?;
     Ok(())
 }
 
@@ -0,0 +1,42 @@
+use baler::ops;
+use baler::util::{CliResult, Config};
+
+#[derive(Deserialize)]
+pub struct Options {
+    flag_index: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+}
+
+pub const USAGE: &'static str = "
+Remove the api token for the registry from local storage
+
+Usage:
+    baler logout [options]
+
+Options:
+    -h, --help               Print this message
+    --index INDEX            Registry index to remove the token for
+    -v, --verbose ...        Use verbose output (-vv very verbose/build.rs output)
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Baler.lock and cache are up to date
+    --locked                 Require Baler.lock is up to date
+
+If `registry.credential-process` is configured the token is erased through
+that process, otherwise it is removed from the credentials file in baler's
+home directory.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult {
+    config.configure(options.flag_verbose,
+                     options.flag_quiet,
+                     &options.flag_color,
+                     options.flag_frozen,
+                     options.flag_locked)?;
+    ops::registry_logout(config, options.flag_index)?;
+    Ok(())
+}
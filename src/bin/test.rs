@@ -13,7 +13,7 @@ pub struct Options {
     flag_no_default_features: bool,
     flag_no_run: bool,
     flag_package: Vec<String>,
-    flag_target: Option<String>,
+    flag_target: Vec<String>,
     flag_lib: bool,
     flag_doc: bool,
     flag_bin: Vec<String>,
@@ -28,6 +28,8 @@ pub struct Options {
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_message_format: MessageFormat,
+    flag_timings: bool,
+    flag_keep_going: bool,
     flag_release: bool,
     flag_no_fail_fast: bool,
     flag_frozen: bool,
@@ -63,12 +65,14 @@ Options:
     --features FEATURES          Space-separated list of features to also build
     --all-features               Build all available features
     --no-default-features        Do not build the `default` feature
-    --target TRIPLE              Build for the target triple
+    --target TRIPLE ...          Build for the target triple(s)
     --manifest-path PATH         Path to the manifest to build tests for
     -v, --verbose ...            Use verbose output (-vv very verbose/build.rs output)
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
-    --message-format FMT         Error format: human, json [default: human]
+    --message-format FMT         Error format: human, short, json, json-render-diagnostics [default: human]
+    --timings                    Write a report of how long each unit took to target/baler-timings
+    --keep-going                 Keep building units which don't depend on a failed one
     --no-fail-fast               Run all tests regardless of failure
     --frozen                     Require Baler.lock and cache are up to date
     --locked                     Require Baler.lock is up to date
@@ -143,7 +147,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
         compile_opts: ops::CompileOptions {
             config: config,
             jobs: options.flag_jobs,
-            target: options.flag_target.as_ref().map(|s| &s[..]),
+            targets: &options.flag_target,
             features: &options.flag_features,
             all_features: options.flag_all_features,
             no_default_features: options.flag_no_default_features,
@@ -154,6 +158,10 @@ pub fn execute(options: Options, config: &Config) -> CliResult {
             message_format: options.flag_message_format,
             target_rustdoc_args: None,
             target_rustc_args: None,
+            build_plan: false,
+            timings: options.flag_timings,
+            keep_going: options.flag_keep_going,
+            out_dir: None,
         },
     };
 
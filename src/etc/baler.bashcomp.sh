@@ -26,9 +26,9 @@ _baler()
 	local opt_lock='--frozen --locked'
 
 	local opt___nocmd="$opt_common -V --version --list"
-	local opt__bench="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --target --lib --bin --example --no-run"
-	local opt__build="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --target --lib --bin --example --release"
-	local opt__check="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --target --lib --bin --example --release"
+	local opt__bench="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --timings --target --lib --bin --example --no-run"
+	local opt__build="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --timings --target --lib --bin --example --release --build-plan"
+	local opt__check="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --timings --target --lib --bin --example --release"
 	local opt__clean="$opt_common $opt_pkg $opt_mani $opt_lock --target --release"
 	local opt__doc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs --message-format --bin --lib --target --open --no-deps --release"
 	local opt__fetch="$opt_common $opt_mani $opt_lock"
@@ -39,6 +39,7 @@ _baler()
 	local opt__install="$opt_common $opt_feat $opt_jobs $opt_lock $opt_force --bin --branch --debug --example --git --list --path --rev --root --tag --vers"
 	local opt__locate_project="$opt_mani -h --help"
 	local opt__login="$opt_common $opt_lock --host"
+	local opt__logout="$opt_common $opt_lock"
 	local opt__metadata="$opt_common $opt_feat $opt_mani $opt_lock --format-version --no-deps"
 	local opt__new="$opt_common $opt_lock --vcs --bin --lib --name"
 	local opt__owner="$opt_common $opt_lock -a --add -r --remove -l --list --index --token"
@@ -50,7 +51,7 @@ _baler()
 	local opt__rustc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --profile --target --lib --bin --example --release"
 	local opt__rustdoc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --target --lib --bin --example --release --open"
 	local opt__search="$opt_common $opt_lock --host --limit"
-	local opt__test="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --all --doc --target --lib --bin --example --no-run --release --no-fail-fast"
+	local opt__test="$opt_common $opt_pkg $opt_feat $opt_mani $opt_lock $opt_jobs $opt_test --message-format --timings --all --doc --target --lib --bin --example --no-run --release --no-fail-fast"
 	local opt__uninstall="$opt_common $opt_lock --bin --root"
 	local opt__update="$opt_common $opt_pkg $opt_mani $opt_lock --aggressive --precise"
 	local opt__verify_project="${opt__fetch}"
@@ -0,0 +1,124 @@
+//! A reference credential process which keeps registry tokens in a file.
+//!
+//! Usage: `baler-credential-file <path>`
+//!
+//! Each line of the file at `<path>` is `<index-url> <token>`. This speaks the
+//! protocol described in `baler::util::credential`, and has no dependencies
+//! so it can be built as a standalone project in tests.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => fail("usage: baler-credential-file <path>"),
+    };
+    let mut request = String::new();
+    if io::stdin().read_to_string(&mut request).is_err() {
+        fail("failed to read the request from stdin");
+    }
+    let action = field(&request, "action").unwrap_or_else(|| fail("missing `action`"));
+    let index = field(&request, "index-url").unwrap_or_else(|| fail("missing `index-url`"));
+
+    let mut tokens = read_tokens(&path);
+    match &action[..] {
+        "get" => {
+            match tokens.iter().find(|t| t.0 == index) {
+                Some(&(_, ref token)) => {
+                    respond(&format!("{{\"token\":\"{}\"}}", escape(token)))
+                }
+                None => respond("{\"token\":null}"),
+            }
+        }
+        "store" => {
+            let token = field(&request, "token").unwrap_or_else(|| fail("missing `token`"));
+            tokens.retain(|t| t.0 != index);
+            tokens.push((index, token));
+            write_tokens(&path, &tokens);
+            respond("{}");
+        }
+        "erase" => {
+            tokens.retain(|t| t.0 != index);
+            write_tokens(&path, &tokens);
+            respond("{}");
+        }
+        other => {
+            respond(&format!("{{\"error\":\"unsupported action `{}`\"}}",
+                             escape(other)))
+        }
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    let _ = writeln!(io::stderr(), "error: {}", msg);
+    process::exit(1)
+}
+
+fn respond(json: &str) {
+    println!("{}", json);
+}
+
+fn read_tokens(path: &str) -> Vec<(String, String)> {
+    let mut contents = String::new();
+    if let Ok(mut f) = File::open(path) {
+        if f.read_to_string(&mut contents).is_err() {
+            fail("failed to read the token file");
+        }
+    }
+    contents.lines().filter_map(|line| {
+        let mut parts = line.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some(index), Some(token)) => Some((index.to_string(), token.to_string())),
+            _ => None,
+        }
+    }).collect()
+}
+
+fn write_tokens(path: &str, tokens: &[(String, String)]) {
+    let contents = tokens.iter().map(|&(ref index, ref token)| {
+        format!("{} {}\n", index, token)
+    }).collect::<String>();
+    let written = File::create(path).and_then(|mut f| f.write_all(contents.as_bytes()));
+    if written.is_err() {
+        fail("failed to write the token file");
+    }
+}
+
+/// Finds the string value of `key` in a flat JSON object.
+///
+/// This is far from a complete JSON parser, but the requests baler sends are
+/// flat objects with string values which is all we need to understand.
+fn field(json: &str, key: &str) -> Option<String> {
+    let pat = format!("\"{}\"", key);
+    let start = match json.find(&pat) {
+        Some(i) => i + pat.len(),
+        None => return None,
+    };
+    let rest = &json[start..];
+    let rest = rest.trim_left().trim_left_matches(':').trim_left();
+    if !rest.starts_with('"') {
+        return None
+    }
+    let mut ret = String::new();
+    let mut chars = rest[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(ret),
+            '\\' => match chars.next() {
+                Some('n') => ret.push('\n'),
+                Some('t') => ret.push('\t'),
+                Some(c) => ret.push(c),
+                None => return None,
+            },
+            c => ret.push(c),
+        }
+    }
+    None
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,292 @@
+//! A reference executor which runs each job in a scratch directory.
+//!
+//! Usage: `baler-executor-scratch <dir>`
+//!
+//! This speaks the protocol described in
+//! `baler::ops::baler_rustc::process_executor`. Each job's outputs are written
+//! to a fresh directory under `<dir>` rather than to its `--out-dir`, leaving
+//! baler to copy them back, and every input it's given must exist. The crate
+//! name of each job is appended to `<dir>/log`, and a line with the crate name
+//! and each of the job's inputs to `<dir>/inputs`. This has no dependencies so
+//! it can be built as a standalone project in tests.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::str::Chars;
+
+fn main() {
+    let dir = match env::args().nth(1) {
+        Some(dir) => PathBuf::from(dir),
+        None => fail("usage: baler-executor-scratch <dir>"),
+    };
+    let mut request = String::new();
+    if io::stdin().read_to_string(&mut request).is_err() {
+        fail("failed to read the request from stdin");
+    }
+    let request = match Parser { chars: request.chars().peekable() }.value() {
+        Ok(request) => request,
+        Err(e) => fail(&format!("invalid request: {}", e)),
+    };
+
+    let program = request.get("program").and_then(Json::as_str)
+        .unwrap_or_else(|| fail("missing `program`"));
+    let mut args = request.get("args").and_then(Json::as_array)
+        .unwrap_or_else(|| fail("missing `args`"))
+        .iter().map(|a| a.as_str().unwrap_or_else(|| fail("invalid `args`")).to_string())
+        .collect::<Vec<_>>();
+
+    let inputs = request.get("inputs").and_then(Json::as_array)
+        .unwrap_or_else(|| fail("missing `inputs`"));
+    for input in inputs {
+        let input = input.as_str().unwrap_or_else(|| fail("invalid `inputs`"));
+        if fs::metadata(input).is_err() {
+            respond(&format!("{{\"error\":\"input `{}` does not exist\"}}",
+                             escape(input)));
+            return
+        }
+    }
+
+    let job = dir.join(format!("job-{}", process::id()));
+    if fs::create_dir_all(&job).is_err() {
+        fail("failed to create the job directory");
+    }
+    let mut crate_name = "?".to_string();
+    for i in 0..args.len() {
+        if args[i] == "--out-dir" && i + 1 < args.len() {
+            args[i + 1] = job.display().to_string();
+        }
+        if args[i] == "--crate-name" && i + 1 < args.len() {
+            crate_name = args[i + 1].clone();
+        }
+    }
+    log(&dir, "log", &crate_name);
+    for input in inputs {
+        log(&dir, "inputs", &format!("{} {}", crate_name, input.as_str().unwrap()));
+    }
+
+    let mut cmd = Command::new(program);
+    cmd.args(&args);
+    if let Some(cwd) = request.get("cwd").and_then(Json::as_str) {
+        cmd.current_dir(cwd);
+    }
+    if let Some(&Json::Object(ref env)) = request.get("env") {
+        for &(ref key, ref value) in env {
+            match value.as_str() {
+                Some(value) => { cmd.env(key, value); }
+                None => { cmd.env_remove(key); }
+            }
+        }
+    }
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(e) => {
+            respond(&format!("{{\"error\":\"failed to run `{}`: {}\"}}",
+                             escape(program), escape(&e.to_string())));
+            return
+        }
+    };
+
+    let outputs = fs::read_dir(&job).unwrap_or_else(|_| fail("failed to read outputs"))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| format!("\"{}\"", escape(&e.path().display().to_string())))
+        .collect::<Vec<_>>();
+    respond(&format!("{{\"status\":{},\"stdout\":\"{}\",\"stderr\":\"{}\",\"outputs\":[{}]}}",
+                     output.status.code().unwrap_or(-1),
+                     escape(&String::from_utf8_lossy(&output.stdout)),
+                     escape(&String::from_utf8_lossy(&output.stderr)),
+                     outputs.join(",")));
+}
+
+fn fail(msg: &str) -> ! {
+    let _ = writeln!(io::stderr(), "error: {}", msg);
+    process::exit(1)
+}
+
+fn respond(json: &str) {
+    println!("{}", json);
+}
+
+fn log(dir: &Path, file: &str, line: &str) {
+    let logged = OpenOptions::new().append(true).create(true).open(dir.join(file))
+        .and_then(|mut f: File| writeln!(f, "{}", line));
+    if logged.is_err() {
+        fail("failed to write the log");
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref fields) => {
+                fields.iter().find(|f| f.0 == key).map(|f| &f.1)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&Vec<Json>> {
+        match *self {
+            Json::Array(ref a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Just enough of a JSON parser to read the requests baler sends.
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.chars.peek().cloned() {
+            Some('{') => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                loop {
+                    self.whitespace();
+                    if self.eat('}') {
+                        return Ok(Json::Object(fields))
+                    }
+                    if !fields.is_empty() {
+                        self.expect(',')?;
+                        self.whitespace();
+                    }
+                    self.expect('"')?;
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(':')?;
+                    let value = self.value()?;
+                    fields.push((key, value));
+                }
+            }
+            Some('[') => {
+                self.chars.next();
+                let mut items = Vec::new();
+                loop {
+                    self.whitespace();
+                    if self.eat(']') {
+                        return Ok(Json::Array(items))
+                    }
+                    if !items.is_empty() {
+                        self.expect(',')?;
+                    }
+                    items.push(self.value()?);
+                }
+            }
+            Some('"') => {
+                self.chars.next();
+                Ok(Json::String(self.string()?))
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !c.is_alphanumeric() && c != '-' && c != '.' && c != '+' {
+                        break
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                match &word[..] {
+                    "null" => Ok(Json::Null),
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    n => n.parse().map(Json::Number)
+                          .map_err(|_| format!("unexpected `{}`", n)),
+                }
+            }
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    /// Parses the rest of a string whose opening quote has been consumed.
+    fn string(&mut self) -> Result<String, String> {
+        let mut ret = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(ret),
+                Some('\\') => {
+                    match self.chars.next() {
+                        Some('n') => ret.push('\n'),
+                        Some('r') => ret.push('\r'),
+                        Some('t') => ret.push('\t'),
+                        Some('b') => ret.push('\u{8}'),
+                        Some('f') => ret.push('\u{c}'),
+                        Some('u') => {
+                            let hex = self.chars.by_ref().take(4).collect::<String>();
+                            let c = u32::from_str_radix(&hex, 16).ok()
+                                .and_then(::std::char::from_u32)
+                                .unwrap_or('\u{fffd}');
+                            ret.push(c);
+                        }
+                        Some(c) => ret.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                Some(c) => ret.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self.chars.peek().map_or(false, |c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", c))
+        }
+    }
+}
@@ -8,11 +8,13 @@ extern crate git2;
 extern crate hamcrest;
 extern crate hex;
 extern crate libc;
+extern crate ring;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
 extern crate tar;
 extern crate tempdir;
+extern crate untrusted;
 extern crate url;
 #[cfg(windows)] extern crate kernel32;
 #[cfg(windows)] extern crate winapi;
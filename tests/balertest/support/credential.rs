@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use hamcrest::assert_that;
+
+use support::{execs, project};
+
+/// Builds the reference file-backed credential process and returns the path
+/// to its binary.
+///
+/// The binary takes the path of the file to keep tokens in as its only
+/// argument, so tests typically configure it as
+/// `registry.credential-process = ["<binary>", "<path>"]`.
+pub fn provider() -> PathBuf {
+    let p = project("baler-credential-file")
+        .file("Baler.toml", r#"
+            [package]
+            name = "baler-credential-file"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", include_str!("../credential-file.rs"));
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    p.bin("baler-credential-file")
+}
@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+use hamcrest::assert_that;
+
+use support::{execs, project};
+
+/// Builds the reference executor which runs jobs in a scratch directory and
+/// returns the path to its binary.
+///
+/// The binary takes the scratch directory as its only argument, so tests
+/// typically configure it as `build.executor = ["<binary>", "<dir>"]`.
+pub fn scratch() -> PathBuf {
+    let p = project("baler-executor-scratch")
+        .file("Baler.toml", r#"
+            [package]
+            name = "baler-executor-scratch"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", include_str!("../executor-scratch.rs"));
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    p.bin("baler-executor-scratch")
+}
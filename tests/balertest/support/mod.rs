@@ -30,6 +30,9 @@ macro_rules! t {
 pub mod paths;
 pub mod git;
 pub mod registry;
+pub mod credential;
+pub mod executor;
+pub mod cross_compile;
 
 /*
  *
@@ -834,9 +837,14 @@ fn substitute_macros(input: &str) -> String {
         ("[WARNING]",     "warning:"),
         ("[DOCUMENTING]", " Documenting"),
         ("[FRESH]",       "       Fresh"),
+        ("[DIRTY]",       "       Dirty"),
+        ("[RESTORED]",    "    Restored"),
         ("[UPDATING]",    "    Updating"),
         ("[ADDING]",      "      Adding"),
         ("[REMOVING]",    "    Removing"),
+        ("[REMOVED]",     "     Removed"),
+        ("[FIXED]",       "       Fixed"),
+        ("[SUMMARY]",     "     Summary"),
         ("[DOCTEST]",     "   Doc-tests"),
         ("[PACKAGING]",   "   Packaging"),
         ("[DOWNLOADING]", " Downloading"),
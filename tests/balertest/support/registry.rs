@@ -7,11 +7,13 @@ use flate2::Compression::Default;
 use flate2::write::GzEncoder;
 use git2;
 use hex::ToHex;
-use tar::{Builder, Header};
+use ring::signature::Ed25519KeyPair;
+use tar::{Builder, Header, EntryType};
+use untrusted;
 use url::Url;
 
 use support::paths;
-use support::git::repo;
+use support::git::{self, repo};
 use baler::util::Sha256;
 
 pub fn registry_path() -> PathBuf { paths::root().join("registry") }
@@ -24,9 +26,26 @@ pub struct Package {
     vers: String,
     deps: Vec<Dependency>,
     files: Vec<(String, String)>,
+    raw: Vec<RawEntry>,
     yanked: bool,
     features: HashMap<String, Vec<String>>,
     local: bool,
+    signing_key: Option<SigningKey>,
+}
+
+/// An ed25519 key pair used to sign packages published to the test registry.
+#[derive(Clone)]
+pub struct SigningKey {
+    seed: [u8; 32],
+}
+
+/// An archive entry written verbatim, without the usual `<name>-<version>`
+/// prefix or any sanity checks, for testing malicious archives.
+struct RawEntry {
+    kind: EntryType,
+    path: String,
+    link: String,
+    contents: String,
 }
 
 struct Dependency {
@@ -64,6 +83,18 @@ pub fn init() {
     fs::create_dir_all(dl_path().join("api/v1/crates")).unwrap();
 }
 
+/// Points the `dl` key of the test registry's `config.json` at `dl`, for
+/// serving downloads from somewhere other than the file system.
+pub fn set_dl(dl: &str) {
+    init();
+    t!(t!(File::create(registry_path().join("config.json"))).write_all(format!(r#"
+        {{"dl":"{0}","api":"{1}"}}
+    "#, dl, dl_url()).as_bytes()));
+    let repo = t!(git2::Repository::open(&registry_path()));
+    git::add(&repo);
+    git::commit(&repo);
+}
+
 impl Package {
     pub fn new(name: &str, vers: &str) -> Package {
         init();
@@ -72,9 +103,11 @@ impl Package {
             vers: vers.to_string(),
             deps: Vec::new(),
             files: Vec::new(),
+            raw: Vec::new(),
             yanked: false,
             features: HashMap::new(),
             local: false,
+            signing_key: None,
         }
     }
 
@@ -88,6 +121,36 @@ impl Package {
         self
     }
 
+    pub fn raw_file(&mut self, path: &str, contents: &str) -> &mut Package {
+        self.raw_entry(EntryType::Regular, path, "", contents)
+    }
+
+    pub fn symlink(&mut self, path: &str, target: &str) -> &mut Package {
+        self.raw_entry(EntryType::Symlink, path, target, "")
+    }
+
+    pub fn hard_link(&mut self, path: &str, target: &str) -> &mut Package {
+        self.raw_entry(EntryType::Link, path, target, "")
+    }
+
+    pub fn device(&mut self, path: &str) -> &mut Package {
+        self.raw_entry(EntryType::Char, path, "", "")
+    }
+
+    fn raw_entry(&mut self,
+                 kind: EntryType,
+                 path: &str,
+                 link: &str,
+                 contents: &str) -> &mut Package {
+        self.raw.push(RawEntry {
+            kind: kind,
+            path: path.to_string(),
+            link: link.to_string(),
+            contents: contents.to_string(),
+        });
+        self
+    }
+
     pub fn dep(&mut self, name: &str, vers: &str) -> &mut Package {
         self.full_dep(name, vers, None, "normal", &[])
     }
@@ -131,6 +194,11 @@ impl Package {
         self
     }
 
+    pub fn sign(&mut self, key: &SigningKey) -> &mut Package {
+        self.signing_key = Some(key.clone());
+        self
+    }
+
     pub fn publish(&self) -> String {
         self.make_archive();
 
@@ -146,11 +214,9 @@ impl Package {
                 "kind": dep.kind,
             })
         }).collect::<Vec<_>>();
-        let cksum = {
-            let mut c = Vec::new();
-            t!(t!(File::open(&self.archive_dst())).read_to_end(&mut c));
-            cksum(&c)
-        };
+        let mut archive = Vec::new();
+        t!(t!(File::open(&self.archive_dst())).read_to_end(&mut archive));
+        let cksum = cksum(&archive);
         let line = json!({
             "name": self.name,
             "vers": self.vers,
@@ -179,11 +245,25 @@ impl Package {
         t!(t!(File::create(&dst))
                   .write_all((prev + &line[..] + "\n").as_bytes()));
 
+        // Write the detached signatures next to the index file
+        let sig_file = format!("{}.sig", file);
+        if let Some(ref key) = self.signing_key {
+            let dst = dst.with_file_name(format!("{}.sig", self.name));
+            let mut prev = String::new();
+            let _ = File::open(&dst).and_then(|mut f| f.read_to_string(&mut prev));
+            let sigs = format!("{} {} {}\n", self.vers, key.sign(line.as_bytes()),
+                               key.sign(&archive));
+            t!(t!(File::create(&dst)).write_all((prev + &sigs).as_bytes()));
+        }
+
         // Add the new file to the index
         if !self.local {
             let repo = t!(git2::Repository::open(&registry_path()));
             let mut index = t!(repo.index());
             t!(index.add_path(Path::new(&file)));
+            if self.signing_key.is_some() {
+                t!(index.add_path(Path::new(&sig_file)));
+            }
             t!(index.write());
             let id = t!(index.write_tree());
 
@@ -235,6 +315,9 @@ impl Package {
                 self.append(&mut a, name, contents);
             }
         }
+        for entry in self.raw.iter() {
+            self.append_raw(&mut a, entry);
+        }
     }
 
     fn append<W: Write>(&self, ar: &mut Builder<W>, file: &str, contents: &str) {
@@ -246,6 +329,23 @@ impl Package {
         t!(ar.append(&header, contents.as_bytes()));
     }
 
+    fn append_raw<W: Write>(&self, ar: &mut Builder<W>, entry: &RawEntry) {
+        // `Header::set_path` refuses the paths we want to test with, so the
+        // names are copied into the header by hand.
+        let mut header = Header::new_old();
+        header.set_entry_type(entry.kind);
+        header.set_mode(0o644);
+        header.set_size(entry.contents.len() as u64);
+        {
+            let old = header.as_old_mut();
+            old.name[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+            old.linkname[..entry.link.len()].copy_from_slice(entry.link.as_bytes());
+        }
+        header.set_cksum();
+
+        t!(ar.append(&header, entry.contents.as_bytes()));
+    }
+
     pub fn archive_dst(&self) -> PathBuf {
         if self.local {
             registry_path().join(format!("{}-{}.crate", self.name,
@@ -256,6 +356,27 @@ impl Package {
     }
 }
 
+impl SigningKey {
+    /// Creates a key pair deterministically derived from `seed`.
+    pub fn new(seed: u8) -> SigningKey {
+        SigningKey { seed: [seed; 32] }
+    }
+
+    fn pair(&self) -> Ed25519KeyPair {
+        t!(Ed25519KeyPair::from_seed_unchecked(untrusted::Input::from(&self.seed)))
+    }
+
+    /// Returns the hex-encoded public key, as used in `signing-keys`.
+    pub fn public_key(&self) -> String {
+        self.pair().public_key_bytes().to_hex()
+    }
+
+    /// Returns the hex-encoded signature of `msg`.
+    pub fn sign(&self, msg: &[u8]) -> String {
+        self.pair().sign(msg).as_ref().to_hex()
+    }
+}
+
 pub fn cksum(s: &[u8]) -> String {
     let mut sha = Sha256::new();
     sha.update(s);
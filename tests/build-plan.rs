@@ -0,0 +1,169 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use balertest::support::{project, execs, basic_bin_manifest, main_file};
+use hamcrest::{assert_that, existing_dir, existing_file, is_not};
+
+#[test]
+fn baler_build_plan_simple() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+    p.build();
+
+    assert_that(p.baler("build").arg("--build-plan"),
+                execs().with_status(0).with_stderr("").with_json(r#"
+    {
+        "invocations": [
+            {
+                "package_name": "foo",
+                "package_version": "0.5.0",
+                "target_kind": ["bin"],
+                "kind": "host",
+                "deps": [],
+                "outputs": ["[..][/]foo[/]target[/]debug[/]deps[/]foo-[..]"],
+                "links": "{...}",
+                "program": "rustc",
+                "args": "{...}",
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": false
+            }
+        ]
+    }
+    "#));
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+    assert_that(&p.root().join("target"), is_not(existing_dir()));
+}
+
+#[test]
+fn baler_build_plan_single_dep() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.5.0"
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate bar;
+            pub fn foo() { bar::bar(); }
+        "#)
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+    p.build();
+
+    assert_that(p.baler("build").arg("--build-plan"),
+                execs().with_status(0).with_json(r#"
+    {
+        "invocations": [
+            {
+                "package_name": "bar",
+                "package_version": "0.0.1",
+                "target_kind": ["lib"],
+                "kind": "host",
+                "deps": [],
+                "outputs": ["[..][/]foo[/]target[/]debug[/]deps[/]libbar-[..].rlib"],
+                "links": {},
+                "program": "rustc",
+                "args": "{...}",
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": false
+            },
+            {
+                "package_name": "foo",
+                "package_version": "0.5.0",
+                "target_kind": ["lib"],
+                "kind": "host",
+                "deps": [0],
+                "outputs": "{...}",
+                "links": "{...}",
+                "program": "rustc",
+                "args": "{...}",
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": false
+            }
+        ]
+    }
+    "#));
+}
+
+#[test]
+fn baler_build_plan_build_script() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.5.0"
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-cfg=foo");
+            }
+        "#);
+    p.build();
+
+    assert_that(p.baler("build").arg("--build-plan"),
+                execs().with_status(0).with_json(r#"
+    {
+        "invocations": [
+            {
+                "package_name": "foo",
+                "package_version": "0.5.0",
+                "target_kind": ["custom-build"],
+                "kind": "host",
+                "deps": [],
+                "outputs": ["[..][/]foo[/]target[/]debug[/]build[/]foo-[..][/]build_script_build-[..]"],
+                "links": "{...}",
+                "program": "rustc",
+                "args": "{...}",
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": false
+            },
+            {
+                "package_name": "foo",
+                "package_version": "0.5.0",
+                "target_kind": ["custom-build"],
+                "kind": "host",
+                "deps": [0],
+                "outputs": [],
+                "links": {},
+                "program": "[..][/]foo[/]target[/]debug[/]build[/]foo-[..][/]build-script-build",
+                "args": [],
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": false
+            },
+            {
+                "package_name": "foo",
+                "package_version": "0.5.0",
+                "target_kind": ["lib"],
+                "kind": "host",
+                "deps": [1],
+                "outputs": "{...}",
+                "links": "{...}",
+                "program": "rustc",
+                "args": "{...}",
+                "env": "{...}",
+                "cwd": "[..][/]foo",
+                "build_script_dependent": true
+            }
+        ]
+    }
+    "#));
+    assert_that(&p.root().join("target"), is_not(existing_dir()));
+}
@@ -406,8 +406,10 @@ fn only_rerun_build_script() {
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0)
                        .with_stderr("\
+[DIRTY] foo v0.5.0 (file://[..]): its sources have changed: [..]
 [COMPILING] foo v0.5.0 (file://[..])
 [RUNNING] `[..][/]build-script-build`
+[DIRTY] foo v0.5.0 (file://[..]): its build script was run again
 [RUNNING] `rustc --crate-name foo [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1790,7 +1792,9 @@ fn adding_an_override_invalidates() {
 
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] foo v0.5.0 ([..]): its sources have changed: [..]
 [COMPILING] foo v0.5.0 ([..]
+[DIRTY] foo v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] -L native=bar`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1829,7 +1833,9 @@ fn changing_an_override_invalidates() {
 
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] foo v0.5.0 ([..]): its sources have changed: [..]
 [COMPILING] foo v0.5.0 ([..]
+[DIRTY] foo v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] -L native=bar`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1942,8 +1948,10 @@ fn rebuild_only_on_explicit_paths() {
     println!("run without");
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] a v0.5.0 ([..]): the file `[..]` [..]
 [COMPILING] a v0.5.0 ([..])
 [RUNNING] `[..][/]build-script-build`
+[DIRTY] a v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] src[/]lib.rs [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1956,8 +1964,10 @@ fn rebuild_only_on_explicit_paths() {
     println!("run with");
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] a v0.5.0 ([..]): the file `[..]` [..]
 [COMPILING] a v0.5.0 ([..])
 [RUNNING] `[..][/]build-script-build`
+[DIRTY] a v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] src[/]lib.rs [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1985,8 +1995,10 @@ fn rebuild_only_on_explicit_paths() {
     File::create(p.root().join("foo")).unwrap();
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] a v0.5.0 ([..]): the file `[..]` [..]
 [COMPILING] a v0.5.0 ([..])
 [RUNNING] `[..][/]build-script-build`
+[DIRTY] a v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] src[/]lib.rs [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -1996,8 +2008,10 @@ fn rebuild_only_on_explicit_paths() {
     fs::remove_file(p.root().join("bar")).unwrap();
     assert_that(p.baler("build").arg("-v"),
                 execs().with_status(0).with_stderr("\
+[DIRTY] a v0.5.0 ([..]): the file `[..]` [..]
 [COMPILING] a v0.5.0 ([..])
 [RUNNING] `[..][/]build-script-build`
+[DIRTY] a v0.5.0 ([..]): its build script was run again
 [RUNNING] `rustc [..] src[/]lib.rs [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -2616,3 +2630,300 @@ fn deterministic_rustc_dependency_flags() {
 -L native=test3 -L native=test4`
 "));
 }
+
+#[test]
+fn rustc_link_arg_bins() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-link-arg-bins=--this-is-a-bogus-flag");
+            }
+        "#);
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name foo src[/]main.rs [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_does_not_contain("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs [..]link-arg[..]`"));
+}
+
+#[test]
+fn rustc_link_arg_not_passed_to_dependents() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.a]
+            path = "a"
+        "#)
+        .file("src/main.rs", "extern crate a; fn main() {}")
+        .file("a/Baler.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("a/build.rs", r#"
+            fn main() {
+                println!("baler:rustc-link-arg=--this-is-a-bogus-flag");
+            }
+        "#);
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[..]link-arg[..]"));
+}
+
+#[test]
+fn rustc_link_arg_passed_to_tests_examples_and_benches() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [lib]
+            bench = false
+        "#)
+        .file("src/lib.rs", "")
+        .file("tests/t.rs", "")
+        .file("examples/ex.rs", "fn main() {}")
+        .file("benches/b.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-link-arg=--this-is-a-bogus-flag");
+            }
+        "#);
+
+    assert_that(p.baler_process("test").arg("-v").arg("--no-run").arg("--keep-going"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs [..]--test [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name t tests[/]t.rs [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name ex examples[/]ex.rs [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_does_not_contain("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs --crate-type lib [..]link-arg[..]`"));
+
+    assert_that(p.baler("bench").arg("-v").arg("--no-run"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name b benches[/]b.rs [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_does_not_contain("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs --crate-type lib [..]link-arg[..]`"));
+}
+
+#[test]
+fn rustc_cdylib_link_arg_only_passed_to_cdylibs() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-cdylib-link-arg=--this-is-a-bogus-flag");
+            }
+        "#);
+
+    assert_that(p.baler_process("build").arg("-v").arg("--keep-going"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs --crate-type cdylib [..]\
+-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_does_not_contain("\
+[RUNNING] `rustc --crate-name foo src[/]main.rs [..]link-arg[..]`"));
+}
+
+#[test]
+fn rustc_check_cfg() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-check-cfg=cfg(foo)");
+            }
+        "#);
+
+    // Only the flag is checked here, as older compilers don't know about it.
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_stderr_contains("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs [..]--check-cfg cfg(foo)[..]`"));
+}
+
+#[test]
+fn linker_args_in_build_script_message() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-cdylib-link-arg=--this-is-a-bogus-flag");
+            }
+        "#);
+
+    assert_that(p.baler_process("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains("\
+{\"cfgs\":[],\"check_cfgs\":[],\"env\":[],\"linked_libs\":[],\"linked_paths\":[],\
+\"linker_args\":[[\"cdylib\",\"--this-is-a-bogus-flag\"]],\"out_dir\":\"[..]\",\
+\"package_id\":\"foo 0.5.0 ([..])\",\
+\"reason\":\"build-script-executed\"}"));
+}
+
+#[test]
+fn metadata_directive() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [dependencies.a]
+            path = "a"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            use std::env;
+            fn main() {
+                assert_eq!(env::var("DEP_FOO_ROOT").unwrap(), "/path/to/foo");
+                assert_eq!(env::var("DEP_FOO_RUSTC_CFG").unwrap(), "x=y");
+            }
+        "#)
+        .file("a/Baler.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            links = "foo"
+            build = "build.rs"
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("a/build.rs", r#"
+            fn main() {
+                println!("baler:metadata=root=/path/to/foo");
+                println!("baler:metadata=rustc-cfg=x=y");
+            }
+        "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0)
+                       .with_stderr("\
+[COMPILING] a v0.5.0 ([..])
+[COMPILING] foo v0.5.0 ([..])
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn unknown_directive_is_metadata_with_warning() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [dependencies.a]
+            path = "a"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            use std::env;
+            fn main() {
+                assert_eq!(env::var("DEP_FOO_ROOT").unwrap(), "/path/to/foo");
+            }
+        "#)
+        .file("a/Baler.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            links = "foo"
+            build = "build.rs"
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("a/build.rs", r#"
+            fn main() {
+                println!("baler:root=/path/to/foo");
+            }
+        "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] unknown directive `baler:root` in build script of `a` is treated as \
+metadata, print `baler:metadata=root=/path/to/foo` instead"));
+}
+
+#[test]
+fn overrides_with_linker_args_and_metadata() {
+    let target = rustc_host();
+
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            links = "foo"
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file("build.rs", "not valid rust code")
+        .file(".baler/config", &format!(r#"
+            [target.{}.foo]
+            rustc-link-arg-bins = ["--this-is-a-bogus-flag"]
+            metadata = {{ root = "/path/to/foo" }}
+        "#, target));
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc --crate-name foo src[/]main.rs [..]-C link-arg=--this-is-a-bogus-flag[..]`")
+                       .with_stderr_does_not_contain("\
+[RUNNING] `rustc --crate-name foo src[/]lib.rs [..]link-arg[..]`"));
+}
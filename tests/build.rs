@@ -12,7 +12,7 @@ use baler::util::process;
 use balertest::{is_nightly, rustc_host, sleep_ms};
 use balertest::support::paths::{CargoPathExt,root};
 use balertest::support::{ProjectBuilder};
-use balertest::support::{project, execs, main_file, basic_bin_manifest};
+use balertest::support::{project, execs, main_file, basic_bin_manifest, basic_lib_manifest};
 use balertest::support::registry::Package;
 use hamcrest::{assert_that, existing_file, is_not};
 use tempdir::TempDir;
@@ -2674,6 +2674,7 @@ fn compiler_json_error_format() {
 
     {
         "reason":"compiler-artifact",
+        "target_triple":"[..]",
         "profile": {
             "debug_assertions": true,
             "debuginfo": 2,
@@ -2690,6 +2691,7 @@ fn compiler_json_error_format() {
             "src_path":"[..]lib.rs"
         },
         "filenames":["[..].rlib"],
+        "executable": null,
         "fresh": false
     }
 
@@ -2707,6 +2709,7 @@ fn compiler_json_error_format() {
 
     {
         "reason":"compiler-artifact",
+        "target_triple":"[..]",
         "package_id":"foo 0.5.0 ([..])",
         "target":{
             "kind":["bin"],
@@ -2723,8 +2726,14 @@ fn compiler_json_error_format() {
         },
         "features": [],
         "filenames": ["[..]"],
+        "executable": "[..]foo[EXE]",
         "fresh": false
     }
+
+    {
+        "reason":"build-finished",
+        "success":true
+    }
 "#));
 
     // With fresh build, we should repeat the artifacts,
@@ -2734,6 +2743,7 @@ fn compiler_json_error_format() {
                 execs().with_status(0).with_json(r#"
     {
         "reason":"compiler-artifact",
+        "target_triple":"[..]",
         "profile": {
             "debug_assertions": true,
             "debuginfo": 2,
@@ -2750,11 +2760,13 @@ fn compiler_json_error_format() {
             "src_path":"[..]lib.rs"
         },
         "filenames":["[..].rlib"],
+        "executable": null,
         "fresh": true
     }
 
     {
         "reason":"compiler-artifact",
+        "target_triple":"[..]",
         "package_id":"foo 0.5.0 ([..])",
         "target":{
             "kind":["bin"],
@@ -2771,8 +2783,14 @@ fn compiler_json_error_format() {
         },
         "features": [],
         "filenames": ["[..]"],
+        "executable": "[..]foo[EXE]",
         "fresh": true
     }
+
+    {
+        "reason":"build-finished",
+        "success":true
+    }
 "#));
 }
 
@@ -2785,7 +2803,13 @@ fn wrong_message_format_option() {
     assert_that(p.baler_process("build").arg("--message-format").arg("XML"),
                 execs().with_status(1)
                        .with_stderr_contains(
-r#"[ERROR] Could not match 'xml' with any of the allowed variants: ["Human", "Json"]"#));
+r#"[ERROR] Could not match 'xml' with any of the allowed variants: ["human", "json", "short", "json-render-diagnostics"]"#));
+
+    // Every name the error lists is accepted.
+    for format in ["human", "json", "short", "json-render-diagnostics"].iter() {
+        assert_that(p.baler("build").arg("--message-format").arg(format),
+                    execs().with_status(0));
+    }
 }
 
 #[test]
@@ -2812,6 +2836,7 @@ fn message_format_json_forward_stderr() {
 
     {
         "reason":"compiler-artifact",
+        "target_triple":"[..]",
         "package_id":"foo 0.5.0 ([..])",
         "target":{
             "kind":["bin"],
@@ -2828,11 +2853,56 @@ fn message_format_json_forward_stderr() {
         },
         "features":[],
         "filenames":["[..]"],
+        "executable":"[..]foo[EXE]",
         "fresh": false
     }
+
+    {
+        "reason":"build-finished",
+        "success":true
+    }
 "#));
 }
 
+#[test]
+fn message_format_short() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() { let unused = 0; }");
+
+    assert_that(p.baler_process("build").arg("--message-format").arg("short"),
+                execs().with_status(0)
+                       .with_stderr_contains("src[/]main.rs:1:[..]: warning: unused variable[..]")
+                       .with_stderr_does_not_contain("[..]-->[..]"));
+}
+
+#[test]
+fn message_format_json_render_diagnostics() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() { let unused = 0; }");
+
+    assert_that(p.baler_process("build")
+                 .arg("--message-format").arg("json-render-diagnostics"),
+                execs().with_status(0)
+                       .with_stderr_contains("[..]unused variable[..]")
+                       .with_stdout_does_not_contain("[..]\"reason\":\"compiler-message\"[..]")
+                       .with_stdout_contains("\
+{[..]\"executable\":\"[..]foo[EXE]\",[..]\"reason\":\"compiler-artifact\"[..]}")
+                       .with_stdout_contains("{\"reason\":\"build-finished\",\"success\":true}"));
+}
+
+#[test]
+fn build_finished_after_failure() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() { let x: u32 = \"\"; }");
+
+    assert_that(p.baler_process("build").arg("--message-format=json"),
+                execs().with_status(101)
+                       .with_stdout_contains("{\"reason\":\"build-finished\",\"success\":false}"));
+}
+
 #[test]
 fn no_warn_about_package_metadata() {
     let p = project("foo")
@@ -3438,3 +3508,160 @@ fn inferred_path_in_src_bin_foo() {
     assert_that(p.baler_process("build"), execs().with_status(0));
     assert_that(&p.bin("bar"), existing_file());
 }
+
+fn keep_going_project() -> ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            a = { path = "a" }
+            b = { path = "b" }
+            c = { path = "c" }
+            d = { path = "d" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("a/Baler.toml", &basic_lib_manifest("a"))
+        .file("a/src/lib.rs", "invalid rust code!")
+        .file("b/Baler.toml", &basic_lib_manifest("b"))
+        .file("b/src/lib.rs", "invalid rust code!")
+        .file("c/Baler.toml", &basic_lib_manifest("c"))
+        .file("c/src/lib.rs", "")
+        .file("d/Baler.toml", r#"
+            [package]
+            name = "d"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            a = { path = "../a" }
+        "#)
+        .file("d/src/lib.rs", "")
+}
+
+#[test]
+fn keep_going_reports_every_failure() {
+    let p = keep_going_project();
+
+    assert_that(p.baler_process("build").arg("--keep-going"),
+                execs().with_status(101)
+                       .with_stderr_contains("[COMPILING] c v0.5.0 ([..])")
+                       .with_stderr_does_not_contain("[COMPILING] d v0.5.0 ([..])")
+                       .with_stderr_does_not_contain("[COMPILING] foo v0.0.1 ([..])")
+                       .with_stderr_contains("[ERROR] 2 units failed to build:")
+                       .with_stderr_contains("  Could not compile `a`.")
+                       .with_stderr_contains("  Could not compile `b`."));
+}
+
+#[test]
+fn keep_going_single_failure() {
+    let p = keep_going_project()
+        .file("b/src/lib.rs", "");
+
+    assert_that(p.baler_process("check").arg("--keep-going"),
+                execs().with_status(101)
+                       .with_stderr_contains("[COMPILING] b v0.5.0 ([..])")
+                       .with_stderr_contains("[COMPILING] c v0.5.0 ([..])")
+                       .with_stderr_does_not_contain("[COMPILING] d v0.5.0 ([..])")
+                       .with_stderr_contains("[ERROR] Could not compile `a`."));
+}
+
+#[test]
+fn target_triple_in_artifact_messages() {
+    let target = rustc_host();
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.baler_process("build").arg("--target").arg(&target)
+                 .arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains(&format!("[..]\"target_triple\":\"{}\"[..]",
+                                                      target)));
+    assert_that(&p.root().join("target").join(&target).join("debug")
+                 .join(&format!("foo{}", env::consts::EXE_SUFFIX)),
+                existing_file());
+}
+
+#[test]
+fn build_target_list_in_config() {
+    let target = rustc_host();
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .file(".baler/config", &format!(r#"
+            [build]
+            target = ["{}"]
+        "#, target));
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    assert_that(&p.root().join("target").join(&target).join("debug")
+                 .join(&format!("foo{}", env::consts::EXE_SUFFIX)),
+                existing_file());
+}
+
+#[test]
+fn every_requested_target_is_used() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.baler_process("build")
+                 .arg("--target").arg(&rustc_host())
+                 .arg("--target").arg("no-such-target"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] failed to run `rustc` to learn about target-specific information"));
+}
+
+#[test]
+fn out_dir_gets_final_artifacts() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "extern crate bar; pub fn foo() { bar::bar() }")
+        .file("src/main.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("bar/Baler.toml", &basic_lib_manifest("bar"))
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.baler_process("build").arg("--out-dir").arg("out"),
+                execs().with_status(0));
+    let out = p.root().join("out");
+    assert_that(&out.join(&format!("foo{}", env::consts::EXE_SUFFIX)), existing_file());
+    assert_that(&out.join("libfoo.rlib"), existing_file());
+    assert_that(&out.join("libbar.rlib"), is_not(existing_file()));
+
+    // A fresh build replaces what's already there.
+    assert_that(p.baler("build").arg("--out-dir").arg("out"),
+                execs().with_status(0));
+    assert_that(&out.join(&format!("foo{}", env::consts::EXE_SUFFIX)), existing_file());
+    let names = fs::read_dir(&out).unwrap().map(|e| {
+        e.unwrap().file_name().into_string().unwrap()
+    }).collect::<Vec<_>>();
+    assert!(names.iter().all(|n| !n.ends_with(".tmp")), "{:?}", names);
+}
+
+#[test]
+fn out_dir_in_config() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .file(".baler/config", r#"
+            [build]
+            out-dir = "dist"
+        "#);
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    assert_that(&p.root().join("dist").join(&format!("foo{}", env::consts::EXE_SUFFIX)),
+                existing_file());
+}
@@ -1,7 +1,10 @@
 extern crate balertest;
 extern crate hamcrest;
 
-use balertest::is_nightly;
+use std::fs::File;
+use std::io::prelude::*;
+
+use balertest::{is_nightly, sleep_ms};
 use balertest::support::{execs, project};
 use balertest::support::registry::Package;
 use hamcrest::assert_that;
@@ -393,3 +396,118 @@ fn check_all() {
         .with_stderr_contains("[..] --crate-name b b[/]src[/]main.rs [..]")
         );
 }
+
+#[test]
+fn replay_warnings_of_fresh_units() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() { let unused = 1; }")
+        .file(".baler/config", r#"
+            [build]
+            replay-warnings = true
+        "#);
+
+    assert_that(foo.baler_process("check"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] foo v0.0.1 ([..])")
+                       .with_stderr_contains("[..]unused variable[..]"));
+
+    assert_that(foo.baler("check"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[COMPILING] foo v0.0.1 ([..])")
+                       .with_stderr_contains("[..]unused variable[..]"));
+
+    assert_that(foo.baler("check").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains("\
+{\"message\":{[..]unused variable[..]},\"package_id\":\"foo 0.0.1 [..]\",\
+\"reason\":\"compiler-message\",\"target\":{[..]}}"));
+}
+
+#[test]
+fn replayed_warnings_have_no_colors() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() { let unused = 1; }")
+        .file(".baler/config", r#"
+            [build]
+            replay-warnings = true
+        "#);
+
+    assert_that(foo.baler_process("check").arg("--color").arg("always"),
+                execs().with_status(0)
+                       .with_stderr_contains("[..]\x1b[..]unused variable[..]"));
+
+    assert_that(foo.baler("check").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains("[..]unused variable[..]")
+                       .with_stdout_does_not_contain("[..]\\u001b[..]"));
+
+    assert_that(foo.baler("check"),
+                execs().with_status(0)
+                       .with_stderr_contains("[..]unused variable[..]")
+                       .with_stderr_does_not_contain("[..]\x1b[..]"));
+}
+
+#[test]
+fn warnings_not_replayed_by_default() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() { let unused = 1; }");
+
+    assert_that(foo.baler_process("check"),
+                execs().with_status(0).with_stderr_contains("[..]unused variable[..]"));
+    assert_that(foo.baler("check"),
+                execs().with_status(0).with_stderr_does_not_contain("[..]unused variable[..]"));
+}
+
+#[test]
+fn fixed_warnings_not_replayed_after_rebuild_without_replay() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() { let unused = 1; }")
+        .file(".baler/config", r#"
+            [build]
+            replay-warnings = true
+        "#);
+
+    assert_that(foo.baler_process("check"),
+                execs().with_status(0).with_stderr_contains("[..]unused variable[..]"));
+
+    sleep_ms(1000);
+    File::create(&foo.root().join(".baler/config")).unwrap()
+         .write_all(b"[build]\nreplay-warnings = false\n").unwrap();
+    File::create(&foo.root().join("src/main.rs")).unwrap()
+         .write_all(b"fn main() {}").unwrap();
+    assert_that(foo.baler("check"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] foo v0.0.1 ([..])")
+                       .with_stderr_does_not_contain("[..]unused variable[..]"));
+
+    File::create(&foo.root().join(".baler/config")).unwrap()
+         .write_all(b"[build]\nreplay-warnings = true\n").unwrap();
+    assert_that(foo.baler("check"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[COMPILING] foo v0.0.1 ([..])")
+                       .with_stderr_does_not_contain("[..]unused variable[..]"));
+}
@@ -2,6 +2,7 @@ extern crate hamcrest;
 extern crate balertest;
 
 use std::env;
+use std::fs;
 
 use balertest::support::{git, project, execs, main_file, basic_bin_manifest};
 use balertest::support::registry::Package;
@@ -234,3 +235,109 @@ fn registry() {
     assert_that(p.baler("build"),
                 execs().with_status(0));
 }
+
+#[test]
+fn stale_artifacts() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    p.change_file("bar/Baler.toml", r#"
+        [package]
+        name = "bar"
+        version = "0.0.2"
+        authors = []
+    "#);
+    assert_that(p.baler("build"), execs().with_status(0));
+
+    let bar_rlibs = || {
+        fs::read_dir(p.root().join("target/debug/deps")).unwrap().filter(|e| {
+            let name = e.as_ref().unwrap().file_name().into_string().unwrap();
+            name.starts_with("libbar-") && name.ends_with(".rlib")
+        }).count()
+    };
+    assert_eq!(bar_rlibs(), 2);
+
+    assert_that(p.baler("clean").arg("--stale").arg("--dry-run").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("[REMOVING] [..]libbar-[..].rlib")
+                       .with_stderr_contains("\
+[SUMMARY] [..] stale files, [..] would be removed"));
+    assert_eq!(bar_rlibs(), 2);
+
+    assert_that(p.baler("clean").arg("--stale"),
+                execs().with_status(0).with_stderr("\
+[REMOVED] [..] stale files, [..]
+"));
+    assert_eq!(bar_rlibs(), 1);
+
+    // Everything which is still built was kept.
+    assert_that(p.baler("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn stale_keeps_every_feature_selection() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [features]
+            default = ["a"]
+            a = []
+            b = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+    assert_that(p.baler("build").arg("--no-default-features"), execs().with_status(0));
+    assert_that(p.baler("build").arg("--features").arg("b"), execs().with_status(0));
+    assert_that(p.baler("build").arg("--release"), execs().with_status(0));
+
+    // `foo` is still part of the resolve, so none of its builds are stale.
+    assert_that(p.baler("clean").arg("--stale"),
+                execs().with_status(0).with_stderr("\
+[REMOVED] 0 stale files, 0 B
+"));
+
+    for &args in [&[][..], &["--no-default-features"][..], &["--features", "b"][..]].iter() {
+        assert_that(p.baler("build").args(args),
+                    execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+    }
+}
+
+#[test]
+fn dry_run_needs_stale() {
+    let p = project("foo")
+        .file("Baler.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.baler("clean").arg("--dry-run"),
+                execs().with_status(101).with_stderr("\
+[ERROR] `--dry-run` can only be used together with `--stale`
+"));
+}
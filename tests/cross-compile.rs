@@ -0,0 +1,98 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use balertest::rustc_host;
+use balertest::support::{project, execs, cross_compile};
+use hamcrest::{assert_that, existing_file};
+
+#[test]
+fn build_for_host_and_alternate() {
+    if cross_compile::disabled() { return }
+
+    let host = rustc_host();
+    let target = cross_compile::alternate();
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rustc-cfg=from_build_script");
+            }
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(from_build_script)]
+            fn main() {}
+        "#);
+
+    let output = p.baler_process("build").arg("-v")
+        .arg("--target").arg(&host)
+        .arg("--target").arg(&target)
+        .exec_with_output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    // The build script runs on the host, so it's only compiled once.
+    assert_eq!(stderr.matches("--crate-name build_script_build").count(), 1,
+               "{}", stderr);
+    assert_eq!(stderr.matches("--crate-name foo ").count(), 2,
+               "{}", stderr);
+    assert_that(&p.target_bin(&host, "foo"), existing_file());
+    assert_that(&p.target_bin(&target, "foo"), existing_file());
+}
+
+#[test]
+fn run_tests_for_several_targets() {
+    if cross_compile::disabled() { return }
+
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("test")
+                 .arg("--target").arg(&rustc_host())
+                 .arg("--target").arg(&cross_compile::alternate()),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] tests can only be run for one target at a time, but 2 were requested; \
+pass `--no-run` to only build them
+"));
+
+    assert_that(p.baler("test").arg("--no-run")
+                 .arg("--target").arg(&rustc_host())
+                 .arg("--target").arg(&cross_compile::alternate()),
+                execs().with_status(0));
+}
+
+#[test]
+fn install_for_several_targets() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file(".baler/config", &format!(r#"
+            [build]
+            target = ["{}", "{}"]
+        "#, rustc_host(), cross_compile::alternate()));
+    p.build();
+
+    assert_that(p.baler("install").arg("--path").arg(p.root()),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] `baler install` can only build for one target at a time, but \
+`build.target` lists 2
+"));
+}
@@ -0,0 +1,120 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use balertest::is_nightly;
+use balertest::support::{project, execs, ProjectBuilder};
+use hamcrest::{assert_that, existing_dir};
+
+const SPEC: &'static str = r#"
+{
+    "llvm-target": "x86_64-unknown-none-gnu",
+    "data-layout": "e-m:e-i64:64-f80:128-n8:16:32:64-S128",
+    "arch": "x86_64",
+    "target-endian": "little",
+    "target-pointer-width": "64",
+    "target-c-int-width": "32",
+    "os": "none",
+    "linker-flavor": "ld.lld"
+}
+"#;
+
+fn no_core_project() -> ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            #![feature(no_core)]
+            #![feature(lang_items)]
+            #![no_core]
+
+            #[lang = "sized"]
+            pub trait Sized {}
+            #[lang = "copy"]
+            pub trait Copy {}
+
+            pub fn foo() -> u32 { 42 }
+        "#)
+        .file("custom-target.json", SPEC)
+}
+
+#[test]
+fn custom_target_minimal() {
+    if !is_nightly() { return }
+
+    let p = no_core_project();
+    p.build();
+
+    assert_that(p.baler("build").arg("--lib").arg("-v")
+                 .arg("--target").arg("custom-target.json"),
+                execs().with_status(0).with_stderr_contains("\
+[RUNNING] `rustc [..]--target [..]custom-target.json[..]`"));
+    assert_that(&p.root().join("target/custom-target/debug"), existing_dir());
+}
+
+#[test]
+fn changing_spec_rebuilds() {
+    if !is_nightly() { return }
+
+    let p = no_core_project();
+    p.build();
+
+    assert_that(p.baler("build").arg("--lib")
+                 .arg("--target").arg("custom-target.json"),
+                execs().with_status(0));
+    assert_that(p.baler("build").arg("--lib")
+                 .arg("--target").arg("custom-target.json"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+
+    p.change_file("custom-target.json",
+                  &SPEC.replace("\"os\": \"none\"", "\"os\": \"none\",\n    \"vendor\": \"ourboard\""));
+    assert_that(p.baler("build").arg("--lib")
+                 .arg("--target").arg("custom-target.json"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn missing_spec() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    assert_that(p.baler("build").arg("--target").arg("missing.json"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] failed to read the target specification `[..]missing.json`"));
+}
+
+#[test]
+fn spec_in_config_is_relative_to_it() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file(".baler/config", r#"
+            [build]
+            target = "specs/missing.json"
+        "#);
+    p.build();
+
+    assert_that(p.baler("build").cwd(p.root().join("src")),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] failed to read the target specification `[..]foo[/]specs[/]missing.json`"));
+}
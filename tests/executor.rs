@@ -0,0 +1,157 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use balertest::support::{project, execs, executor, paths, ProjectBuilder};
+use hamcrest::assert_that;
+
+fn with_executor(p: ProjectBuilder, bin: &Path) -> ProjectBuilder {
+    p.file(".baler/config", &format!(r#"
+        [build]
+        executor = ['{}', '{}']
+    "#, bin.display(), paths::root().join("scratch").display()))
+}
+
+fn logged_jobs() -> String {
+    let mut log = String::new();
+    File::open(paths::root().join("scratch/log")).unwrap()
+        .read_to_string(&mut log).unwrap();
+    log
+}
+
+#[test]
+fn builds_through_executor() {
+    let bin = executor::scratch();
+    let p = with_executor(project("foo"), &bin)
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#)
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() -> i32 { 3 }");
+
+    assert_that(p.baler_process("run"),
+                execs().with_status(0).with_stdout("3"));
+
+    let log = logged_jobs();
+    assert!(log.lines().any(|l| l == "bar"), "{}", log);
+    assert!(log.lines().any(|l| l == "foo"), "{}", log);
+
+    // The outputs were copied back, so nothing needs to run again.
+    assert_that(p.baler("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+    assert_eq!(logged_jobs(), log);
+}
+
+#[test]
+fn first_build_inputs_include_sources_outside_the_package() {
+    let bin = executor::scratch();
+    let p = with_executor(project("foo"), &bin)
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar" }
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#)
+        .file("../bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [lib]
+            path = "../shared/bar.rs"
+        "#)
+        .file("../shared/bar.rs", "pub fn bar() -> i32 { 3 }");
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+
+    // Nothing was built before, so there was no dep-info to go by.
+    let mut inputs = String::new();
+    File::open(paths::root().join("scratch/inputs")).unwrap()
+        .read_to_string(&mut inputs).unwrap();
+    let bar_src = format!("bar {}", paths::root().join("shared/bar.rs").display());
+    let bar_root = format!("foo {}", paths::root().join("bar").display());
+    assert!(inputs.lines().any(|l| l == bar_src), "{}", inputs);
+    assert!(inputs.lines().any(|l| l == bar_root), "{}", inputs);
+}
+
+#[test]
+fn executor_reports_compile_errors() {
+    let bin = executor::scratch();
+    let p = with_executor(project("foo"), &bin)
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() -> i32 { \"not a number\" }");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("error[E0308]: mismatched types")
+                       .with_stderr_contains("[ERROR] Could not compile `foo`."));
+}
+
+#[test]
+fn executor_forwards_json_messages() {
+    let bin = executor::scratch();
+    let p = with_executor(project("foo"), &bin)
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "fn unused() {}");
+
+    assert_that(p.baler_process("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains("[..]function is never used: `unused`[..]")
+                       .with_stdout_contains("[..]\"reason\":\"compiler-message\"[..]"));
+}
+
+#[test]
+fn executor_failure() {
+    let p = with_executor(project("foo"), &paths::root().join("missing"))
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[..]failed to run `rustc [..]` using the executor `[..]missing` (configured by \
+`build.executor` in [..]config)"));
+}
@@ -0,0 +1,171 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use balertest::support::{execs, git, paths, project};
+use hamcrest::assert_that;
+
+fn read(path: &::std::path::Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn fixes_unused_mut() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() -> u32 { let mut x = 1; x }\n");
+    p.build();
+
+    assert_that(p.baler("fix").arg("--allow-dirty"),
+                execs().with_status(0)
+                       .with_stderr_contains("[FIXED] src[/]lib.rs (1 fix)"));
+    assert_eq!(read(&p.root().join("src/lib.rs")),
+               "pub fn foo() -> u32 { let x = 1; x }\n");
+
+    // There's nothing left to fix.
+    assert_that(p.baler("fix").arg("--allow-dirty"),
+                execs().with_status(0).with_stderr_does_not_contain("[FIXED] [..]"));
+}
+
+#[test]
+fn nothing_is_fixed_in_dependencies() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar" }
+        "#)
+        .file("src/lib.rs", "extern crate bar;");
+    p.build();
+    let bar = project("bar")
+        .file("Baler.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn bar() -> u32 { let mut x = 1; x }\n");
+    bar.build();
+
+    assert_that(p.baler("fix").arg("--allow-dirty"), execs().with_status(0));
+    assert_eq!(read(&paths::root().join("bar/src/lib.rs")),
+               "pub fn bar() -> u32 { let mut x = 1; x }\n");
+}
+
+#[test]
+fn compile_errors_are_reported() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() -> u32 { missing }\n");
+    p.build();
+
+    assert_that(p.baler("fix").arg("--allow-dirty"),
+                execs().with_status(101)
+                       .with_stderr_contains("[ERROR] Could not compile `foo`."));
+}
+
+#[test]
+fn refuses_dirty_working_directory() {
+    let p = project("foo");
+    p.build();
+
+    git::repo(&paths::root().join("foo"))
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}\n")
+        .build();
+
+    p.change_file("src/lib.rs", "pub fn foo() -> u32 { let mut x = 1; x }\n");
+
+    assert_that(p.baler("fix"),
+                execs().with_status(101)
+                       .with_stderr("\
+error: 1 files in the working directory contain changes that were not yet \
+committed into git:
+
+src[/]lib.rs
+
+to proceed despite this, pass the `--allow-dirty` flag
+"));
+    assert_that(p.baler("fix").arg("--allow-dirty"),
+                execs().with_status(0)
+                       .with_stderr_contains("[FIXED] src[/]lib.rs (1 fix)"));
+}
+
+#[test]
+fn fixes_breaking_the_build_are_reverted() {
+    // Stands in for rustc, but fails once `src/a.rs` was fixed.
+    let rustc = project("rustc")
+        .file("Baler.toml", r#"
+            [package]
+            name = "rustc"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Read;
+            use std::process::{self, Command};
+
+            fn main() {
+                let mut contents = String::new();
+                if let Ok(mut file) = File::open("src/a.rs") {
+                    file.read_to_string(&mut contents).unwrap();
+                }
+                if !contents.is_empty() && !contents.contains("let mut") {
+                    eprintln!("error: fixing src/a.rs broke the build");
+                    process::exit(1);
+                }
+                let status = Command::new("rustc").args(env::args().skip(1))
+                                                  .status().unwrap();
+                process::exit(status.code().unwrap_or(101));
+            }
+        "#);
+    assert_that(rustc.baler_process("build"), execs().with_status(0));
+
+    let a = "pub fn a() -> u32 {\n    let mut x = 1;\n    x\n}\n";
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "mod a;\npub fn foo() -> u32 { let mut x = 1; x }\n")
+        .file("src/a.rs", a);
+    p.build();
+
+    assert_that(p.baler("fix").arg("--allow-dirty").env("RUSTC", rustc.bin("rustc")),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] the code no longer compiled after fixing `src[/]a.rs`, so the last \
+fixes to it were reverted")
+                       .with_stderr_contains("[FIXED] src[/]lib.rs (1 fix)")
+                       .with_stderr_does_not_contain("[FIXED] src[/]a.rs [..]"));
+    assert_eq!(read(&p.root().join("src/a.rs")), a);
+    assert_eq!(read(&p.root().join("src/lib.rs")),
+               "mod a;\npub fn foo() -> u32 { let x = 1; x }\n");
+}
@@ -6,10 +6,9 @@ use std::io::prelude::*;
 
 use balertest::sleep_ms;
 use balertest::support::{project, execs, path2url};
-use balertest::support::paths::CargoPathExt;
+use balertest::support::paths::{self, CargoPathExt};
 use hamcrest::{assert_that, existing_file};
 
-#[test]
 fn modifying_and_moving() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -92,7 +91,6 @@ fn modify_only_some_files() {
     assert_that(&p.bin("foo"), existing_file());
 }
 
-#[test]
 fn rebuild_sub_package_then_while_package() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -144,7 +142,6 @@ fn rebuild_sub_package_then_while_package() {
                 execs().with_status(0));
 }
 
-#[test]
 fn changing_lib_features_caches_targets() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -191,7 +188,6 @@ fn changing_lib_features_caches_targets() {
 "));
 }
 
-#[test]
 fn changing_profiles_caches_targets() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -241,7 +237,6 @@ fn changing_profiles_caches_targets() {
 "));
 }
 
-#[test]
 fn changing_bin_paths_common_target_features_caches_targets() {
     // Make sure dep_cache crate is built once per feature
     let p = project("foo")
@@ -372,7 +367,6 @@ fn changing_bin_paths_common_target_features_caches_targets() {
 "));
 }
 
-#[test]
 fn changing_bin_features_caches_targets() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -428,7 +422,6 @@ fn changing_bin_features_caches_targets() {
 "));
 }
 
-#[test]
 fn rebuild_tests_if_lib_changes() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -459,7 +452,6 @@ fn rebuild_tests_if_lib_changes() {
                 execs().with_status(101));
 }
 
-#[test]
 fn no_rebuild_transitive_target_deps() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -515,7 +507,6 @@ fn no_rebuild_transitive_target_deps() {
 "));
 }
 
-#[test]
 fn rerun_if_changed_in_dep() {
     let p = project("foo")
         .file("Baler.toml", r#"
@@ -548,7 +539,6 @@ fn rerun_if_changed_in_dep() {
                 execs().with_status(0).with_stdout(""));
 }
 
-#[test]
 fn same_build_dir_cached_packages() {
     let p = project("foo")
         .file("a1/Baler.toml", r#"
@@ -615,7 +605,6 @@ fn same_build_dir_cached_packages() {
 ", dir = p.url())));
 }
 
-#[test]
 fn no_rebuild_if_build_artifacts_move_backwards_in_time() {
     let p = project("backwards_in_time")
         .file("Baler.toml", r#"
@@ -681,7 +670,6 @@ fn rebuild_if_build_artifacts_move_forward_in_time() {
 "));
 }
 
-#[test]
 fn rebuild_if_environment_changes() {
     let p = project("env_change")
         .file("Baler.toml", r#"
@@ -721,3 +709,250 @@ fn rebuild_if_environment_changes() {
 [RUNNING] `target[/]debug[/]env_change[EXE]`
 ", dir = p.url())));
 }
+
+#[test]
+fn content_freshness_ignores_touched_files() {
+    let p = project("touched")
+        .file("Baler.toml", r#"
+            [package]
+            name = "touched"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            a = { path = "a" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("a/Baler.toml", r#"
+            [package]
+            name = "a"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("a/src/lib.rs", "")
+        .file(".baler/config", r#"
+            [build]
+            freshness = "content"
+        "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0));
+
+    p.root().move_into_the_future();
+
+    assert_that(p.baler("build"),
+                execs().with_status(0).with_stdout("").with_stderr("\
+[FINISHED] [..]
+"));
+    assert_that(&p.root().join("target/debug/.fingerprint/content-hashes.json"),
+                existing_file());
+}
+
+#[test]
+fn content_freshness_rerun_if_changed() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("build.rs", r#"
+            fn main() {
+                println!("baler:rerun-if-changed=data.txt");
+            }
+        "#)
+        .file("data.txt", "old")
+        .file("src/lib.rs", "")
+        .file(".baler/config", r#"
+            [build]
+            freshness = "content"
+        "#);
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0));
+
+    // Touching the file without changing it doesn't rerun the build script.
+    p.root().join("data.txt").move_into_the_future();
+    assert_that(p.baler("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("[FRESH] foo v0.0.1 ([..])"));
+
+    File::create(&p.root().join("data.txt")).unwrap()
+         .write_all(b"new").unwrap();
+    assert_that(p.baler("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("[RUNNING] `[..]build-script-build`"));
+}
+
+#[test]
+fn invalid_freshness() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file(".baler/config", r#"
+            [build]
+            freshness = "size"
+        "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] build.freshness must be `mtime` or `content`, but found `size` in [..]config
+"));
+}
+
+#[test]
+fn dirty_reason_stale_file() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[DIRTY][..]"));
+
+    sleep_ms(1000);
+    File::create(&p.root().join("src/lib.rs")).unwrap()
+         .write_all(b"pub fn foo() {}").unwrap();
+
+    assert_that(p.baler("build").arg("-v"),
+                execs().with_status(0).with_stderr("\
+[DIRTY] foo v0.0.1 ([..]): the file `[..]lib.rs` has changed ([..])
+[COMPILING] foo v0.0.1 ([..])
+[RUNNING] `rustc [..]`
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn dirty_reason_rustflags() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0));
+
+    assert_that(p.baler("build").arg("-v").env("RUSTFLAGS", "--cfg foo"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[DIRTY] foo v0.0.1 ([..]): the compiler flags have changed: \
+previously [] now [\"--cfg\", \"foo\"]"));
+}
+
+#[test]
+fn dirty_reason_dependency() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "extern crate bar;")
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0));
+
+    sleep_ms(1000);
+    File::create(&p.root().join("bar/src/lib.rs")).unwrap()
+         .write_all(b"pub fn bar() {}").unwrap();
+
+    assert_that(p.baler("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[DIRTY] bar v0.0.1 ([..]): the file `[..]lib.rs` has changed ([..])")
+                       .with_stderr_contains("\
+[DIRTY] foo v0.0.1 ([..]): the dependency `bar v0.0.1 ([..])` was rebuilt"));
+}
+
+#[test]
+fn dirty_reason_message() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.baler_process("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_does_not_contain("[..]unit-dirty-reason[..]"));
+
+    sleep_ms(1000);
+    File::create(&p.root().join("src/lib.rs")).unwrap()
+         .write_all(b"pub fn foo() {}").unwrap();
+
+    assert_that(p.baler("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains(r#"{"dirty_reason":{"current_mtime":[..],"kind":"stale-file","path":"[..]lib.rs","previous_mtime":[..]},"message":"the file `[..]lib.rs` has changed ([..])","package_id":"foo 0.0.1 [..]","reason":"unit-dirty-reason","target":{[..]}}"#));
+}
+
+// Runs a test with `build.freshness` set to `mode` for everything below the
+// test's root directory.
+fn with_freshness(mode: &str, test: fn()) {
+    let config = paths::root().join(".baler/config");
+    fs::create_dir_all(config.parent().unwrap()).unwrap();
+    File::create(&config).unwrap().write_all(format!(r#"
+        [build]
+        freshness = "{}"
+    "#, mode).as_bytes()).unwrap();
+    test()
+}
+
+// Tests which don't depend on how changes are detected run under both
+// freshness modes.
+macro_rules! freshness_tests {
+    ($($name:ident)*) => (
+        mod mtime {
+            $(#[test] fn $name() { super::with_freshness("mtime", super::$name) })*
+        }
+
+        mod content {
+            $(#[test] fn $name() { super::with_freshness("content", super::$name) })*
+        }
+    )
+}
+
+freshness_tests! {
+    modifying_and_moving
+    rebuild_sub_package_then_while_package
+    changing_lib_features_caches_targets
+    changing_profiles_caches_targets
+    changing_bin_paths_common_target_features_caches_targets
+    changing_bin_features_caches_targets
+    rebuild_tests_if_lib_changes
+    no_rebuild_transitive_target_deps
+    rerun_if_changed_in_dep
+    same_build_dir_cached_packages
+    no_rebuild_if_build_artifacts_move_backwards_in_time
+    rebuild_if_environment_changes
+}
@@ -2060,6 +2060,7 @@ fn include_overrides_gitignore() {
                        .with_stderr("\
 [FRESH] libc [..]
 [FRESH] filetime [..]
+[DIRTY] reduction [..]: the file `[..]not_incl.rs` has changed ([..])
 [COMPILING] reduction [..]
 [RUNNING] `rustc --crate-name reduction src[/]lib.rs --crate-type lib [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
@@ -2077,8 +2078,10 @@ fn include_overrides_gitignore() {
                        .with_stderr("\
 [FRESH] libc [..]
 [FRESH] filetime [..]
+[DIRTY] reduction [..]: its sources have changed: [..]
 [COMPILING] reduction [..]
 [RUNNING] `[..][/]build-script-tango-build`
+[DIRTY] reduction [..]: the file `[..]incl.rs` has changed ([..])
 [RUNNING] `rustc --crate-name reduction src[/]lib.rs --crate-type lib [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
@@ -6,8 +6,8 @@ use std::fs::{self, File};
 use std::io::prelude::*;
 
 use balertest::support::paths::{self, CargoPathExt};
-use balertest::support::registry::Package;
-use balertest::support::{project, execs};
+use balertest::support::registry::{Package, SigningKey};
+use balertest::support::{project, execs, ProjectBuilder};
 use hamcrest::assert_that;
 
 fn setup() {
@@ -396,3 +396,320 @@ fn crates_io_registry_url_is_optional() {
 "));
     assert_that(p.baler("test"), execs().with_status(0));
 }
+
+fn setup_signed(key: &SigningKey, require: bool) {
+    let root = paths::root();
+    t!(fs::create_dir(&root.join(".baler")));
+    t!(t!(File::create(root.join(".baler/config"))).write_all(format!(r#"
+        [source.crates-io]
+        registry = 'https://wut'
+        replace-with = 'my-awesome-local-registry'
+
+        [source.my-awesome-local-registry]
+        local-registry = 'registry'
+        signing-keys = ['{}']
+        require-signatures = {}
+    "#, key.public_key(), require).as_bytes()));
+}
+
+fn signed_project() -> ProjectBuilder {
+    project("bar")
+        .file("Baler.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            foo = "0.0.1"
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate foo;
+            pub fn bar() {
+                foo::foo();
+            }
+        "#)
+}
+
+#[test]
+fn signed_package() {
+    let key = SigningKey::new(1);
+    setup_signed(&key, true);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .sign(&key)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    let p = signed_project();
+    assert_that(p.baler_process("build"),
+                execs().with_status(0).with_stderr(&format!("\
+[UNPACKING] foo v0.0.1 ([..])
+[COMPILING] foo v0.0.1
+[COMPILING] bar v0.0.1 ({dir})
+[FINISHED] [..]
+",
+        dir = p.url())));
+}
+
+#[test]
+fn unsigned_package_allowed_without_require() {
+    let key = SigningKey::new(1);
+    setup_signed(&key, false);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    assert_that(signed_project().baler_process("build"),
+                execs().with_status(0));
+}
+
+#[test]
+fn unsigned_package_rejected() {
+    let key = SigningKey::new(1);
+    setup_signed(&key, true);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    let p = signed_project();
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[..]no signature was found for `foo v0.0.1 ([..])` and this source \
+requires signatures
+"));
+
+    // Nothing from the rejected tarball should have been unpacked
+    let src = paths::home().join(".baler/registry/src");
+    assert!(fs::read_dir(&src).map(|d| d.count() == 0).unwrap_or(true));
+}
+
+#[test]
+fn signature_from_untrusted_key() {
+    let trusted = SigningKey::new(1);
+    let untrusted = SigningKey::new(2);
+    setup_signed(&trusted, false);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .sign(&untrusted)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    assert_that(signed_project().baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[..]failed to verify the signature of the index entry for `foo v0.0.1 ([..])`
+"));
+}
+
+#[test]
+fn tampered_crate_file() {
+    let key = SigningKey::new(1);
+    setup_signed(&key, true);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .sign(&key)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    // Replace the crate file and its checksum in the index, but keep the
+    // original signatures around.
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", "pub fn foo() { loop {} }")
+            .publish();
+    let index = paths::root().join("registry/index/3/f/foo");
+    let mut lines = String::new();
+    t!(t!(File::open(&index)).read_to_string(&mut lines));
+    let last = lines.lines().last().unwrap().to_string();
+    t!(t!(File::create(&index)).write_all(format!("{}\n", last).as_bytes()));
+
+    assert_that(signed_project().baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[..]failed to verify the signature of the index entry for `foo v0.0.1 ([..])`
+"));
+}
+
+#[test]
+fn tampered_crate_file_with_valid_index_entry() {
+    let key = SigningKey::new(1);
+    setup_signed(&key, true);
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .sign(&key)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+    let sig_file = paths::root().join("registry/index/3/f/foo.sig");
+    let mut sigs = String::new();
+    t!(t!(File::open(&sig_file)).read_to_string(&mut sigs));
+    let crate_sig = sigs.split_whitespace().nth(2).unwrap().to_string();
+
+    // Replace the crate file, and give it an index entry with a valid
+    // signature, while the signature of the crate file is still the one of
+    // the original.
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", "pub fn foo() { loop {} }")
+            .publish();
+    let index = paths::root().join("registry/index/3/f/foo");
+    let mut lines = String::new();
+    t!(t!(File::open(&index)).read_to_string(&mut lines));
+    let last = lines.lines().last().unwrap().to_string();
+    t!(t!(File::create(&index)).write_all(format!("{}\n", last).as_bytes()));
+    t!(t!(File::create(&sig_file)).write_all(format!("0.0.1 {} {}\n",
+                                                     key.sign(last.as_bytes()),
+                                                     crate_sig).as_bytes()));
+
+    assert_that(signed_project().baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[..]failed to verify the signature of the crate file for `foo v0.0.1 ([..])`
+"));
+}
+
+#[test]
+fn invalid_signing_key() {
+    let root = paths::root();
+    t!(fs::create_dir(&root.join(".baler")));
+    t!(t!(File::create(root.join(".baler/config"))).write_all(br#"
+        [source.crates-io]
+        registry = 'https://wut'
+        replace-with = 'my-awesome-local-registry'
+
+        [source.my-awesome-local-registry]
+        local-registry = 'registry'
+        signing-keys = ['not-a-key']
+    "#));
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+
+    assert_that(signed_project().baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[..]invalid signature configuration for `source.my-awesome-local-registry`
+"));
+}
+
+fn assert_rejected(pkg: &mut Package, err: &str) {
+    setup();
+    pkg.local(true).publish();
+
+    let p = project("bar")
+        .file("Baler.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            foo = "0.0.1"
+        "#)
+        .file("src/lib.rs", "extern crate foo;");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr_contains(&format!("\
+Caused by:
+  failed to unpack package `foo v0.0.1 ([..])`
+
+Caused by:
+  {}
+", err)));
+
+    // Nothing from the rejected tarball should be left behind
+    let src = paths::home().join(".baler/registry/src");
+    for dir in t!(fs::read_dir(&src)) {
+        assert!(!t!(dir).path().join("foo-0.0.1").exists());
+    }
+    assert!(!paths::root().join("evil").exists());
+}
+
+#[test]
+fn archive_path_traversal() {
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .raw_file("foo-0.0.1/../../evil", "pwned"),
+                    "invalid path in archive: `foo-0.0.1/../../evil` is \
+                     absolute or escapes the archive");
+}
+
+#[test]
+fn archive_absolute_path() {
+    let evil = paths::root().join("evil");
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .raw_file(evil.to_str().unwrap(), "pwned"),
+                    "invalid path in archive: `[..]evil` is absolute or \
+                     escapes the archive");
+}
+
+#[test]
+fn archive_outside_of_prefix() {
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .raw_file("bar-0.0.1/src/lib.rs", ""),
+                    "invalid path in archive: `bar-0.0.1/src/lib.rs` is not \
+                     under the `foo-0.0.1` directory");
+}
+
+#[test]
+fn archive_symlink_outside() {
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .symlink("foo-0.0.1/src/evil", "../../../evil"),
+                    "invalid link in archive: `foo-0.0.1/src/evil` points to \
+                     `../../../evil` which is outside of the `foo-0.0.1` \
+                     directory");
+}
+
+#[test]
+fn archive_write_through_symlink() {
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .symlink("foo-0.0.1/dir", ".")
+                        .raw_file("foo-0.0.1/dir/evil", "pwned"),
+                    "invalid path in archive: `foo-0.0.1/dir/evil` is located \
+                     inside of a symbolic link");
+}
+
+#[test]
+fn archive_device_file() {
+    assert_rejected(Package::new("foo", "0.0.1")
+                        .device("foo-0.0.1/evil"),
+                    "invalid entry in archive: `foo-0.0.1/evil` is not a \
+                     regular file, directory or link");
+}
+
+#[test]
+fn archive_too_large() {
+    let root = paths::root();
+    t!(fs::create_dir(&root.join(".baler")));
+    t!(t!(File::create(root.join(".baler/config"))).write_all(br#"
+        [registry]
+        max-unpacked-size = 1024
+
+        [source.crates-io]
+        registry = 'https://wut'
+        replace-with = 'my-awesome-local-registry'
+
+        [source.my-awesome-local-registry]
+        local-registry = 'registry'
+    "#));
+    Package::new("foo", "0.0.1")
+            .local(true)
+            .file("src/lib.rs", &format!("// {}", "x".repeat(2048)))
+            .publish();
+
+    let p = project("bar")
+        .file("Baler.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            foo = "0.0.1"
+        "#)
+        .file("src/lib.rs", "extern crate foo;");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+  archive is larger than the maximum unpacked size of 1024 bytes \
+(configured by `registry.max-unpacked-size`)
+"));
+}
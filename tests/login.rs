@@ -6,9 +6,11 @@ extern crate toml;
 
 use std::io::prelude::*;
 use std::fs::{self, File};
+use std::path::Path;
 
 use balertest::baler_process;
-use balertest::support::execs;
+use balertest::support::{execs, paths};
+use balertest::support::credential;
 use balertest::support::registry::registry;
 use balertest::install::baler_home;
 use baler::util::config::Config;
@@ -126,3 +128,105 @@ fn new_credentials_is_used_instead_old() {
     let token = config.get_string("registry.token").unwrap().map(|p| p.val);
     assert!(token.unwrap() == TOKEN);
 }
+
+fn setup_credential_process(tokens: &Path) {
+    let provider = credential::provider();
+    let config = baler_home().join("config");
+    t!(fs::create_dir_all(config.parent().unwrap()));
+    t!(t!(File::create(&config)).write_all(format!(r#"
+        [registry]
+        credential-process = [{:?}, {:?}]
+    "#, provider.to_str().unwrap(), tokens.to_str().unwrap()).as_bytes()));
+}
+
+fn read_tokens(tokens: &Path) -> String {
+    let mut contents = String::new();
+    File::open(tokens).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn login_with_credential_process() {
+    let tokens = paths::root().join("tokens");
+    setup_credential_process(&tokens);
+
+    assert_that(baler_process().arg("login")
+                .arg("--host").arg(registry().to_string()).arg(TOKEN),
+                execs().with_status(0));
+
+    let credentials = baler_home().join("credentials");
+    assert_that(&credentials, is_not(existing_file()));
+    assert!(read_tokens(&tokens).contains(TOKEN));
+}
+
+#[test]
+fn logout_with_credential_process() {
+    let tokens = paths::root().join("tokens");
+    setup_credential_process(&tokens);
+
+    assert_that(baler_process().arg("login")
+                .arg("--host").arg(registry().to_string()).arg(TOKEN),
+                execs().with_status(0));
+    assert_that(baler_process().arg("logout"),
+                execs().with_status(0));
+
+    assert!(!read_tokens(&tokens).contains(TOKEN));
+}
+
+#[test]
+fn credential_process_tokens_are_kept_per_registry() {
+    let tokens = paths::root().join("tokens");
+    setup_credential_process(&tokens);
+
+    assert_that(baler_process().arg("login").arg("crates-io-token"),
+                execs().with_status(0));
+    assert_that(baler_process().arg("login")
+                .arg("--index").arg(registry().to_string()).arg(TOKEN),
+                execs().with_status(0));
+    assert!(read_tokens(&tokens).contains("crates-io-token"));
+    assert!(read_tokens(&tokens).contains(TOKEN));
+
+    assert_that(baler_process().arg("logout")
+                .arg("--index").arg(registry().to_string()),
+                execs().with_status(0));
+    assert!(read_tokens(&tokens).contains("crates-io-token"));
+    assert!(!read_tokens(&tokens).contains(TOKEN));
+}
+
+#[test]
+fn logout_without_credential_process() {
+    setup_new_credentials();
+
+    assert_that(baler_process().arg("logout"),
+                execs().with_status(0));
+
+    let config = Config::new(Shell::new(), baler_home(), baler_home());
+    let token = config.get_string("registry.token").unwrap();
+    assert!(token.is_none());
+}
+
+#[test]
+fn logout_without_credentials() {
+    assert_that(baler_process().arg("logout"),
+                execs().with_status(0));
+
+    let credentials = baler_home().join("credentials");
+    assert_that(&credentials, is_not(existing_file()));
+}
+
+#[test]
+fn credential_process_error() {
+    // A directory can't be opened as the token file, so storing fails.
+    let tokens = paths::root().join("tokens");
+    t!(fs::create_dir_all(&tokens));
+    setup_credential_process(&tokens);
+
+    assert_that(baler_process().arg("login")
+                .arg("--host").arg(registry().to_string()).arg(TOKEN),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] failed to `store` the token for `[..]` using the credential process \
+`[..]baler-credential-file[EXE]` (configured by `registry.credential-process` \
+in [..])")
+                       .with_stderr_contains("[..]failed to write the token file"));
+}
@@ -1,18 +1,20 @@
 #[macro_use]
 extern crate balertest;
+extern crate filetime;
 extern crate flate2;
 extern crate git2;
 extern crate hamcrest;
 extern crate tar;
 extern crate baler;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
 use balertest::{baler_process, process};
 use balertest::support::{project, execs, paths, git, path2url, baler_exe};
-use balertest::support::registry::Package;
+use balertest::support::registry::{self, Package};
+use filetime::FileTime;
 use flate2::read::GzDecoder;
 use hamcrest::{assert_that, existing_file, contains, equal_to};
 use tar::Archive;
@@ -854,3 +856,85 @@ fn package_two_kinds_of_deps() {
     assert_that(p.baler_process("package").arg("--no-verify"),
                 execs().with_status(0));
 }
+
+#[test]
+fn reproducible_archives() {
+    Package::new("other", "1.0.0").publish();
+    Package::new("other1", "1.0.0").publish();
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            other = "1.0"
+            other1 = { version = "1.0", optional = true }
+
+            [features]
+            a = ["other1"]
+            b = []
+            c = ["a", "b"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("src/a.rs", "")
+        .file("src/b.rs", "");
+
+    fn checksum(path: &Path) -> String {
+        let mut contents = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut contents).unwrap();
+        registry::cksum(&contents)
+    }
+
+    let archive = p.root().join("target/package/foo-0.0.1.crate");
+    assert_that(p.baler_process("package").arg("--no-verify"),
+                execs().with_status(0));
+    let first = checksum(&archive);
+
+    // Neither the files' modification times nor the archive's own should
+    // matter.
+    let mtime = FileTime::from_seconds_since_1970(1000000000, 0);
+    filetime::set_file_times(&p.root().join("src/a.rs"), mtime, mtime).unwrap();
+    fs::remove_file(&archive).unwrap();
+    assert_that(p.baler("package").arg("--no-verify"),
+                execs().with_status(0));
+    assert_that(checksum(&archive), equal_to(first.clone()));
+
+    assert_that(p.baler("package").arg("--no-verify")
+                 .env("SOURCE_DATE_EPOCH", "1234567890"),
+                execs().with_status(0));
+    assert_that(checksum(&archive) == first, equal_to(false));
+
+    let mut rdr = GzDecoder::new(File::open(&archive).unwrap()).unwrap();
+    let mut contents = Vec::new();
+    rdr.read_to_end(&mut contents).unwrap();
+    let mut ar = Archive::new(&contents[..]);
+    for f in ar.entries().unwrap() {
+        let f = f.unwrap();
+        let header = f.header();
+        assert_that(header.mtime().unwrap(), equal_to(1234567890));
+        assert_that(header.uid().unwrap(), equal_to(0));
+        assert_that(header.gid().unwrap(), equal_to(0));
+        assert_that(header.mode().unwrap(), equal_to(0o644));
+    }
+}
+
+#[test]
+fn invalid_source_date_epoch() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.baler_process("package").arg("--no-verify")
+                 .env("SOURCE_DATE_EPOCH", "yesterday"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] `SOURCE_DATE_EPOCH` should be a number of seconds since the Unix epoch, \
+but is `yesterday`"));
+}
@@ -0,0 +1,151 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use std::fs;
+
+use balertest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+
+fn lib_with_dep(bar: &str) -> ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.5.0"
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "extern crate bar; pub fn foo() { bar::bar() }")
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("bar/src/lib.rs", bar)
+}
+
+#[test]
+fn rlib_dependents_use_metadata() {
+    let p = lib_with_dep("pub fn bar() {}");
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v0.0.1 ([..])
+[RUNNING] `rustc --crate-name bar bar[/]src[/]lib.rs --error-format json --json=artifacts \
+        --crate-type lib --emit=dep-info,metadata,link [..]`
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `rustc --crate-name foo src[/]lib.rs --crate-type lib --emit=dep-info,link [..]\
+        --extern bar=[..]target[/]debug[/]deps[/]libbar-[..].rmeta`
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+
+    let deps = p.root().join("target/debug/deps");
+    let outputs = fs::read_dir(&deps).unwrap().map(|e| {
+        e.unwrap().file_name().into_string().unwrap()
+    }).collect::<Vec<_>>();
+    assert!(outputs.iter().any(|f| f.starts_with("libbar-") && f.ends_with(".rmeta")),
+            "{:?}", outputs);
+    assert!(outputs.iter().any(|f| f.starts_with("libbar-") && f.ends_with(".rlib")),
+            "{:?}", outputs);
+}
+
+#[test]
+fn binaries_wait_for_rlib() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.5.0"
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v0.0.1 ([..])
+[RUNNING] `rustc --crate-name bar bar[/]src[/]lib.rs --crate-type lib --emit=dep-info,link [..]`
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `rustc --crate-name foo src[/]main.rs --crate-type bin --emit=dep-info,link [..]\
+        --extern bar=[..]target[/]debug[/]deps[/]libbar-[..].rlib`
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn pipelining_disabled() {
+    let p = lib_with_dep("pub fn bar() {}")
+        .file(".baler/config", r#"
+            [build]
+            pipelining = false
+        "#);
+
+    assert_that(p.baler_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[..]--json=artifacts[..]")
+                       .with_stderr_contains("[..]--extern bar=[..]libbar-[..].rlib`"));
+}
+
+#[test]
+fn pipelined_warnings_are_rendered() {
+    let p = lib_with_dep("pub fn bar() {} fn dead() {}");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[WARNING] [..]never used[..]")
+                       .with_stderr_does_not_contain("[..]\"rendered\"[..]"));
+}
+
+#[test]
+fn pipelined_artifacts_not_forwarded() {
+    let p = lib_with_dep("pub fn bar() {}");
+
+    assert_that(p.baler_process("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_does_not_contain("[..]\"emit\":\"metadata\"[..]"));
+}
+
+#[test]
+fn pipelined_dep_error_fails_build() {
+    let p = lib_with_dep("pub fn bar() { missing() }");
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("[ERROR] Could not compile `bar`.")
+                       .with_stderr_does_not_contain("[COMPILING] foo[..]"));
+}
+
+#[test]
+fn missing_rmeta_rebuilds() {
+    let p = lib_with_dep("pub fn bar() {}");
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+
+    let deps = p.root().join("target/debug/deps");
+    for entry in fs::read_dir(&deps).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if name.starts_with("libbar-") && name.ends_with(".rmeta") {
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    assert_that(p.baler("build"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v0.0.1 ([..])
+[COMPILING] foo v0.5.0 ([..])
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+}
@@ -10,6 +10,7 @@ use std::fs::{self, File};
 use std::io::SeekFrom;
 use std::path::PathBuf;
 
+use balertest::support::credential;
 use balertest::support::git::repo;
 use balertest::support::paths;
 use balertest::support::{project, execs};
@@ -520,3 +521,38 @@ See [..]
     // Ensure the API request wasn't actually made
     assert!(!upload_path().join("api/v1/crates/new").exists());
 }
+
+#[test]
+fn publish_with_token_from_credential_process() {
+    setup();
+    let provider = credential::provider();
+    let tokens = paths::root().join("tokens");
+    t!(t!(File::create(paths::root().join(".baler/config"))).write_all(format!(r#"
+        [registry]
+        credential-process = [{:?}, {:?}]
+    "#, provider.to_str().unwrap(), tokens.to_str().unwrap()).as_bytes()));
+
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.baler_process("publish").arg("--no-verify")
+                 .arg("--index").arg(registry().to_string()),
+                execs().with_status(101).with_stderr_contains("\
+[..]no upload token found, please run `baler login`"));
+
+    assert_that(p.baler("login")
+                 .arg("--index").arg(registry().to_string()).arg("api-token"),
+                execs().with_status(0));
+    assert_that(p.baler("publish").arg("--no-verify")
+                 .arg("--index").arg(registry().to_string()),
+                execs().with_status(0)
+                       .with_stderr_contains("[UPLOADING] foo v0.0.1 ([..])"));
+}
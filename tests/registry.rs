@@ -5,7 +5,10 @@ extern crate url;
 
 use std::fs::{self, File};
 use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpListener;
 use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
 
 use balertest::baler_process;
 use balertest::support::git;
@@ -1408,3 +1411,174 @@ fn vv_prints_warnings() {
     assert_that(p.baler("build").arg("-vv"),
                 execs().with_status(0));
 }
+
+#[test]
+fn archive_hard_link_outside() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    Package::new("bar", "0.0.1")
+            .hard_link("bar-0.0.1/evil", "../../../.baler/credentials")
+            .publish();
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+Caused by:
+  failed to unpack package `bar v0.0.1 (registry file://[..])`
+
+Caused by:
+  invalid link in archive: `bar-0.0.1/evil` points to \
+`../../../.baler/credentials` which uses `..` in a position that isn't allowed
+"));
+}
+
+#[cfg(unix)]
+#[test]
+fn archive_symlink_inside_package() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar(); }");
+
+    Package::new("bar", "0.0.1")
+            .file("src/bar.rs", "pub fn bar() {}")
+            .symlink("bar-0.0.1/src/lib.rs", "bar.rs")
+            .publish();
+
+    assert_that(p.baler_process("build"), execs().with_status(0));
+}
+
+/// Starts an HTTP server answering one request per connection with each of
+/// `responses` in turn, and sets it as the registry's download location.
+///
+/// The returned thread yields the request lines it saw.
+fn flaky_dl_server(responses: Vec<Vec<u8>>) -> JoinHandle<Vec<String>> {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    registry::set_dl(&format!("http://{}", addr));
+
+    thread::spawn(move || {
+        responses.into_iter().map(|response| {
+            let conn = server.accept().unwrap().0;
+            let mut conn = BufReader::new(conn);
+            let mut request = String::new();
+            conn.read_line(&mut request).unwrap();
+            let mut line = String::new();
+            while conn.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            conn.get_mut().write_all(&response).unwrap();
+            request.trim().to_string()
+        }).collect()
+    })
+}
+
+fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut ret = format!("HTTP/1.1 {}\r\n{}Content-Length: {}\r\n\
+                           Connection: close\r\n\r\n",
+                          status, headers, body.len()).into_bytes();
+    ret.extend_from_slice(body);
+    ret
+}
+
+fn downloaded_bar() -> Vec<u8> {
+    let mut archive = Vec::new();
+    t!(t!(File::open(registry::dl_path().join("bar/0.0.1/download")))
+           .read_to_end(&mut archive));
+    archive
+}
+
+fn bar_dependent() -> balertest::support::ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+}
+
+#[test]
+fn download_retries_server_errors() {
+    Package::new("bar", "0.0.1").publish();
+    let t = flaky_dl_server(vec![
+        response("503 Service Unavailable", "", b""),
+        response("429 Too Many Requests", "Retry-After: 0\r\n", b""),
+        response("200 OK", "", &downloaded_bar()),
+    ]);
+    let p = bar_dependent();
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] spurious network error on attempt 1/3: failed to get 200 \
+response from `http://127.0.0.1:[..]/bar/0.0.1/download`, got 503")
+                       .with_stderr_contains("\
+[WARNING] spurious network error on attempt 2/3: failed to get 200 \
+response from `http://127.0.0.1:[..]/bar/0.0.1/download`, got 429"));
+    assert_eq!(t.join().unwrap(), vec![
+        "GET /bar/0.0.1/download HTTP/1.1",
+        "GET /bar/0.0.1/download HTTP/1.1",
+        "GET /bar/0.0.1/download HTTP/1.1",
+    ]);
+}
+
+#[test]
+fn download_gives_up_after_net_retry() {
+    Package::new("bar", "0.0.1").publish();
+    let t = flaky_dl_server(vec![
+        response("429 Too Many Requests", "Retry-After: 0\r\n", b""),
+        response("429 Too Many Requests", "Retry-After: 0\r\n", b""),
+    ]);
+    let p = bar_dependent()
+        .file(".baler/config", r#"
+            [net]
+            retry = 1
+        "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[WARNING] spurious network error on attempt 1/2: [..]got 429")
+                       .with_stderr_contains("\
+Caused by:
+  failed to get 200 response from `http://127.0.0.1:[..]/bar/0.0.1/download`, got 429"));
+    assert_eq!(t.join().unwrap().len(), 2);
+}
+
+#[test]
+fn download_does_not_retry_client_errors() {
+    Package::new("bar", "0.0.1").publish();
+    let t = flaky_dl_server(vec![
+        response("404 Not Found", "", b""),
+    ]);
+    let p = bar_dependent();
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101)
+                       .with_stderr_does_not_contain("[WARNING] spurious[..]")
+                       .with_stderr_contains("\
+Caused by:
+  failed to get 200 response from `http://127.0.0.1:[..]/bar/0.0.1/download`, got 404"));
+    assert_eq!(t.join().unwrap().len(), 1);
+}
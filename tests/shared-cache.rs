@@ -0,0 +1,192 @@
+extern crate balertest;
+extern crate hamcrest;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+
+use balertest::rustc_host;
+use balertest::support::paths;
+use balertest::support::registry::Package;
+use balertest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+
+fn configure_cache(max_size: Option<u64>) {
+    let dir = paths::root().join(".baler");
+    fs::create_dir_all(&dir).unwrap();
+    let mut config = format!("[build]\nshared-cache = '{}'\n",
+                             paths::root().join("cache").display());
+    if let Some(max_size) = max_size {
+        config.push_str(&format!("shared-cache-max-size = {}\n", max_size));
+    }
+    File::create(dir.join("config")).unwrap()
+        .write_all(config.as_bytes()).unwrap();
+}
+
+fn uses_bar(name: &str) -> ProjectBuilder {
+    project(name)
+        .file("Baler.toml", &format!(r#"
+            [project]
+            name = "{}"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+        "#, name))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#)
+}
+
+#[test]
+fn registry_deps_are_restored_in_other_workspaces() {
+    configure_cache(None);
+    Package::new("bar", "0.0.1")
+        .file("src/lib.rs", "pub fn bar() -> i32 { 3 }")
+        .publish();
+
+    let foo = uses_bar("foo");
+    foo.build();
+    assert_that(foo.baler("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] bar v0.0.1"));
+
+    let baz = uses_bar("baz");
+    baz.build();
+    assert_that(baz.baler("run"),
+                execs().with_status(0).with_stdout("3")
+                       .with_stderr_contains("\
+[RESTORED] bar v0.0.1
+[COMPILING] baz v0.0.1 ([..])
+")
+                       .with_stderr_does_not_contain("[COMPILING] bar [..]"));
+
+    // Once restored the artifacts are fresh like any others.
+    assert_that(baz.baler("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("[FRESH] bar v0.0.1"));
+}
+
+#[test]
+fn entries_depend_on_the_linker_and_archiver() {
+    configure_cache(None);
+    Package::new("bar", "0.0.1")
+        .file("src/lib.rs", "pub fn bar() -> i32 { 3 }")
+        .publish();
+
+    let foo = uses_bar("foo");
+    foo.build();
+    assert_that(foo.baler("build"), execs().with_status(0));
+
+    let baz = uses_bar("baz")
+        .file(".baler/config", &format!(r#"
+            [target.{}]
+            ar = "my-ar"
+        "#, rustc_host()));
+    baz.build();
+    assert_that(baz.baler("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] bar v0.0.1")
+                       .with_stderr_does_not_contain("[RESTORED] bar [..]"));
+}
+
+#[test]
+fn path_deps_are_not_cached() {
+    configure_cache(None);
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Baler.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+    p.build();
+
+    assert_that(p.baler("build"), execs().with_status(0));
+    assert_that(p.baler("clean"), execs().with_status(0));
+    assert_that(p.baler("build"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v0.0.1 ([..])
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn deps_with_build_scripts_are_not_cached() {
+    configure_cache(None);
+    Package::new("bar", "0.0.1")
+        .file("build.rs", "fn main() {}")
+        .file("src/lib.rs", "pub fn bar() -> i32 { 3 }")
+        .publish();
+
+    let p = uses_bar("foo");
+    p.build();
+    assert_that(p.baler("build"), execs().with_status(0));
+    assert_that(p.baler("clean"), execs().with_status(0));
+    assert_that(p.baler("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] bar v0.0.1"));
+}
+
+#[test]
+fn cache_is_trimmed_to_max_size() {
+    configure_cache(Some(0));
+    Package::new("bar", "0.0.1")
+        .file("src/lib.rs", "pub fn bar() -> i32 { 3 }")
+        .publish();
+
+    let p = uses_bar("foo");
+    p.build();
+    assert_that(p.baler("build"), execs().with_status(0));
+    assert_that(p.baler("clean"), execs().with_status(0));
+    assert_that(p.baler("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] bar v0.0.1"));
+
+    let cache = paths::root().join("cache");
+    for triple in fs::read_dir(&cache).unwrap() {
+        let triple = triple.unwrap();
+        if triple.file_type().unwrap().is_dir() {
+            assert_eq!(fs::read_dir(triple.path()).unwrap().count(), 0,
+                       "{} isn't empty", triple.path().display());
+        }
+    }
+}
+
+#[test]
+fn invalid_max_size() {
+    let dir = paths::root().join(".baler");
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("config")).unwrap().write_all(b"
+        [build]
+        shared-cache = 'cache'
+        shared-cache-max-size = -1
+    ").unwrap();
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.baler("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] build.shared-cache-max-size must not be negative, but found -1 in [..]config
+"));
+}
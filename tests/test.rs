@@ -909,6 +909,41 @@ fn bin_there_for_integration() {
     assert!(output.contains("test_test ... ok"), "no test_test\n{}", output);
 }
 
+#[test]
+fn bin_exe_env_for_integration() {
+    let p = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "foo-cli"
+            path = "src/main.rs"
+        "#)
+        .file("src/main.rs", "
+            fn main() { std::process::exit(101); }
+        ")
+        .file("tests/foo.rs", r#"
+            use std::process::Command;
+            #[test]
+            fn test_test() {
+                let exe = env!("BALER_BIN_EXE_foo-cli");
+                assert!(exe.contains("custom-target"), "{}", exe);
+                let status = Command::new(exe).status().unwrap();
+                assert_eq!(status.code(), Some(101));
+            }
+        "#);
+
+    assert_that(p.baler_process("test").env("CARGO_TARGET_DIR", "custom-target"),
+                execs().with_status(0)
+                       .with_stdout_contains("test test_test ... ok"));
+
+    // The variable is set when the binaries are only checked, too.
+    assert_that(p.baler("check").arg("--tests"), execs().with_status(0));
+}
+
 #[test]
 fn test_dylib() {
     let p = project("foo")
@@ -1996,11 +2031,13 @@ fn bin_does_not_rebuild_tests() {
     File::create(&p.root().join("src/main.rs")).unwrap()
          .write_all(b"fn main() { 3; }").unwrap();
 
-    assert_that(p.baler("test").arg("-v").arg("--no-run"),
+    assert_that(p.baler("test").arg("-v").arg("--no-run").arg("-j1"),
                 execs().with_status(0)
                        .with_stderr("\
+[DIRTY] foo v0.0.1 ([..]): the file `[..]main.rs` has changed ([..])
 [COMPILING] foo v0.0.1 ([..])
 [RUNNING] `rustc [..] src[/]main.rs [..]`
+[DIRTY] foo v0.0.1 ([..]): the file `[..]main.rs` has changed ([..])
 [RUNNING] `rustc [..] src[/]main.rs [..]`
 [FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
 "));
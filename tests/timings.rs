@@ -0,0 +1,130 @@
+extern crate balertest;
+extern crate hamcrest;
+extern crate serde_json;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use balertest::support::{project, execs, ProjectBuilder};
+use hamcrest::{assert_that, existing_file};
+use serde_json::Value;
+
+fn project_with_dep() -> ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.5.0"
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "extern crate bar;")
+        .file("bar/Baler.toml", r#"
+            [package]
+            name = "bar"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("bar/src/lib.rs", "")
+}
+
+#[test]
+fn timings_writes_report() {
+    let p = project_with_dep();
+    p.build();
+
+    assert_that(p.baler("build").arg("--timings"),
+                execs().with_status(0)
+                       .with_stderr_contains("[..]Timing report saved to \
+                                              [..][/]target[/]baler-timings[/]\
+                                              baler-timing-[..].html"));
+
+    let dir = p.root().join("target/baler-timings");
+    assert_that(&dir.join("baler-timing.html"), existing_file());
+    assert_that(&dir.join("baler-timing.json"), existing_file());
+
+    let mut html = String::new();
+    File::open(dir.join("baler-timing.html")).unwrap()
+        .read_to_string(&mut html).unwrap();
+    assert!(html.contains("bar v0.0.1 (build)"), "{}", html);
+    assert!(html.contains("foo v0.5.0 (build)"), "{}", html);
+
+    let mut json = String::new();
+    File::open(dir.join("baler-timing.json")).unwrap()
+        .read_to_string(&mut json).unwrap();
+    let report: Value = serde_json::from_str(&json).unwrap();
+    let units = report["units"].as_array().unwrap();
+    assert_eq!(units.len(), 2);
+    assert_eq!(units[0]["name"], "bar");
+    assert_eq!(units[1]["name"], "foo");
+    // `foo` can't start until `bar` is done, so they make up the critical
+    // path together.
+    assert_eq!(units[1]["deps"], json_array(&[0]));
+    assert_eq!(report["critical_path"], json_array(&[0, 1]));
+}
+
+#[test]
+fn timings_not_written_by_default() {
+    let p = project_with_dep();
+    p.build();
+
+    assert_that(p.baler("build"),
+                execs().with_status(0)
+                       .with_stderr_does_not_contain("[..]Timing[..]"));
+    assert!(!p.root().join("target/baler-timings").exists());
+}
+
+#[test]
+fn timing_info_messages() {
+    let p = project_with_dep();
+    p.build();
+
+    assert_that(p.baler("build").arg("--timings").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains(r#"{"duration":[..],"fresh":false,"mode":"build","package_id":"bar 0.0.1 [..]","reason":"timing-info","target":{[..]}}"#)
+                       .with_stdout_contains(r#"{"duration":[..],"fresh":false,"mode":"build","package_id":"foo 0.5.0 [..]","reason":"timing-info","target":{[..]}}"#));
+
+    // Fresh units are still reported.
+    assert_that(p.baler("build").arg("--timings").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_contains(r#"{"duration":[..],"fresh":true,"mode":"build","package_id":"foo 0.5.0 [..]","reason":"timing-info","target":{[..]}}"#));
+}
+
+#[test]
+fn timing_info_requires_timings() {
+    let p = project_with_dep();
+    p.build();
+
+    assert_that(p.baler("build").arg("--message-format=json"),
+                execs().with_status(0)
+                       .with_stdout_does_not_contain("[..]timing-info[..]"));
+}
+
+#[test]
+fn unit_costs_remembered() {
+    let p = project_with_dep();
+    p.build();
+
+    assert_that(p.baler("build"), execs().with_status(0));
+
+    let path = p.root().join("target/debug/.fingerprint/unit-costs.json");
+    let read_costs = || {
+        let mut contents = String::new();
+        File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+        serde_json::from_str::<Vec<(String, u64)>>(&contents).unwrap()
+    };
+    let costs = read_costs();
+    assert_eq!(costs.len(), 2, "{:?}", costs);
+    assert!(costs.iter().any(|c| c.0.starts_with("bar 0.0.1 ")), "{:?}", costs);
+    assert!(costs.iter().any(|c| c.0.starts_with("foo 0.5.0 ")), "{:?}", costs);
+
+    // Fresh units aren't built, so what they cost is kept.
+    assert_that(p.baler("build"), execs().with_status(0));
+    assert_eq!(read_costs(), costs);
+}
+
+fn json_array(indices: &[u64]) -> Value {
+    Value::Array(indices.iter().map(|&i| Value::from(i)).collect())
+}
@@ -0,0 +1,217 @@
+extern crate balertest;
+extern crate hamcrest;
+#[cfg(unix)]
+extern crate openssl;
+
+use std::path::PathBuf;
+
+use balertest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+
+// The certificates in `tests/tls` were generated with the `openssl` command
+// line tool: a self-signed CA, and a server certificate for `127.0.0.1` and a
+// client certificate both signed by that CA.
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/tls").join(name)
+}
+
+fn git_project(port: u16, config: &str) -> ProjectBuilder {
+    project("foo")
+        .file("Baler.toml", &format!(r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            git = "https://127.0.0.1:{}/foo/bar"
+        "#, port))
+        .file("src/main.rs", "")
+        .file(".baler/config", &format!(r#"
+            [net]
+            retry = 0
+            {}
+        "#, config))
+}
+
+#[cfg(unix)]
+mod server {
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use openssl::pkey::PKey;
+    use openssl::ssl::{SslAcceptorBuilder, SslMethod};
+    use openssl::ssl::{SSL_VERIFY_PEER, SSL_VERIFY_FAIL_IF_NO_PEER_CERT};
+    use openssl::x509::X509;
+
+    use super::fixture;
+
+    /// Starts a TLS server for one connection, using the test CA's server
+    /// certificate.
+    ///
+    /// The returned thread yields the request line if the handshake succeeded.
+    /// If `client_auth` is set, clients must present a certificate signed by
+    /// the test CA.
+    pub fn serve(client_auth: bool) -> (u16, JoinHandle<Option<String>>) {
+        let key = PKey::private_key_from_pem(
+            include_bytes!("tls/server-key.pem")).unwrap();
+        let cert = X509::from_pem(include_bytes!("tls/server.pem")).unwrap();
+        let mut builder = SslAcceptorBuilder::mozilla_intermediate(
+            SslMethod::tls(), &key, &cert, Vec::<X509>::new()).unwrap();
+        if client_auth {
+            let ctx = builder.builder_mut();
+            ctx.set_ca_file(fixture("ca.pem")).unwrap();
+            ctx.set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+        }
+        let acceptor = builder.build();
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let t = thread::spawn(move || {
+            let conn = server.accept().unwrap().0;
+            let conn = match acceptor.accept(conn) {
+                Ok(conn) => conn,
+                Err(..) => return None,
+            };
+            let mut conn = BufReader::new(conn);
+            let mut line = String::new();
+            conn.read_line(&mut line).unwrap();
+            conn.get_mut().write_all(b"\
+                HTTP/1.1 404 Not Found\r\n\
+                Content-Length: 0\r\n\
+                Connection: close\r\n\
+                \r\n\
+            ").unwrap();
+            Some(line.trim().to_string())
+        });
+        (port, t)
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn cainfo_trusts_custom_ca() {
+    let (port, t) = server::serve(false);
+    let p = git_project(port, &format!(r#"
+        [http]
+        cainfo = "{}"
+    "#, fixture("ca.pem").display()));
+
+    assert_that(p.baler_process("build"), execs().with_status(101));
+    assert_eq!(t.join().unwrap().as_ref().map(|s| &s[..]),
+               Some("GET /foo/bar/info/refs?service=git-upload-pack HTTP/1.1"));
+}
+
+#[cfg(unix)]
+#[test]
+fn unknown_ca_is_rejected() {
+    let (port, t) = server::serve(false);
+    let p = git_project(port, r#"
+        [http]
+        ssl-version = "tlsv1.2"
+    "#);
+
+    assert_that(p.baler_process("build"), execs().with_status(101));
+    assert_eq!(t.join().unwrap(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn client_certificate() {
+    let (port, t) = server::serve(true);
+    let p = git_project(port, &format!(r#"
+        [http]
+        cainfo = "{}"
+        client-cert = "{}"
+        client-key = "{}"
+        ssl-version = {{ min = "tlsv1.2" }}
+    "#, fixture("ca.pem").display(),
+        fixture("client.pem").display(),
+        fixture("client-key.pem").display()));
+
+    assert_that(p.baler_process("build"), execs().with_status(101));
+    assert!(t.join().unwrap().is_some());
+}
+
+#[cfg(unix)]
+#[test]
+fn client_certificate_required() {
+    let (port, t) = server::serve(true);
+    let p = git_project(port, &format!(r#"
+        [http]
+        cainfo = "{}"
+    "#, fixture("ca.pem").display()));
+
+    assert_that(p.baler_process("build"), execs().with_status(101));
+    assert_eq!(t.join().unwrap(), None);
+}
+
+#[test]
+fn missing_cainfo() {
+    let p = git_project(1, r#"
+        [http]
+        cainfo = "missing.pem"
+    "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] the file `missing.pem` configured by `http.cainfo` in [..]config \
+does not exist
+"));
+}
+
+#[test]
+fn bad_config_only_breaks_network_commands() {
+    let p = git_project(1, r#"
+        [http]
+        cainfo = "missing.pem"
+        ssl-version = "sslv3"
+    "#);
+
+    assert_that(p.baler_process("--version"), execs().with_status(0));
+    assert_that(p.baler("new").arg("baz").env("USER", "foo"),
+                execs().with_status(0));
+}
+
+#[test]
+fn client_key_without_cert() {
+    let p = git_project(1, &format!(r#"
+        [http]
+        client-key = "{}"
+    "#, fixture("client-key.pem").display()));
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] `http.client-key` is configured without `http.client-cert`
+"));
+}
+
+#[test]
+fn invalid_ssl_version() {
+    let p = git_project(1, r#"
+        [http]
+        ssl-version = "sslv3"
+    "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] invalid TLS version `sslv3` for `http.ssl-version` in [..]config, \
+expected one of `default`, `tlsv1`, `tlsv1.0`, `tlsv1.1`, `tlsv1.2` or `tlsv1.3`
+"));
+}
+
+#[test]
+fn invalid_ssl_version_range() {
+    let p = git_project(1, r#"
+        [http]
+        ssl-version = { min = 12 }
+    "#);
+
+    assert_that(p.baler_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] expected a string for `http.ssl-version.min`, but found integer in \
+[..]config
+"));
+}
@@ -2,7 +2,7 @@ extern crate balertest;
 extern crate hamcrest;
 
 use balertest::rustc_host;
-use balertest::support::{path2url, project, execs};
+use balertest::support::{path2url, project, execs, ProjectBuilder};
 use hamcrest::assert_that;
 
 #[test]
@@ -169,3 +169,110 @@ fn custom_runner() {
 [RUNNING] `nonexistent-runner -r [..][/]target[/]release[/]deps[/]bench-[..][EXE] --param --bench`
 ", url = foo.url())));
 }
+
+#[cfg(unix)]
+fn with_runner_script(foo: &ProjectBuilder) {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::os::unix::prelude::*;
+
+    let script = foo.root().join("runner.sh");
+    File::create(&script).unwrap().write_all(b"\
+#!/bin/sh
+echo \"runner: $*\"
+exec \"$@\"
+").unwrap();
+    let mut perms = fs::metadata(&script).unwrap().permissions();
+    let mode = perms.mode();
+    perms.set_mode(mode | 0o111);
+    fs::set_permissions(&script, perms).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn cfg_runner() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", r#"fn main() { println!("hello"); }"#)
+        .file("tests/test.rs", "")
+        .file(".baler/config", r#"
+            [target.'cfg(unix)']
+            runner = "./runner.sh"
+        "#);
+
+    foo.build();
+    with_runner_script(&foo);
+
+    assert_that(foo.baler("run").args(&["--", "--param"]),
+                execs().with_status(0)
+                       .with_stderr_contains("[RUNNING] `[..]runner.sh target[/]debug[/]foo --param`")
+                       .with_stdout("\
+runner: target[/]debug[/]foo --param
+hello
+"));
+
+    assert_that(foo.baler("test").args(&["--test", "test"]),
+                execs().with_status(0)
+                       .with_stdout_contains("runner: [..][/]target[/]debug[/]deps[/]test-[..]"));
+}
+
+#[cfg(unix)]
+#[test]
+fn triple_runner_before_cfg_runner() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file(".baler/config", &format!(r#"
+            [target.{}]
+            runner = "nonexistent-runner -r"
+
+            [target.'cfg(unix)']
+            runner = "./runner.sh"
+        "#, rustc_host()));
+
+    foo.build();
+    with_runner_script(&foo);
+
+    assert_that(foo.baler("run"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `nonexistent-runner -r target[/]debug[/]foo`"));
+}
+
+#[test]
+fn several_cfg_runners() {
+    let foo = project("foo")
+        .file("Baler.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file(".baler/config", r#"
+            [target.'cfg(not(target_os = "no-such-os"))']
+            runner = "first-runner"
+
+            [target.'cfg(target_os = "no-such-os")']
+            runner = "unused-runner"
+
+            [target.'cfg(not(target_arch = "no-such-arch"))']
+            runner = "second-runner"
+        "#);
+
+    foo.build();
+
+    assert_that(foo.baler("run"),
+                execs().with_status(101)
+                       .with_stderr_contains(&format!("\
+[ERROR] several runners are configured for the target `{}`, but only one can be used:
+  `target.cfg(not(target_arch = \"no-such-arch\")).runner` in [..]config
+  `target.cfg(not(target_os = \"no-such-os\")).runner` in [..]config", rustc_host())));
+}